@@ -7,19 +7,37 @@ layout(location = 0) out vec4 vFragColor;
 smooth in vec3 vUV;
 
 uniform sampler3D volume;
+// The 2D transfer function shader is this shader with TRANSFER_FUNCTION_2D defined, which looks up
+// the colour by value and gradient magnitude instead of by value alone.
+#ifdef TRANSFER_FUNCTION_2D
+// Gradient magnitude of every voxel, normalised to the gradient range of the volume.
+uniform sampler3D gradient;
+// Colour and opacity over the value range along x and the gradient range along y, with
+// LOOKUP_TABLE_SIZE texels on each axis.
+uniform sampler2D transfer_function_2d;
+#else
+// Colour and opacity over the value range, a single row of LOOKUP_TABLE_SIZE texels.
+uniform sampler2D transfer_function;
+#endif
 uniform vec3 cam_pos;
-uniform uint lower_threshold;
-uniform uint upper_threshold;
+uniform vec3 extent;
+uniform float lower_threshold;
+uniform float upper_threshold;
+uniform float value_scale;
+uniform float value_min;
+uniform float value_max;
 
 const int MAX_SAMPLES = 300;
+const float LOOKUP_TABLE_SIZE = 256.0;
 const vec3 MIN_TEX = vec3(0);
 const vec3 MAX_TEX = vec3(1);
+// Distance between samples in world units.
 const float STEP_SIZE = 0.01;
 
 void main() {
     vec3 data_position = vUV;
-    vec3 direction = normalize((vUV - vec3(0.5)) - cam_pos);
-    vec3 dirStep = direction * STEP_SIZE;
+    vec3 direction = normalize((vUV - vec3(0.5)) * extent - cam_pos);
+    vec3 dirStep = direction * STEP_SIZE / extent;
     bool stop = false;
 
     for (int i = 0; i < MAX_SAMPLES; i++) {
@@ -30,14 +48,27 @@ void main() {
         if (stop)
             break;
 
-        float value = texture(volume, data_position).r;
-
-        float scaled_value = value * 255.0;
-        if (scaled_value < float(lower_threshold) || scaled_value > float(upper_threshold))
+        float native_value = texture(volume, data_position).r * value_scale;
+        if (native_value < lower_threshold || native_value > upper_threshold)
             continue;
 
-        float prev_alpha = value - (value * vFragColor.a);
-        vFragColor.rgb = prev_alpha * vec3(value) + vFragColor.rgb;
+        float value = clamp((native_value - value_min) / (value_max - value_min), 0.0, 1.0);
+
+#ifdef TRANSFER_FUNCTION_2D
+        float gradient_magnitude = texture(gradient, data_position).r;
+
+        // Sample the centres of the first and last texels at the ends of the ranges.
+        vec2 lookup = (vec2(value, gradient_magnitude) * (LOOKUP_TABLE_SIZE - 1.0) + 0.5)
+            / LOOKUP_TABLE_SIZE;
+        vec4 color = texture(transfer_function_2d, lookup);
+#else
+        // Sample the centres of the first and last texel at the ends of the value range.
+        float lookup = (value * (LOOKUP_TABLE_SIZE - 1.0) + 0.5) / LOOKUP_TABLE_SIZE;
+        vec4 color = texture(transfer_function, vec2(lookup, 0.5));
+#endif
+
+        float prev_alpha = color.a - (color.a * vFragColor.a);
+        vFragColor.rgb = prev_alpha * color.rgb + vFragColor.rgb;
         vFragColor.a += prev_alpha;
 
         if (vFragColor.a > 0.99)
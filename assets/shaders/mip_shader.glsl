@@ -8,18 +8,23 @@ smooth in vec3 vUV;
 
 uniform sampler3D volume;
 uniform vec3 cam_pos;
-uniform uint lower_threshold;
-uniform uint upper_threshold;
+uniform vec3 extent;
+uniform float lower_threshold;
+uniform float upper_threshold;
+uniform float value_scale;
+uniform float value_min;
+uniform float value_max;
 
 const int MAX_SAMPLES = 2000;
 const vec3 MIN_TEX = vec3(0);
 const vec3 MAX_TEX = vec3(1);
+// Distance between samples in world units.
 const float STEP_SIZE = 0.001;
 
 void main() {
     vec3 data_position = vUV;
-    vec3 direction = normalize((vUV - vec3(0.5)) - cam_pos);
-    vec3 step = direction * STEP_SIZE;
+    vec3 direction = normalize((vUV - vec3(0.5)) * extent - cam_pos);
+    vec3 step = direction * STEP_SIZE / extent;
 
     float max_value = 0.0;
     bool stop = false;
@@ -33,12 +38,12 @@ void main() {
             break;
         }
 
-        float value = texture(volume, data_position).r;
-
-        float scaled_value = value * 255.0;
-        if (scaled_value < float(lower_threshold) || scaled_value > float(upper_threshold))
+        float native_value = texture(volume, data_position).r * value_scale;
+        if (native_value < lower_threshold || native_value > upper_threshold)
             continue;
 
+        float value = clamp((native_value - value_min) / (value_max - value_min), 0.0, 1.0);
+
         if (value > max_value) {
             max_value = value;
         }
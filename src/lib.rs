@@ -1,21 +1,30 @@
 #![allow(special_module_name)]
 pub mod renderer;
 pub mod shader;
+pub mod transfer_function;
 pub mod ui;
 pub mod uniform;
 pub mod volume;
 
+use std::cell::RefCell;
 use std::ffi::OsString;
+use std::path::Path;
 
-use crate::renderer::Renderer;
+use crate::renderer::{
+    Renderer, GRADIENT_TEXTURE_UNIT, TRANSFER_FUNCTION_2D_TEXTURE_UNIT,
+    TRANSFER_FUNCTION_TEXTURE_UNIT, VOLUME_TEXTURE_UNIT,
+};
 use crate::shader::Shader;
 use crate::shader::ShaderType;
+use crate::transfer_function::preset::Preset;
 use crate::ui::UserInterface;
+use crate::volume::memory::MemoryFiles;
 use three_d::*;
 
 // Entry point for wasm
 use wasm_bindgen::prelude::*;
 
+#[cfg(target_arch = "wasm32")]
 fn init_wasm() {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
     console_log::init_with_level(log::Level::Debug).unwrap();
@@ -24,6 +33,53 @@ fn init_wasm() {
     info!("Logging works!");
 }
 
+thread_local! {
+    /// Files passed in by JavaScript or picked in the browser, which are opened on the next frame.
+    static OPENED_FILES: RefCell<Vec<MemoryFiles>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Queues in-memory files to be opened as a volume on the next frame.
+pub fn open_files(files: MemoryFiles) {
+    OPENED_FILES.with(|opened| opened.borrow_mut().push(files));
+}
+
+thread_local! {
+    /// Names and contents of preset files picked in the browser, which are read on the next frame.
+    static OPENED_PRESETS: RefCell<Vec<(String, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Queues a transfer function preset file to be read on the next frame.
+pub fn open_presets(name: String, data: Vec<u8>) {
+    OPENED_PRESETS.with(|opened| opened.borrow_mut().push((name, data)));
+}
+
+/// Files that are opened together from JavaScript, e.g. the files dropped onto the page or
+/// fetched as ArrayBuffers.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct VolumeFiles {
+    files: MemoryFiles,
+}
+
+#[wasm_bindgen]
+impl VolumeFiles {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> VolumeFiles {
+        VolumeFiles::default()
+    }
+
+    /// Adds a file. The name is used to detect the format and to find detached data files.
+    pub fn add(&mut self, name: &str, data: Vec<u8>) {
+        self.files.push(name, data);
+    }
+}
+
+/// Opens the files as a volume, replacing the current one.
+#[wasm_bindgen]
+pub fn open_volume(files: VolumeFiles) {
+    open_files(files.files);
+}
+
 #[wasm_bindgen(start)]
 pub fn start() -> Result<(), JsValue> {
     #[cfg(target_arch = "wasm32")]
@@ -41,6 +97,16 @@ pub fn start() -> Result<(), JsValue> {
 
     let mut gui = three_d::GUI::new(&renderer.gl.clone());
     window.render_loop(move |mut frame_input| {
+        for files in OPENED_FILES.with(|opened| opened.take()) {
+            renderer.open_memory(files);
+        }
+        for (name, data) in OPENED_PRESETS.with(|opened| opened.take()) {
+            let presets = Preset::parse(Path::new(&name), &String::from_utf8_lossy(&data));
+            if renderer.scene.add_presets(presets) {
+                renderer.update_transfer_texture();
+            }
+        }
+        renderer.poll_loading();
         let mut panel_width = 0.0;
         gui.update(
             &mut frame_input.events.clone(),
@@ -51,7 +117,38 @@ pub fn start() -> Result<(), JsValue> {
                 use three_d::egui::*;
                 CentralPanel::default().show(gui_context, |ui| {
                     ui.vertical(|ui| {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if let Some(path) = UserInterface::render_file_menu(ui) {
+                            renderer.open(&path);
+                        }
+                        #[cfg(target_arch = "wasm32")]
+                        UserInterface::render_file_menu(ui);
+                        if UserInterface::render_loading(ui, &renderer.scene) {
+                            renderer.cancel_loading();
+                        }
+                        UserInterface::render_load_error(ui, &renderer.scene);
+                        if let Some(index) =
+                            UserInterface::render_series_picker(ui, &renderer.scene)
+                        {
+                            renderer.load_series(index);
+                        }
+                        if UserInterface::render_resampling(ui, &mut renderer.scene) {
+                            renderer.resample_isotropic();
+                        }
                         UserInterface::render_controls(ui, &mut renderer.scene);
+                        if renderer.scene.shader_type == ShaderType::TransferFunction2DShader {
+                            renderer.prepare_transfer_function_2d();
+                            if UserInterface::render_transfer_function_2d(ui, &mut renderer.scene) {
+                                renderer.update_transfer_texture_2d();
+                            }
+                        } else {
+                            if UserInterface::render_presets(ui, &mut renderer.scene) {
+                                renderer.update_transfer_texture();
+                            }
+                            if UserInterface::render_transfer_function(ui, &mut renderer.scene) {
+                                renderer.update_transfer_texture();
+                            }
+                        }
                         // UserInterface::render_histogram(ui, &renderer.scene.volume);
                     });
                     Frame::canvas(&Style {
@@ -80,6 +177,9 @@ pub fn start() -> Result<(), JsValue> {
 
                         // Create local variables to ensure thread safety.
                         let texture = renderer.texture;
+                        let transfer_texture = renderer.transfer_texture;
+                        let gradient_texture = renderer.gradient_texture;
+                        let transfer_texture_2d = renderer.transfer_texture_2d;
                         let vao = renderer.vao;
                         let indices_length = renderer.scene.volume.indices.len();
                         let uniforms = renderer.calculate_uniforms();
@@ -87,18 +187,8 @@ pub fn start() -> Result<(), JsValue> {
                         // TODO: Fix path
                         let shader_path = OsString::from("assets/shaders/");
 
-                        let shader_file = match renderer.scene.shader_type {
-                            ShaderType::DefaultShader => "cookbook_shader.glsl",
-                            ShaderType::MipShader => "mip_shader.glsl",
-                            ShaderType::AipShader => "aip_shader.glsl",
-                        };
-                        let mut fragment_shader = shader_path.clone();
-                        fragment_shader.push(&shader_file);
-
-                        let mut vertex_shader = shader_path.clone();
-                        vertex_shader.push("vertex_shader.glsl");
-
-                        let shaders = Shader::load_from_file(vertex_shader, fragment_shader);
+                        let shaders =
+                            Shader::load_from_file(shader_path, renderer.rendered_shader_type());
 
                         let callback = egui::PaintCallback {
                             rect,
@@ -118,9 +208,31 @@ pub fn start() -> Result<(), JsValue> {
                                     shaders.delete_shader(painter.gl(), vs);
                                     shaders.delete_shader(painter.gl(), fs);
                                     shaders.use_program(painter.gl(), program);
-                                    Renderer::set_uniform_values(&uniforms, &painter.gl(), program);
+                                    Renderer::set_uniform_values(&uniforms, painter.gl(), program);
 
                                     unsafe {
+                                        painter.gl().active_texture(
+                                            glow::TEXTURE0
+                                                + TRANSFER_FUNCTION_2D_TEXTURE_UNIT as u32,
+                                        );
+                                        painter
+                                            .gl()
+                                            .bind_texture(glow::TEXTURE_2D, transfer_texture_2d);
+                                        painter.gl().active_texture(
+                                            glow::TEXTURE0 + GRADIENT_TEXTURE_UNIT as u32,
+                                        );
+                                        painter
+                                            .gl()
+                                            .bind_texture(glow::TEXTURE_3D, gradient_texture);
+                                        painter.gl().active_texture(
+                                            glow::TEXTURE0 + TRANSFER_FUNCTION_TEXTURE_UNIT as u32,
+                                        );
+                                        painter
+                                            .gl()
+                                            .bind_texture(glow::TEXTURE_2D, transfer_texture);
+                                        painter.gl().active_texture(
+                                            glow::TEXTURE0 + VOLUME_TEXTURE_UNIT as u32,
+                                        );
                                         painter.gl().bind_texture(glow::TEXTURE_3D, texture);
                                         painter.gl().bind_vertex_array(vao);
                                         painter.gl().draw_elements(
@@ -137,6 +249,7 @@ pub fn start() -> Result<(), JsValue> {
                             )),
                         };
                         ui.painter().add(callback);
+                        UserInterface::render_orientation_marker(ui, rect, &renderer.scene.camera);
                     });
                 });
                 panel_width = gui_context.used_rect().width();
@@ -1,16 +1,54 @@
 use crate::shader::{Shader, ShaderType};
-use crate::volume::Volume;
+use crate::transfer_function::preset::{Preset, PresetError};
+use crate::transfer_function::two_dimensional::TransferFunction2D;
+use crate::transfer_function::{TransferFunction, LOOKUP_TABLE_SIZE};
+use crate::volume::dicom_series::DicomSeries;
+use crate::volume::gradient::GradientVolume;
+use crate::volume::loading::{LoadKind, LoadRequest, VolumeLoadTask};
+use crate::volume::memory::MemoryFiles;
+use crate::volume::resample::{fitting_spacing, isotropic_spacing, Interpolation};
+use crate::volume::{Volume, VolumeError, VoxelData};
 use glow::{Buffer, HasContext, Texture, VertexArray};
-use nalgebra::Matrix4;
-use std::{mem, sync::Arc};
+use nalgebra::{Matrix3, Matrix4, Vector3};
+use std::path::Path;
+use std::{borrow::Cow, mem};
+use three_d::egui::TextureHandle;
 use three_d::{degrees, Camera, Context, Viewport};
 
+/// Dataset that is opened on start-up. The browser has no file system, so there the volume is
+/// passed in by JavaScript or opened from the File menu.
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_VOLUME_PATH: &str = "assets/data/DCM_0000/";
+/// Camera position from which the volume, centred at the origin, is viewed after loading. World
+/// coordinates are LPS patient coordinates, so the camera looks at the patient from the front
+/// with the head up.
+const CAMERA_POSITION: three_d_asset::Vector3<f32> = three_d_asset::Vector3::new(0.0, -2.5, 0.0);
+const CAMERA_TARGET: three_d_asset::Vector3<f32> = three_d_asset::Vector3::new(0.0, 0.0, 0.0);
+const CAMERA_UP: three_d_asset::Vector3<f32> = three_d_asset::Vector3::new(0.0, 0.0, 1.0);
+
+/// Texture units the shaders sample the volume, the gradient magnitudes and the transfer functions
+/// from.
+pub const VOLUME_TEXTURE_UNIT: i32 = 0;
+pub const TRANSFER_FUNCTION_TEXTURE_UNIT: i32 = 1;
+pub const GRADIENT_TEXTURE_UNIT: i32 = 2;
+pub const TRANSFER_FUNCTION_2D_TEXTURE_UNIT: i32 = 3;
+
 pub struct Renderer {
-    pub gl: Arc<three_d::Context>,
+    pub gl: three_d::Context,
     pub vbo: Option<Buffer>,
     pub vao: Option<VertexArray>,
     pub ebo: Option<Buffer>,
     pub texture: Option<Texture>,
+    /// Lookup table of the transfer function, a strip of RGBA texels over the value range.
+    pub transfer_texture: Option<Texture>,
+    /// Gradient magnitudes of the volume in 8 bits.
+    pub gradient_texture: Option<Texture>,
+    /// Lookup table of the 2D transfer function, a square of RGBA texels over the value and
+    /// gradient ranges.
+    pub transfer_texture_2d: Option<Texture>,
+    /// GL_MAX_3D_TEXTURE_SIZE of the GPU. Larger volumes are downsampled while they are read or
+    /// resampled.
+    pub max_texture_size: usize,
     pub scene: Scene,
 }
 
@@ -18,23 +56,134 @@ pub struct Scene {
     pub volume: Volume,
     pub camera: Camera,
     pub shader_type: ShaderType,
-    pub lower_threshold: u8,
-    pub upper_threshold: u8,
+    pub lower_threshold: f32,
+    pub upper_threshold: f32,
+    /// Colour and opacity of the voxel values in the default shader.
+    pub transfer_function: TransferFunction,
+    /// Colour and opacity of the voxel values and gradient magnitudes in the 2D transfer function
+    /// shader, which is created when the shader is first chosen for a volume.
+    pub transfer_function_2d: Option<TransferFunction2D>,
+    /// Joint histogram of the volume as an image for the user interface, which is created when
+    /// it is first shown.
+    pub joint_histogram_image: Option<TextureHandle>,
+    /// Built-in presets followed by the presets loaded from files.
+    pub presets: Vec<Preset>,
+    /// Error of the last preset file that could not be read or saved.
+    pub preset_error: Option<PresetError>,
+    /// Error of the last volume load, which is shown in the user interface.
+    pub load_error: Option<VolumeError>,
+    /// Files that were left out of the loaded series because they could not be read.
+    pub skipped_files: Vec<VolumeError>,
+    /// Series found in the opened DICOM directory, which can be picked in the user interface.
+    pub series: Vec<DicomSeries>,
+    pub selected_series: Option<usize>,
+    /// Load running in the background, whose volume replaces the current one once it finishes.
+    pub loading: Option<VolumeLoadTask>,
+    /// Interpolation used when the volume is resampled.
+    pub interpolation: Interpolation,
+}
+
+impl Scene {
+    /// Adds the presets read from a file and applies the first of them, or keeps the error to show
+    /// it in the user interface. Returns true if the transfer function changed.
+    pub fn add_presets(&mut self, presets: Result<Vec<Preset>, PresetError>) -> bool {
+        match presets {
+            Ok(presets) => {
+                self.preset_error = None;
+                let Some(first) = presets.first() else {
+                    return false;
+                };
+                self.transfer_function = first.transfer_function();
+                self.presets.extend(presets);
+                true
+            }
+            Err(error) => {
+                self.preset_error = Some(error);
+                false
+            }
+        }
+    }
 }
 
 pub struct Uniforms {
-    pub cam_pos: three_d_asset::Vector3<f32>,
+    /// Camera position along the axes of the volume, which is where the rays are cast.
+    pub cam_pos: Vector3<f32>,
     pub model_matrix: Matrix4<f32>,
+    /// Scale of the unit cube along each axis, so that the volume has its physical proportions.
+    pub extent: Vector3<f32>,
     pub view_matrix: three_d_asset::Matrix4<f32>,
     pub projection_matrix: three_d_asset::Matrix4<f32>,
-    pub lower_threshold: u8,
-    pub upper_threshold: u8,
+    pub lower_threshold: f32,
+    pub upper_threshold: f32,
+    pub value_scale: f32,
+    pub value_min: f32,
+    pub value_max: f32,
+}
+
+/// OpenGL format used to upload the voxel data to the 3D texture.
+#[derive(Debug, PartialEq)]
+pub struct TextureFormat {
+    pub internal_format: u32,
+    pub format: u32,
+    pub data_type: u32,
+    /// Factor that turns a sampled texture value back into the native voxel value, as normalized
+    /// integer formats are sampled in [0, 1] or [-1, 1].
+    pub value_scale: f32,
+}
+
+impl TextureFormat {
+    pub fn for_voxel_data(texture_data: &VoxelData) -> TextureFormat {
+        let (internal_format, data_type, value_scale) = match texture_data {
+            VoxelData::U8(_) => (glow::R8, glow::UNSIGNED_BYTE, u8::MAX as f32),
+            // WebGL2 has no 16-bit normalized formats and cannot filter R32F without
+            // OES_texture_float_linear, so 16-bit and float data are converted to half floats.
+            #[cfg(target_arch = "wasm32")]
+            VoxelData::I16(_) | VoxelData::U16(_) | VoxelData::F32(_) => {
+                (glow::R16F, glow::HALF_FLOAT, 1.0)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            VoxelData::I16(_) => (glow::R16_SNORM, glow::SHORT, i16::MAX as f32),
+            #[cfg(not(target_arch = "wasm32"))]
+            VoxelData::U16(_) => (glow::R16, glow::UNSIGNED_SHORT, u16::MAX as f32),
+            #[cfg(not(target_arch = "wasm32"))]
+            VoxelData::F32(_) => (glow::R32F, glow::FLOAT, 1.0),
+        };
+        TextureFormat {
+            internal_format,
+            format: glow::RED,
+            data_type,
+            value_scale,
+        }
+    }
+
+    pub fn texture_bytes(texture_data: &VoxelData) -> Cow<'_, [u8]> {
+        match texture_data {
+            VoxelData::U8(data) => Cow::Borrowed(data),
+            #[cfg(target_arch = "wasm32")]
+            VoxelData::I16(data) => Cow::Owned(half_float_bytes(data)),
+            #[cfg(target_arch = "wasm32")]
+            VoxelData::U16(data) => Cow::Owned(half_float_bytes(data)),
+            #[cfg(not(target_arch = "wasm32"))]
+            VoxelData::I16(data) => Cow::Borrowed(bytemuck::cast_slice(data)),
+            #[cfg(not(target_arch = "wasm32"))]
+            VoxelData::U16(data) => Cow::Borrowed(bytemuck::cast_slice(data)),
+            #[cfg(target_arch = "wasm32")]
+            VoxelData::F32(data) => Cow::Owned(half_float_bytes(data)),
+            #[cfg(not(target_arch = "wasm32"))]
+            VoxelData::F32(data) => Cow::Borrowed(bytemuck::cast_slice(data)),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn half_float_bytes<T: Copy + Into<f32>>(data: &[T]) -> Vec<u8> {
+    data.iter()
+        .flat_map(|&value| three_d::f16::from_f32(value.into()).to_ne_bytes())
+        .collect()
 }
 
 impl Renderer {
     pub fn new(context: Context) -> Self {
-        let arc_context = Arc::new(context.clone());
-
         let camera = Camera::new_perspective(
             Viewport {
                 x: 0,
@@ -42,34 +191,224 @@ impl Renderer {
                 width: 0,
                 height: 0,
             },
-            three_d_asset::Vector3::new(0.0, 0.0, -2.5),
-            three_d_asset::Vector3::new(0.0, 0.0, 0.0),
-            three_d_asset::Vector3::new(0.0, 1.0, 0.0),
+            CAMERA_POSITION,
+            CAMERA_TARGET,
+            CAMERA_UP,
             degrees(45.0),
             0.1,
             100.0,
         );
 
+        let volume = Volume::new();
+        let value_range = volume.value_range();
+
+        let max_texture_size =
+            unsafe { context.get_parameter_i32(glow::MAX_3D_TEXTURE_SIZE) }.max(1) as usize;
         let mut renderer = Renderer {
-            gl: arc_context,
+            gl: context,
             vao: None,
             vbo: None,
             ebo: None,
             texture: None,
+            transfer_texture: None,
+            gradient_texture: None,
+            transfer_texture_2d: None,
+            max_texture_size,
             scene: Scene {
-                volume: Volume::new(),
+                volume,
                 camera,
                 shader_type: ShaderType::DefaultShader,
-                lower_threshold: 0,
-                upper_threshold: 255,
+                lower_threshold: value_range.min,
+                upper_threshold: value_range.max,
+                transfer_function: TransferFunction::ramp(value_range),
+                transfer_function_2d: None,
+                joint_histogram_image: None,
+                presets: Preset::built_in(),
+                preset_error: None,
+                load_error: None,
+                skipped_files: Vec::new(),
+                series: Vec::new(),
+                selected_series: None,
+                loading: None,
+                interpolation: Interpolation::default(),
             },
         };
         renderer.create_vao();
         renderer.create_vbo();
         renderer.create_ebo();
         renderer.create_texture();
+        renderer.update_transfer_texture();
+        #[cfg(not(target_arch = "wasm32"))]
+        renderer.open(Path::new(DEFAULT_VOLUME_PATH));
         renderer
     }
+
+    /// Starts opening a volume file or directory in the background. The series of a DICOM
+    /// directory, DICOMDIR or archive are listed in the scene, and the series with the most slices is
+    /// loaded. A load that is still running is cancelled.
+    pub fn open(&mut self, path: &Path) {
+        self.scene.series.clear();
+        self.scene.selected_series = None;
+        self.scene.loading = Some(VolumeLoadTask::spawn(LoadRequest::Open(
+            path.to_path_buf(),
+            self.max_texture_size,
+        )));
+    }
+
+    /// Starts reading in-memory files in the background, e.g. files opened in the browser.
+    pub fn open_memory(&mut self, files: MemoryFiles) {
+        self.scene.series.clear();
+        self.scene.selected_series = None;
+        self.scene.loading = Some(VolumeLoadTask::spawn(LoadRequest::Memory(files)));
+    }
+
+    /// Starts loading one of the series found by [Renderer::open] in the background. Files that
+    /// cannot be decoded are left out and listed in the scene.
+    pub fn load_series(&mut self, index: usize) {
+        self.scene.selected_series = Some(index);
+        let series = self.scene.series[index].clone();
+        self.scene.loading = Some(VolumeLoadTask::spawn(LoadRequest::Series(series)));
+    }
+
+    /// Starts resampling the volume to cubic voxels in the background, so that anisotropic
+    /// volumes such as CT series with thick slices are rendered without stretched voxels.
+    pub fn resample_isotropic(&mut self) {
+        let texture = self.scene.volume.texture.clone();
+        let spacing = fitting_spacing(
+            &texture,
+            isotropic_spacing(texture.spacing),
+            self.max_texture_size,
+        );
+        self.scene.loading = Some(VolumeLoadTask::spawn(LoadRequest::Resample(
+            texture,
+            spacing,
+            self.scene.interpolation,
+        )));
+    }
+
+    /// Cancels the running load and keeps the current volume. The 2D transfer function shader is
+    /// left if its gradient magnitudes were still being computed, as it cannot be shown without
+    /// them.
+    pub fn cancel_loading(&mut self) {
+        if let Some(task) = self.scene.loading.take() {
+            task.cancel();
+            log::info!("Volume loading cancelled");
+        }
+        if self.scene.shader_type == ShaderType::TransferFunction2DShader
+            && self.scene.volume.gradient.is_none()
+        {
+            self.scene.shader_type = ShaderType::DefaultShader;
+        }
+    }
+
+    /// Shader the volume is drawn with. The default shader stands in for the 2D transfer function
+    /// shader while the gradient magnitudes are computed.
+    pub fn rendered_shader_type(&self) -> &ShaderType {
+        match self.scene.shader_type {
+            ShaderType::TransferFunction2DShader if self.gradient_texture.is_none() => {
+                &ShaderType::DefaultShader
+            }
+            ref shader_type => shader_type,
+        }
+    }
+
+    /// Swaps in the volume of a finished load. Called once per frame, as the texture can only be
+    /// uploaded on the thread that owns the OpenGL context.
+    pub fn poll_loading(&mut self) {
+        let Some(outcome) = self
+            .scene
+            .loading
+            .as_ref()
+            .and_then(VolumeLoadTask::try_take)
+        else {
+            return;
+        };
+        self.scene.loading = None;
+        if let Some((series, selected_series)) = outcome.series {
+            self.scene.series = series;
+            self.scene.selected_series = selected_series;
+        }
+        match outcome.result {
+            Err(VolumeError::Cancelled) => log::info!("Volume loading cancelled"),
+            Ok(volume) if outcome.kind == LoadKind::Resample => self.replace_volume(volume),
+            Ok(volume) if outcome.kind == LoadKind::Gradient => self.set_gradient(volume.gradient),
+            result => {
+                self.set_load_result(result);
+                self.scene.skipped_files = outcome.skipped_files;
+            }
+        }
+    }
+
+    fn set_load_result(&mut self, result: Result<Volume, VolumeError>) {
+        match result {
+            Ok(volume) => self.set_volume(volume),
+            Err(error) => {
+                log::error!("Cannot load volume: {}", error);
+                self.scene.load_error = Some(error);
+            }
+        }
+    }
+
+    /// Replaces the volume of the scene, resets the thresholds and the transfer functions to its
+    /// value range, recentres the camera and uploads the volume to a new texture.
+    pub fn set_volume(&mut self, volume: Volume) {
+        let value_range = volume.value_range();
+        self.scene.lower_threshold = value_range.min;
+        self.scene.upper_threshold = value_range.max;
+        self.scene.transfer_function = TransferFunction::ramp(value_range);
+        self.scene.transfer_function_2d = None;
+        self.scene.skipped_files.clear();
+        self.scene
+            .camera
+            .set_view(CAMERA_POSITION, CAMERA_TARGET, CAMERA_UP);
+        self.replace_volume(volume);
+    }
+
+    /// Replaces the volume of the scene with a resample of it and uploads it to a new texture. The
+    /// thresholds, transfer functions and camera are kept.
+    pub fn replace_volume(&mut self, volume: Volume) {
+        self.scene.volume = volume;
+        self.scene.joint_histogram_image = None;
+        self.scene.load_error = None;
+        for texture in [self.texture.take(), self.gradient_texture.take()]
+            .into_iter()
+            .flatten()
+        {
+            unsafe {
+                self.gl.delete_texture(texture);
+            }
+        }
+        self.create_texture();
+        self.update_transfer_texture();
+    }
+
+    /// Starts computing the gradient magnitudes of the volume in the background, unless they have
+    /// been computed since the volume was loaded or another load is running. Called while the 2D
+    /// transfer function shader is chosen.
+    pub fn prepare_transfer_function_2d(&mut self) {
+        if self.scene.volume.gradient.is_some() || self.scene.loading.is_some() {
+            return;
+        }
+        self.scene.loading = Some(VolumeLoadTask::spawn(LoadRequest::Gradient(
+            self.scene.volume.texture.clone(),
+        )));
+    }
+
+    /// Stores the gradient magnitudes computed for the volume and uploads them together with the
+    /// 2D transfer function.
+    fn set_gradient(&mut self, gradient: Option<GradientVolume>) {
+        let Some(gradient) = gradient else {
+            return;
+        };
+        let value_range = self.scene.volume.value_range();
+        let gradient_range = gradient.magnitude_range();
+        self.scene.volume.gradient = Some(gradient);
+        self.scene
+            .transfer_function_2d
+            .get_or_insert_with(|| TransferFunction2D::boundaries(value_range, gradient_range));
+        self.create_gradient_texture();
+        self.update_transfer_texture_2d();
+    }
     pub fn create_vao(&mut self) {
         unsafe {
             self.vao = self.gl.create_vertex_array().ok();
@@ -137,41 +476,176 @@ impl Renderer {
                 glow::CLAMP_TO_EDGE as i32,
             );
 
+            let texture_data = &self.scene.volume.texture.texture_data;
+            let format = TextureFormat::for_voxel_data(texture_data);
+            self.gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
             self.gl.tex_image_3d(
                 glow::TEXTURE_3D,
                 0,
-                glow::R8 as i32,
+                format.internal_format as i32,
                 self.scene.volume.texture.dimensions.width,
                 self.scene.volume.texture.dimensions.height,
                 self.scene.volume.texture.dimensions.depth,
                 0,
+                format.format,
+                format.data_type,
+                Some(&TextureFormat::texture_bytes(texture_data)),
+            );
+        }
+    }
+
+    /// Uploads the gradient magnitudes of the volume, which have the same dimensions as the
+    /// volume.
+    pub fn create_gradient_texture(&mut self) {
+        let Some(gradient) = &self.scene.volume.gradient else {
+            return;
+        };
+        let dimensions = self.scene.volume.texture.dimensions;
+        unsafe {
+            self.gradient_texture = self.gl.create_texture().ok();
+            self.gl
+                .bind_texture(glow::TEXTURE_3D, self.gradient_texture);
+            for (parameter, value) in [
+                (glow::TEXTURE_MIN_FILTER, glow::LINEAR),
+                (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+                (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+                (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+                (glow::TEXTURE_WRAP_R, glow::CLAMP_TO_EDGE),
+            ] {
+                self.gl
+                    .tex_parameter_i32(glow::TEXTURE_3D, parameter, value as i32);
+            }
+            self.gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            self.gl.tex_image_3d(
+                glow::TEXTURE_3D,
+                0,
+                glow::R8 as i32,
+                dimensions.width,
+                dimensions.height,
+                dimensions.depth,
+                0,
                 glow::RED,
                 glow::UNSIGNED_BYTE,
-                Some(bytemuck::cast_slice(
-                    &self.scene.volume.texture.texture_data,
-                )),
+                Some(&gradient.magnitudes),
             );
         }
     }
 
+    /// Uploads the lookup table of the transfer function, e.g. after it has been edited. WebGL has
+    /// no 1D textures, so the table is stored as a 2D texture with a single row.
+    pub fn update_transfer_texture(&mut self) {
+        let table = self
+            .scene
+            .transfer_function
+            .lookup_table(self.scene.volume.value_range(), LOOKUP_TABLE_SIZE);
+        unsafe {
+            if self.transfer_texture.is_none() {
+                self.transfer_texture = self.create_lookup_texture();
+            }
+            self.gl
+                .bind_texture(glow::TEXTURE_2D, self.transfer_texture);
+            self.gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            self.gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA8 as i32,
+                LOOKUP_TABLE_SIZE as i32,
+                1,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                Some(&table),
+            );
+        }
+    }
+
+    /// Uploads the lookup table of the 2D transfer function, with the values along x and the
+    /// gradient magnitudes along y.
+    pub fn update_transfer_texture_2d(&mut self) {
+        let (Some(transfer_function), Some(gradient)) = (
+            &self.scene.transfer_function_2d,
+            &self.scene.volume.gradient,
+        ) else {
+            return;
+        };
+        let table = transfer_function.lookup_table(
+            self.scene.volume.value_range(),
+            gradient.magnitude_range(),
+            LOOKUP_TABLE_SIZE,
+        );
+        unsafe {
+            if self.transfer_texture_2d.is_none() {
+                self.transfer_texture_2d = self.create_lookup_texture();
+            }
+            self.gl
+                .bind_texture(glow::TEXTURE_2D, self.transfer_texture_2d);
+            self.gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            self.gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA8 as i32,
+                LOOKUP_TABLE_SIZE as i32,
+                LOOKUP_TABLE_SIZE as i32,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                Some(&table),
+            );
+        }
+    }
+
+    /// Creates a linearly filtered 2D texture for a lookup table.
+    unsafe fn create_lookup_texture(&self) -> Option<Texture> {
+        let texture = self.gl.create_texture().ok();
+        self.gl.bind_texture(glow::TEXTURE_2D, texture);
+        for (parameter, value) in [
+            (glow::TEXTURE_MIN_FILTER, glow::LINEAR),
+            (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+            (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+            (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+        ] {
+            self.gl
+                .tex_parameter_i32(glow::TEXTURE_2D, parameter, value as i32);
+        }
+        texture
+    }
+
     pub fn calculate_uniforms(&self) -> Uniforms {
-        let cam_pos = *self.scene.camera.position();
+        let position = self.scene.camera.position();
+        let value_range = self.scene.volume.value_range();
+        let format = TextureFormat::for_voxel_data(&self.scene.volume.texture.texture_data);
+        let extent = Vector3::from(self.scene.volume.extent());
+        let rotation = self.volume_rotation();
+        let inverse_rotation = rotation.try_inverse().unwrap_or_else(Matrix3::identity);
         Uniforms {
-            cam_pos,
-            model_matrix: Matrix4::identity(),
+            cam_pos: inverse_rotation * Vector3::new(position.x, position.y, position.z),
+            model_matrix: rotation.to_homogeneous() * Matrix4::new_nonuniform_scaling(&extent),
+            extent,
             view_matrix: *self.scene.camera.view(),
             projection_matrix: *self.scene.camera.projection(),
             lower_threshold: self.scene.lower_threshold,
             upper_threshold: self.scene.upper_threshold,
+            value_scale: format.value_scale,
+            value_min: value_range.min,
+            value_max: value_range.max,
         }
     }
 
+    /// Rotates the axes of the volume onto their directions in patient coordinates. The origin of
+    /// the volume is left out, so the volume stays centred for the camera.
+    fn volume_rotation(&self) -> Matrix3<f32> {
+        let directions = self.scene.volume.texture.orientation.directions;
+        let column = |axis: usize| directions[axis].map(|value| value as f32).into();
+        Matrix3::from_columns(&[column(0), column(1), column(2)])
+    }
+
     pub fn set_uniform_values(
         uniforms: &Uniforms,
         context: &glow::Context,
         program: glow::Program,
     ) {
         Shader::set_uniform_value(context, program, "M", uniforms.model_matrix);
+        Shader::set_uniform_value(context, program, "extent", uniforms.extent);
 
         Shader::set_uniform_value(context, program, "cam_pos", uniforms.cam_pos);
         Shader::set_uniform_value(context, program, "V", uniforms.view_matrix);
@@ -188,5 +662,22 @@ impl Renderer {
             "upper_threshold",
             uniforms.upper_threshold,
         );
+        Shader::set_uniform_value(context, program, "value_scale", uniforms.value_scale);
+        Shader::set_uniform_value(context, program, "value_min", uniforms.value_min);
+        Shader::set_uniform_value(context, program, "value_max", uniforms.value_max);
+        Shader::set_uniform_value(context, program, "volume", VOLUME_TEXTURE_UNIT);
+        Shader::set_uniform_value(
+            context,
+            program,
+            "transfer_function",
+            TRANSFER_FUNCTION_TEXTURE_UNIT,
+        );
+        Shader::set_uniform_value(context, program, "gradient", GRADIENT_TEXTURE_UNIT);
+        Shader::set_uniform_value(
+            context,
+            program,
+            "transfer_function_2d",
+            TRANSFER_FUNCTION_2D_TEXTURE_UNIT,
+        );
     }
 }
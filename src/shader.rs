@@ -12,21 +12,88 @@ pub enum ShaderType {
     DefaultShader,
     MipShader,
     AipShader,
+    /// Composites like the default shader with a transfer function over the value and the
+    /// gradient magnitude.
+    TransferFunction2DShader,
+}
+
+impl ShaderType {
+    /// Preprocessor symbols the fragment shader of this type is compiled with.
+    pub fn defines(&self) -> &'static [&'static str] {
+        match self {
+            ShaderType::TransferFunction2DShader => &["TRANSFER_FUNCTION_2D"],
+            _ => &[],
+        }
+    }
+
+    /// Fragment shader file of this type. The 2D transfer function shader is a variant of the
+    /// default shader.
+    pub fn fragment_file(&self) -> &'static str {
+        match self {
+            ShaderType::DefaultShader | ShaderType::TransferFunction2DShader => {
+                "cookbook_shader.glsl"
+            }
+            ShaderType::MipShader => "mip_shader.glsl",
+            ShaderType::AipShader => "aip_shader.glsl",
+        }
+    }
 }
 
 impl Shader {
-    pub fn load_from_file(vertex_path: OsString, fragment_path: OsString) -> Shader {
-        // TODO: Find a better way to handle shaders in WASM
+    /// Loads the shaders of a shader type from a directory and compiles in its defines. The
+    /// browser has no file system, so there the shaders built into the binary are used.
+    pub fn load_from_file(shader_directory: OsString, shader_type: &ShaderType) -> Shader {
         #[cfg(target_arch = "wasm32")]
-        {
-            return Shader {
-                vertex: include_str!("../assets/shaders/vertex_shader.glsl").to_string(),
-                fragment: include_str!("../assets/shaders/mip_shader.glsl").to_string(),
-            };
+        let shader = {
+            let _ = shader_directory;
+            Shader::embedded(shader_type)
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let shader = {
+            let mut vertex_path = shader_directory.clone();
+            vertex_path.push("vertex_shader.glsl");
+            let mut fragment_path = shader_directory;
+            fragment_path.push(shader_type.fragment_file());
+            let vertex = std::fs::read_to_string(vertex_path).expect("Cannot read vertex shader");
+            let fragment =
+                std::fs::read_to_string(fragment_path).expect("Cannot read fragment shader");
+            Shader { vertex, fragment }
+        };
+        shader.with_defines(shader_type.defines())
+    }
+
+    #[cfg(any(target_arch = "wasm32", test))]
+    fn embedded(shader_type: &ShaderType) -> Shader {
+        let fragment = match shader_type {
+            ShaderType::DefaultShader | ShaderType::TransferFunction2DShader => {
+                include_str!("../assets/shaders/cookbook_shader.glsl")
+            }
+            ShaderType::MipShader => include_str!("../assets/shaders/mip_shader.glsl"),
+            ShaderType::AipShader => include_str!("../assets/shaders/aip_shader.glsl"),
+        };
+        Shader {
+            vertex: include_str!("../assets/shaders/vertex_shader.glsl").to_string(),
+            fragment: fragment.to_string(),
         }
-        let vertex = std::fs::read_to_string(vertex_path).expect("Cannot read vertex shader");
-        let fragment = std::fs::read_to_string(fragment_path).expect("Cannot read fragment shader");
-        Shader { vertex, fragment }
+    }
+
+    /// Defines preprocessor symbols at the start of the fragment shader, which select the variant
+    /// of a shader file that is shared by several shader types. They follow the #version
+    /// directive, which has to come first.
+    pub fn with_defines(mut self, defines: &[&str]) -> Shader {
+        if defines.is_empty() {
+            return self;
+        }
+        let (version, body) = self
+            .fragment
+            .split_once('\n')
+            .unwrap_or((&self.fragment, ""));
+        let defines: String = defines
+            .iter()
+            .map(|define| format!("#define {}\n", define))
+            .collect();
+        self.fragment = format!("{}\n{}{}", version, defines, body);
+        self
     }
 
     pub fn get_vertex(&self) -> &str {
@@ -101,3 +168,38 @@ impl Shader {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_with_defines() {
+        let shader = Shader {
+            vertex: String::new(),
+            fragment: "#version 300 es\nvoid main() {}\n".to_string(),
+        };
+        let shader = shader.with_defines(ShaderType::TransferFunction2DShader.defines());
+
+        assert_eq!(
+            "#version 300 es\n#define TRANSFER_FUNCTION_2D\nvoid main() {}\n",
+            shader.get_fragment()
+        );
+    }
+
+    #[test]
+    fn test_embedded_shaders() {
+        let cookbook = include_str!("../assets/shaders/cookbook_shader.glsl");
+
+        for shader_type in [
+            ShaderType::DefaultShader,
+            ShaderType::TransferFunction2DShader,
+        ] {
+            assert_eq!(cookbook, Shader::embedded(&shader_type).get_fragment());
+        }
+        assert_eq!(
+            include_str!("../assets/shaders/mip_shader.glsl"),
+            Shader::embedded(&ShaderType::MipShader).get_fragment()
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::volume::ValueRange;
+use serde::{Deserialize, Serialize};
+
+mod import;
+pub mod preset;
+pub mod two_dimensional;
+
+/// Number of entries of the lookup table that is uploaded to the GPU, which the shaders use to find
+/// the texel centres.
+pub const LOOKUP_TABLE_SIZE: usize = 256;
+
+/// Colour and opacity of the transfer function at a voxel value.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct ControlPoint {
+    /// Voxel value in native units, e.g. Hounsfield units.
+    pub value: f32,
+    /// Red, green, blue and opacity in [0, 1], not premultiplied.
+    pub color: [f32; 4],
+}
+
+/// Piecewise-linear mapping from voxel values to colour and opacity. Values below the first or
+/// above the last control point take the colour of that point.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TransferFunction {
+    points: Vec<ControlPoint>,
+}
+
+impl TransferFunction {
+    pub fn new(mut points: Vec<ControlPoint>) -> Self {
+        points.sort_by(|a, b| a.value.total_cmp(&b.value));
+        TransferFunction { points }
+    }
+
+    /// Grey ramp from transparent black at the lowest value to opaque white at the highest, which
+    /// is what the cookbook shader rendered before it used a transfer function.
+    pub fn ramp(value_range: ValueRange) -> Self {
+        TransferFunction::new(vec![
+            ControlPoint {
+                value: value_range.min,
+                color: [0.0; 4],
+            },
+            ControlPoint {
+                value: value_range.max,
+                color: [1.0; 4],
+            },
+        ])
+    }
+
+    pub fn points(&self) -> &[ControlPoint] {
+        &self.points
+    }
+
+    /// Adds a control point and returns its index.
+    pub fn insert(&mut self, point: ControlPoint) -> usize {
+        let index = self
+            .points
+            .partition_point(|other| other.value <= point.value);
+        self.points.insert(index, point);
+        index
+    }
+
+    /// Removes a control point. The last two points are kept, as they span the function.
+    pub fn remove(&mut self, index: usize) {
+        if self.points.len() > 2 && index < self.points.len() {
+            self.points.remove(index);
+        }
+    }
+
+    /// Moves a control point to another value, keeping it between its neighbours so the points
+    /// stay in order while they are dragged.
+    pub fn set_value(&mut self, index: usize, value: f32) {
+        let min = index
+            .checked_sub(1)
+            .map_or(f32::MIN, |previous| self.points[previous].value);
+        let max = self
+            .points
+            .get(index + 1)
+            .map_or(f32::MAX, |next| next.value);
+        self.points[index].value = value.clamp(min, max);
+    }
+
+    pub fn set_color(&mut self, index: usize, color: [f32; 4]) {
+        self.points[index].color = color.map(|channel| channel.clamp(0.0, 1.0));
+    }
+
+    /// Returns the interpolated colour and opacity at a voxel value.
+    pub fn sample(&self, value: f32) -> [f32; 4] {
+        let Some(first) = self.points.first() else {
+            return [0.0; 4];
+        };
+        let index = self.points.partition_point(|point| point.value <= value);
+        if index == 0 {
+            return first.color;
+        }
+        let Some(next) = self.points.get(index) else {
+            return self.points[index - 1].color;
+        };
+        let previous = &self.points[index - 1];
+        let t = (value - previous.value) / (next.value - previous.value);
+        [0, 1, 2, 3].map(|channel| {
+            previous.color[channel] + (next.color[channel] - previous.color[channel]) * t
+        })
+    }
+
+    /// Samples the function at `size` values spread evenly over the value range, as 8-bit RGBA.
+    pub fn lookup_table(&self, value_range: ValueRange, size: usize) -> Vec<u8> {
+        (0..size)
+            .flat_map(|index| {
+                let position = index as f32 / (size - 1).max(1) as f32;
+                self.sample(value_range.denormalize(position))
+                    .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn point(value: f32, color: [f32; 4]) -> ControlPoint {
+        ControlPoint { value, color }
+    }
+
+    #[test]
+    fn test_sample() {
+        let transfer_function = TransferFunction::new(vec![
+            point(100.0, [1.0, 1.0, 1.0, 1.0]),
+            point(-100.0, [0.0, 0.0, 0.0, 0.0]),
+            point(0.0, [1.0, 0.0, 0.0, 0.5]),
+        ]);
+
+        assert_eq!([0.0; 4], transfer_function.sample(-1000.0));
+        assert_eq!([0.5, 0.0, 0.0, 0.25], transfer_function.sample(-50.0));
+        assert_eq!([1.0, 0.5, 0.5, 0.75], transfer_function.sample(50.0));
+        assert_eq!([1.0; 4], transfer_function.sample(3000.0));
+    }
+
+    #[test]
+    fn test_edit_points() {
+        let mut transfer_function = TransferFunction::ramp(ValueRange {
+            min: 0.0,
+            max: 10.0,
+        });
+        let index = transfer_function.insert(point(4.0, [1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(1, index);
+
+        // A dragged point stops at its neighbours.
+        transfer_function.set_value(index, 20.0);
+        assert_eq!(10.0, transfer_function.points()[1].value);
+        transfer_function.remove(1);
+        transfer_function.remove(1);
+        assert_eq!(2, transfer_function.points().len());
+    }
+
+    #[test]
+    fn test_lookup_table() {
+        let value_range = ValueRange {
+            min: -1000.0,
+            max: 1000.0,
+        };
+        let table = TransferFunction::ramp(value_range).lookup_table(value_range, 3);
+
+        assert_eq!(
+            vec![0, 0, 0, 0, 128, 128, 128, 128, 255, 255, 255, 255],
+            table
+        );
+    }
+}
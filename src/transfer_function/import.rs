@@ -0,0 +1,267 @@
+use super::preset::{Preset, PresetError};
+use super::{ControlPoint, TransferFunction};
+use serde_json::Value;
+
+/// Reads ParaView colour maps, as exported from its colour map editor: a list of objects with a
+/// `Name`, `RGBPoints` of value, red, green and blue, and optionally opacity `Points` of value,
+/// opacity, midpoint and sharpness. Maps without opacity points get ParaView's default linear
+/// opacity ramp.
+pub fn read_paraview(value: &Value) -> Result<Vec<Preset>, PresetError> {
+    let maps = match value {
+        Value::Array(maps) => maps.iter().collect(),
+        map => vec![map],
+    };
+    maps.into_iter()
+        .filter(|map| map.get("RGBPoints").is_some())
+        .map(|map| {
+            let name = map
+                .get("Name")
+                .and_then(Value::as_str)
+                .unwrap_or("ParaView");
+            let rgb_points = numbers(map, "RGBPoints")?;
+            let colors = chunks::<4>(&rgb_points, "RGBPoints")?
+                .into_iter()
+                .map(|[value, r, g, b]| (value, [r, g, b]))
+                .collect::<Vec<_>>();
+            let opacities = match map.get("Points") {
+                Some(_) => chunks::<4>(&numbers(map, "Points")?, "Points")?
+                    .into_iter()
+                    .map(|[value, opacity, _, _]| (value, opacity))
+                    .collect(),
+                None => {
+                    let first = colors.first().map_or(0.0, |color| color.0);
+                    let last = colors.last().map_or(1.0, |color| color.0);
+                    vec![(first, 0.0), (last, 1.0)]
+                }
+            };
+            Ok(Preset {
+                name: name.to_string(),
+                points: merge(&colors, &opacities),
+            })
+        })
+        .collect()
+}
+
+/// Reads a Slicer volume property file (.vp). After the interpolation, shading and lighting
+/// lines come the scalar opacity, the gradient opacity and the colour transfer function, each as
+/// the number of values followed by the values. The gradient opacity is left out.
+pub fn read_slicer_volume_property(name: &str, text: &str) -> Result<Preset, PresetError> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.len() < 9 {
+        return Err(PresetError::Import(format!(
+            "expected 9 lines in a volume property file but found {}",
+            lines.len()
+        )));
+    }
+    slicer_preset(name, lines[6], lines[8])
+}
+
+/// Reads the VolumeProperty elements of Slicer's presets.xml, which hold the scalar opacity and
+/// colour transfer functions in the same form as volume property files.
+pub fn read_slicer_presets(text: &str) -> Result<Vec<Preset>, PresetError> {
+    text.split("<VolumeProperty")
+        .skip(1)
+        .map(|element| {
+            let element = element.split('>').next().unwrap_or_default();
+            let attribute = |key: &str| {
+                attribute(element, key)
+                    .ok_or_else(|| PresetError::Import(format!("VolumeProperty without {}", key)))
+            };
+            slicer_preset(
+                attribute("name")?,
+                attribute("scalarOpacity")?,
+                attribute("colorTransfer")?,
+            )
+        })
+        .collect()
+}
+
+fn slicer_preset(name: &str, opacity: &str, color: &str) -> Result<Preset, PresetError> {
+    let opacities = chunks::<2>(&counted_values(opacity)?, "scalar opacity")?
+        .into_iter()
+        .map(|[value, opacity]| (value, opacity))
+        .collect::<Vec<_>>();
+    let colors = chunks::<4>(&counted_values(color)?, "colour transfer")?
+        .into_iter()
+        .map(|[value, r, g, b]| (value, [r, g, b]))
+        .collect::<Vec<_>>();
+    Ok(Preset {
+        name: name.to_string(),
+        points: merge(&colors, &opacities),
+    })
+}
+
+/// Combines colour and opacity functions with different points into control points at the
+/// values of both.
+fn merge(colors: &[(f32, [f32; 3])], opacities: &[(f32, f32)]) -> Vec<ControlPoint> {
+    let color_function = TransferFunction::new(
+        colors
+            .iter()
+            .map(|&(value, [r, g, b])| ControlPoint {
+                value,
+                color: [r, g, b, 1.0],
+            })
+            .collect(),
+    );
+    let opacity_function = TransferFunction::new(
+        opacities
+            .iter()
+            .map(|&(value, opacity)| ControlPoint {
+                value,
+                color: [opacity; 4],
+            })
+            .collect(),
+    );
+    let mut values: Vec<f32> = colors
+        .iter()
+        .map(|color| color.0)
+        .chain(opacities.iter().map(|opacity| opacity.0))
+        .collect();
+    values.sort_by(f32::total_cmp);
+    values.dedup();
+    values
+        .into_iter()
+        .map(|value| {
+            let mut color = color_function.sample(value);
+            color[3] = opacity_function.sample(value)[3];
+            ControlPoint { value, color }
+        })
+        .collect()
+}
+
+/// Parses a list of values that starts with the number of values, e.g. `4 -1000 0 3000 1`.
+fn counted_values(text: &str) -> Result<Vec<f32>, PresetError> {
+    let invalid = || PresetError::Import(format!("invalid list of values '{}'", text));
+    let mut values = text
+        .split_whitespace()
+        .map(|value| value.parse::<f32>().map_err(|_| invalid()));
+    let count = values.next().ok_or_else(invalid)?? as usize;
+    let values = values.collect::<Result<Vec<f32>, PresetError>>()?;
+    if values.len() != count {
+        return Err(invalid());
+    }
+    Ok(values)
+}
+
+fn numbers(map: &Value, key: &str) -> Result<Vec<f32>, PresetError> {
+    map.get(key)
+        .and_then(Value::as_array)
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_f64().map(|value| value as f32))
+                .collect()
+        })
+        .ok_or_else(|| PresetError::Import(format!("{} is not a list of numbers", key)))
+}
+
+fn chunks<const N: usize>(values: &[f32], name: &str) -> Result<Vec<[f32; N]>, PresetError> {
+    if !values.len().is_multiple_of(N) {
+        return Err(PresetError::Import(format!(
+            "{} has {} values, which is not a multiple of {}",
+            name,
+            values.len(),
+            N
+        )));
+    }
+    Ok(values
+        .chunks_exact(N)
+        .map(|chunk| chunk.try_into().expect("Chunks have N values"))
+        .collect())
+}
+
+/// Returns the value of an XML attribute in the text of an element. The attribute follows any XML
+/// whitespace, may have whitespace around its `=` and is quoted with either quote character.
+fn attribute<'a>(element: &'a str, key: &str) -> Option<&'a str> {
+    let is_xml_space = |c: char| matches!(c, ' ' | '\t' | '\r' | '\n');
+    element.match_indices(key).find_map(|(index, _)| {
+        if !element[..index].ends_with(is_xml_space) {
+            return None;
+        }
+        let rest = element[index + key.len()..].trim_start_matches(is_xml_space);
+        let rest = rest.strip_prefix('=')?.trim_start_matches(is_xml_space);
+        let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        let value = &rest[1..];
+        Some(&value[..value.find(quote)?])
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_read_paraview() {
+        let json = r#"[{
+            "ColorSpace": "RGB",
+            "Name": "Bone",
+            "RGBPoints": [-1000, 0, 0, 0, 1000, 1, 1, 0.5],
+            "Points": [0, 0, 0.5, 0, 1000, 1, 0.5, 0]
+        }, {
+            "Name": "Grey",
+            "RGBPoints": [0, 0, 0, 0, 100, 1, 1, 1]
+        }]"#;
+        let presets = Preset::parse(Path::new("maps.json"), json).unwrap();
+
+        assert_eq!(2, presets.len());
+        assert_eq!("Bone", presets[0].name);
+        let values: Vec<f32> = presets[0].points.iter().map(|point| point.value).collect();
+        assert_eq!(vec![-1000.0, 0.0, 1000.0], values);
+        assert_eq!([0.5, 0.5, 0.25, 0.0], presets[0].points[1].color);
+        assert_eq!([1.0, 1.0, 1.0, 1.0], presets[1].points[1].color);
+    }
+
+    #[test]
+    fn test_read_slicer_volume_property() {
+        let vp =
+            "1\n1\n0.2\n0.9\n0.2\n10\n4 -1000 0 500 0.8\n4 0 1 255 1\n8 -1000 0 0 0 500 1 0.5 0\n";
+        let presets = Preset::parse(Path::new("CT-Bone.vp"), vp).unwrap();
+
+        assert_eq!("CT-Bone", presets[0].name);
+        assert_eq!(
+            vec![
+                ControlPoint {
+                    value: -1000.0,
+                    color: [0.0, 0.0, 0.0, 0.0]
+                },
+                ControlPoint {
+                    value: 500.0,
+                    color: [1.0, 0.5, 0.0, 0.8]
+                }
+            ],
+            presets[0].points
+        );
+    }
+
+    #[test]
+    fn test_read_slicer_presets() {
+        let xml = r#"<?xml version="1.0"?>
+<MRML>
+<VolumeProperty id="vtkMRMLVolumePropertyNode1" name="CT-Lung" interpolation="1"
+  scalarOpacity="4 -1000 0 -500 0.5" gradientOpacity="4 0 1 255 1"
+  colorTransfer="8 -1000 0.3 0.3 1 -500 1 0.8 0.7" />
+<VolumeProperty name="Broken" scalarOpacity="3 0 0" colorTransfer="4 0 0 0 0" />
+</MRML>"#;
+        let result = read_slicer_presets(xml);
+        assert!(matches!(result, Err(PresetError::Import(_))));
+
+        let presets = read_slicer_presets(&xml.replace("3 0 0", "2 0 0")).unwrap();
+        assert_eq!("CT-Lung", presets[0].name);
+        assert_eq!(2, presets[0].points.len());
+        assert_eq!([1.0, 0.8, 0.7, 0.5], presets[0].points[1].color);
+    }
+
+    #[test]
+    fn test_attributes_on_separate_lines() {
+        let element = "<VolumeProperty\n\tname='CT-Bone'\r\nscalarOpacity = \"2 0 1\"\n/>";
+
+        assert_eq!(Some("CT-Bone"), attribute(element, "name"));
+        assert_eq!(Some("2 0 1"), attribute(element, "scalarOpacity"));
+        assert_eq!(None, attribute(element, "Opacity"));
+    }
+}
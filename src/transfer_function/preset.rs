@@ -0,0 +1,229 @@
+use super::import::{read_paraview, read_slicer_presets, read_slicer_volume_property};
+use super::{ControlPoint, TransferFunction};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files [Preset::read] can read.
+pub const PRESET_EXTENSIONS: &[&str] = &["json", "toml", "vp", "xml"];
+
+/// A named transfer function that can be saved and shared. Built-in presets are given in
+/// Hounsfield units and are meant for CT volumes.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub points: Vec<ControlPoint>,
+}
+
+#[derive(Debug)]
+pub enum PresetError {
+    Io(PathBuf, std::io::Error),
+    Json(serde_json::Error),
+    Toml(String),
+    /// A Slicer or ParaView file that cannot be read.
+    Import(String),
+    UnsupportedFormat(PathBuf),
+    NoPresets,
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            PresetError::Json(error) => write!(f, "invalid JSON preset: {}", error),
+            PresetError::Toml(error) => write!(f, "invalid TOML preset: {}", error),
+            PresetError::Import(reason) => write!(f, "cannot import transfer function: {}", reason),
+            PresetError::UnsupportedFormat(path) => {
+                write!(f, "{} is not a transfer function preset", path.display())
+            }
+            PresetError::NoPresets => write!(f, "the file contains no transfer function"),
+        }
+    }
+}
+
+impl From<serde_json::Error> for PresetError {
+    fn from(error: serde_json::Error) -> Self {
+        PresetError::Json(error)
+    }
+}
+
+impl Preset {
+    pub fn new(name: &str, transfer_function: &TransferFunction) -> Self {
+        Preset {
+            name: name.to_string(),
+            points: transfer_function.points().to_vec(),
+        }
+    }
+
+    pub fn transfer_function(&self) -> TransferFunction {
+        TransferFunction::new(self.points.clone())
+    }
+
+    /// Presets for CT volumes in Hounsfield units, similar to those of commercial viewers.
+    pub fn built_in() -> Vec<Preset> {
+        let preset = |name: &str, points: &[(f32, [f32; 4])]| Preset {
+            name: name.to_string(),
+            points: points
+                .iter()
+                .map(|&(value, color)| ControlPoint { value, color })
+                .collect(),
+        };
+        vec![
+            preset(
+                "Bone",
+                &[
+                    (-1000.0, [0.0, 0.0, 0.0, 0.0]),
+                    (150.0, [0.55, 0.25, 0.15, 0.0]),
+                    (300.0, [0.88, 0.6, 0.3, 0.4]),
+                    (800.0, [1.0, 0.94, 0.85, 0.85]),
+                    (3000.0, [1.0, 1.0, 1.0, 0.9]),
+                ],
+            ),
+            preset(
+                "Soft tissue",
+                &[
+                    (-1000.0, [0.0, 0.0, 0.0, 0.0]),
+                    (-150.0, [0.55, 0.25, 0.15, 0.0]),
+                    (-50.0, [0.9, 0.65, 0.45, 0.1]),
+                    (40.0, [0.8, 0.3, 0.25, 0.3]),
+                    (200.0, [1.0, 0.9, 0.8, 0.6]),
+                    (3000.0, [1.0, 1.0, 1.0, 0.8]),
+                ],
+            ),
+            preset(
+                "Lung",
+                &[
+                    (-1000.0, [0.0, 0.0, 0.0, 0.0]),
+                    (-950.0, [0.3, 0.3, 0.8, 0.0]),
+                    (-800.0, [0.75, 0.55, 0.5, 0.15]),
+                    (-500.0, [0.95, 0.75, 0.65, 0.05]),
+                    (-300.0, [0.0, 0.0, 0.0, 0.0]),
+                    (3000.0, [0.0, 0.0, 0.0, 0.0]),
+                ],
+            ),
+            preset(
+                "Angiography",
+                &[
+                    (-1000.0, [0.0, 0.0, 0.0, 0.0]),
+                    (120.0, [0.6, 0.05, 0.05, 0.0]),
+                    (200.0, [0.9, 0.2, 0.15, 0.5]),
+                    (400.0, [1.0, 0.85, 0.75, 0.8]),
+                    (3000.0, [1.0, 1.0, 1.0, 0.9]),
+                ],
+            ),
+            preset(
+                "Skin",
+                &[
+                    (-1000.0, [0.0, 0.0, 0.0, 0.0]),
+                    (-500.0, [0.8, 0.6, 0.5, 0.0]),
+                    (-200.0, [0.95, 0.75, 0.65, 0.9]),
+                    (3000.0, [1.0, 0.85, 0.75, 1.0]),
+                ],
+            ),
+        ]
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Presets can be serialized")
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Presets can be serialized")
+    }
+
+    /// Reads the presets in a file, detecting its format from the extension: JSON or TOML presets,
+    /// ParaView colour maps (.json), Slicer volume properties (.vp) or Slicer's presets.xml.
+    pub fn read(path: &Path) -> Result<Vec<Preset>, PresetError> {
+        let text =
+            std::fs::read_to_string(path).map_err(|error| PresetError::Io(path.into(), error))?;
+        Preset::parse(path, &text)
+    }
+
+    /// Reads the presets in the text of a file, using its path to detect the format.
+    pub fn parse(path: &Path, text: &str) -> Result<Vec<Preset>, PresetError> {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        let presets = match extension.as_deref() {
+            Some("json") => {
+                let value: serde_json::Value = serde_json::from_str(text)?;
+                if value.is_array() || value.get("RGBPoints").is_some() {
+                    read_paraview(&value)?
+                } else {
+                    vec![serde_json::from_value(value)?]
+                }
+            }
+            Some("toml") => {
+                vec![toml::from_str(text).map_err(|error| PresetError::Toml(error.to_string()))?]
+            }
+            Some("vp") => vec![read_slicer_volume_property(&name, text)?],
+            Some("xml") => read_slicer_presets(text)?,
+            _ => return Err(PresetError::UnsupportedFormat(path.to_path_buf())),
+        };
+        if presets.is_empty() {
+            return Err(PresetError::NoPresets);
+        }
+        Ok(presets)
+    }
+
+    /// Saves the preset as TOML if the path ends with .toml and as JSON otherwise.
+    pub fn write(&self, path: &Path) -> Result<(), PresetError> {
+        let is_toml = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+        let text = if is_toml {
+            self.to_toml()
+        } else {
+            self.to_json()
+        };
+        std::fs::write(path, text).map_err(|error| PresetError::Io(path.to_path_buf(), error))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::test_path;
+
+    #[test]
+    fn test_built_in_presets() {
+        let presets = Preset::built_in();
+        let names: Vec<&str> = presets.iter().map(|preset| preset.name.as_str()).collect();
+
+        assert_eq!(
+            vec!["Bone", "Soft tissue", "Lung", "Angiography", "Skin"],
+            names
+        );
+        // Air is transparent in every preset.
+        for preset in &presets {
+            assert_eq!(0.0, preset.transfer_function().sample(-1000.0)[3]);
+        }
+    }
+
+    #[test]
+    fn test_json_and_toml_round_trip() {
+        let preset = Preset::built_in().remove(0);
+
+        let json = Preset::parse(Path::new("bone.json"), &preset.to_json()).unwrap();
+        assert_eq!(vec![preset.clone()], json);
+        let toml = Preset::parse(Path::new("bone.toml"), &preset.to_toml()).unwrap();
+        assert_eq!(vec![preset], toml);
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let path = test_path("preset", "preset.toml");
+        let preset = Preset::new("Custom", &Preset::built_in()[4].transfer_function());
+        preset.write(&path).unwrap();
+
+        assert_eq!(vec![preset], Preset::read(&path).unwrap());
+        assert!(matches!(
+            Preset::parse(Path::new("preset.txt"), ""),
+            Err(PresetError::UnsupportedFormat(_))
+        ));
+    }
+}
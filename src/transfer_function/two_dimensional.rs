@@ -0,0 +1,297 @@
+use crate::volume::ValueRange;
+
+/// Area of the value × gradient magnitude plane that a region covers. Positions are given as
+/// `[value, gradient magnitude]` in native units, e.g. Hounsfield units and Hounsfield units per
+/// millimetre.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Shape {
+    /// Constant opacity between two corners.
+    Rectangle { min: [f32; 2], max: [f32; 2] },
+    /// Triangle with its apex at a value without gradient that widens towards stronger gradients,
+    /// which follows the arch that a boundary between two materials draws in the joint histogram.
+    /// The opacity falls off from the centre line to the sides.
+    Triangle {
+        value: f32,
+        /// Distance from the centre line to the sides at the top of the triangle.
+        half_width: f32,
+        gradient_max: f32,
+    },
+}
+
+impl Shape {
+    /// Returns how much of the region's opacity applies at a position, between 0 outside the
+    /// shape and 1.
+    pub fn weight(&self, position: [f32; 2]) -> f32 {
+        let [value, gradient] = position;
+        match *self {
+            Shape::Rectangle { min, max } => {
+                let inside =
+                    (min[0]..=max[0]).contains(&value) && (min[1]..=max[1]).contains(&gradient);
+                if inside {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Shape::Triangle {
+                value: centre,
+                half_width,
+                gradient_max,
+            } => {
+                if gradient < 0.0 || gradient > gradient_max || gradient_max <= 0.0 {
+                    return 0.0;
+                }
+                let width = half_width * gradient / gradient_max;
+                if width <= 0.0 {
+                    return if value == centre { 1.0 } else { 0.0 };
+                }
+                (1.0 - (value - centre).abs() / width).max(0.0)
+            }
+        }
+    }
+
+    /// Corners of the shape, counter-clockwise in the plane.
+    pub fn vertices(&self) -> Vec<[f32; 2]> {
+        match *self {
+            Shape::Rectangle { min, max } => {
+                vec![min, [max[0], min[1]], max, [min[0], max[1]]]
+            }
+            Shape::Triangle {
+                value,
+                half_width,
+                gradient_max,
+            } => vec![
+                [value, 0.0],
+                [value + half_width, gradient_max],
+                [value - half_width, gradient_max],
+            ],
+        }
+    }
+
+    /// Position of the handle that resizes the shape, which is its top right corner.
+    pub fn handle(&self) -> [f32; 2] {
+        match *self {
+            Shape::Rectangle { max, .. } => max,
+            Shape::Triangle {
+                value,
+                half_width,
+                gradient_max,
+            } => [value + half_width, gradient_max],
+        }
+    }
+
+    /// Moves the handle to a position. The shape keeps its bottom left corner or its apex.
+    pub fn set_handle(&mut self, position: [f32; 2]) {
+        match self {
+            Shape::Rectangle { min, max } => {
+                *max = [position[0].max(min[0]), position[1].max(min[1])];
+            }
+            Shape::Triangle {
+                value,
+                half_width,
+                gradient_max,
+            } => {
+                *half_width = (position[0] - *value).abs();
+                *gradient_max = position[1].max(0.0);
+            }
+        }
+    }
+
+    /// Moves the shape by a distance in value and gradient magnitude. Triangles only move along
+    /// the values, as their apex stays at zero gradient.
+    pub fn translate(&mut self, delta: [f32; 2]) {
+        match self {
+            Shape::Rectangle { min, max } => {
+                let delta_gradient = delta[1].max(-min[1]);
+                *min = [min[0] + delta[0], min[1] + delta_gradient];
+                *max = [max[0] + delta[0], max[1] + delta_gradient];
+            }
+            Shape::Triangle { value, .. } => *value += delta[0],
+        }
+    }
+}
+
+/// Shape that is painted with one colour and opacity.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Region {
+    pub shape: Shape,
+    /// Red, green, blue and opacity in [0, 1], not premultiplied.
+    pub color: [f32; 4],
+}
+
+/// Mapping from voxel values and gradient magnitudes to colour and opacity, painted with
+/// regions. Compared to a [super::TransferFunction] over the values alone it can tell the
+/// boundaries between materials from their interiors.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TransferFunction2D {
+    regions: Vec<Region>,
+}
+
+impl TransferFunction2D {
+    pub fn new(regions: Vec<Region>) -> Self {
+        TransferFunction2D { regions }
+    }
+
+    /// Shows all boundaries, i.e. the upper half of the gradient magnitudes over all values, in
+    /// half-transparent white.
+    pub fn boundaries(value_range: ValueRange, gradient_range: ValueRange) -> Self {
+        TransferFunction2D::new(vec![Region {
+            shape: Shape::Rectangle {
+                min: [value_range.min, gradient_range.denormalize(0.5)],
+                max: [value_range.max, gradient_range.max],
+            },
+            color: [1.0, 1.0, 1.0, 0.5],
+        }])
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    pub fn region_mut(&mut self, index: usize) -> &mut Region {
+        &mut self.regions[index]
+    }
+
+    /// Adds a region on top of the others and returns its index.
+    pub fn insert(&mut self, region: Region) -> usize {
+        self.regions.push(region);
+        self.regions.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.regions.len() {
+            self.regions.remove(index);
+        }
+    }
+
+    /// Returns the index of the topmost region that covers a position.
+    pub fn region_at(&self, position: [f32; 2]) -> Option<usize> {
+        self.regions
+            .iter()
+            .rposition(|region| region.shape.weight(position) > 0.0)
+    }
+
+    /// Returns the colour and opacity at a value and gradient magnitude. Overlapping regions add
+    /// up their opacities like layers of a material, and their colours are weighted by opacity.
+    pub fn sample(&self, position: [f32; 2]) -> [f32; 4] {
+        let mut transparency = 1.0;
+        let mut color = [0.0; 3];
+        let mut total_opacity = 0.0;
+        for region in &self.regions {
+            let opacity = region.color[3] * region.shape.weight(position);
+            if opacity <= 0.0 {
+                continue;
+            }
+            transparency *= 1.0 - opacity;
+            total_opacity += opacity;
+            for (channel, value) in color.iter_mut().enumerate() {
+                *value += region.color[channel] * opacity;
+            }
+        }
+        if total_opacity <= 0.0 {
+            return [0.0; 4];
+        }
+        let [r, g, b] = color.map(|value| value / total_opacity);
+        [r, g, b, 1.0 - transparency]
+    }
+
+    /// Samples the function on a grid of `size` × `size` values and gradient magnitudes spread
+    /// evenly over their ranges, as 8-bit RGBA rows starting at the lowest gradient.
+    pub fn lookup_table(
+        &self,
+        value_range: ValueRange,
+        gradient_range: ValueRange,
+        size: usize,
+    ) -> Vec<u8> {
+        let position = |index: usize| index as f32 / (size - 1).max(1) as f32;
+        (0..size)
+            .flat_map(|row| {
+                let gradient = gradient_range.denormalize(position(row));
+                (0..size).flat_map(move |column| {
+                    let value = value_range.denormalize(position(column));
+                    self.sample([value, gradient])
+                        .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rectangle(min: [f32; 2], max: [f32; 2], color: [f32; 4]) -> Region {
+        Region {
+            shape: Shape::Rectangle { min, max },
+            color,
+        }
+    }
+
+    #[test]
+    fn test_triangle_weight() {
+        let triangle = Shape::Triangle {
+            value: 100.0,
+            half_width: 50.0,
+            gradient_max: 10.0,
+        };
+
+        assert_eq!(1.0, triangle.weight([100.0, 5.0]));
+        assert_eq!(0.5, triangle.weight([112.5, 5.0]));
+        assert_eq!(0.0, triangle.weight([130.0, 5.0]));
+        assert_eq!(0.0, triangle.weight([100.0, 11.0]));
+        assert_eq!([150.0, 10.0], triangle.handle());
+    }
+
+    #[test]
+    fn test_sample_overlapping_regions() {
+        let transfer_function = TransferFunction2D::new(vec![
+            rectangle([0.0, 0.0], [10.0, 10.0], [1.0, 0.0, 0.0, 0.5]),
+            rectangle([5.0, 0.0], [15.0, 10.0], [0.0, 0.0, 1.0, 0.5]),
+        ]);
+
+        assert_eq!([1.0, 0.0, 0.0, 0.5], transfer_function.sample([2.0, 2.0]));
+        assert_eq!([0.5, 0.0, 0.5, 0.75], transfer_function.sample([7.0, 2.0]));
+        assert_eq!([0.0; 4], transfer_function.sample([20.0, 2.0]));
+        assert_eq!(Some(1), transfer_function.region_at([7.0, 2.0]));
+    }
+
+    #[test]
+    fn test_edit_shapes() {
+        let mut shape = Shape::Rectangle {
+            min: [0.0, 2.0],
+            max: [10.0, 4.0],
+        };
+        shape.set_handle([-5.0, 8.0]);
+        assert_eq!([0.0, 8.0], shape.handle());
+        // A rectangle stops at zero gradient.
+        shape.translate([1.0, -5.0]);
+        assert_eq!(
+            vec![[1.0, 0.0], [1.0, 0.0], [1.0, 6.0], [1.0, 6.0]],
+            shape.vertices()
+        );
+    }
+
+    #[test]
+    fn test_lookup_table() {
+        let transfer_function = TransferFunction2D::boundaries(
+            ValueRange {
+                min: 0.0,
+                max: 100.0,
+            },
+            ValueRange { min: 0.0, max: 4.0 },
+        );
+        let table = transfer_function.lookup_table(
+            ValueRange {
+                min: 0.0,
+                max: 100.0,
+            },
+            ValueRange { min: 0.0, max: 4.0 },
+            3,
+        );
+
+        assert_eq!(3 * 3 * 4, table.len());
+        assert!(table[..3 * 4].iter().all(|&channel| channel == 0));
+        assert_eq!(&[255, 255, 255, 128], &table[3 * 4..4 * 4]);
+    }
+}
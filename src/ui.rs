@@ -1,17 +1,44 @@
-use egui_plot::{Bar, BarChart, Legend, Plot};
-use three_d::egui::{Color32, Response, Slider, Ui};
+use egui_plot::{
+    Bar, BarChart, Legend, Line, MarkerShape, Plot, PlotBounds, PlotImage, PlotPoint, PlotPoints,
+    Points, Polygon,
+};
+use three_d::egui::{
+    Align2, Button, Color32, ColorImage, ComboBox, DragValue, FontId, Id, ProgressBar, Rect,
+    Response, RichText, Slider, Stroke, TextureOptions, Ui, Vec2,
+};
+use three_d::{Camera, Vec4};
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::transfer_function::preset::{Preset, PRESET_EXTENSIONS};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::volume::format::VOLUME_EXTENSIONS;
+#[cfg(target_arch = "wasm32")]
+use crate::volume::memory::MemoryFiles;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+use crate::transfer_function::two_dimensional::{Region, Shape};
+use crate::transfer_function::ControlPoint;
+use crate::volume::gradient::JOINT_HISTOGRAM_BINS;
+use crate::volume::orientation::PATIENT_DIRECTIONS;
+use crate::volume::resample::Interpolation;
 use crate::{renderer::Scene, shader::ShaderType, volume::Volume};
 
 pub struct UserInterface;
 
 impl UserInterface {
     pub fn render_histogram(ui: &mut Ui, volume: &Volume) -> Response {
+        let value_range = volume.value_range();
+        let bin_count = volume.histogram.len() as f32;
+        let bin_width = (value_range.max - value_range.min) / bin_count;
         let bars = volume
             .histogram
             .iter()
             .enumerate()
-            .map(|(x, index)| Bar::new(x as f64, *index as f64))
+            .map(|(x, index)| {
+                let value = value_range.denormalize((x as f32 + 0.5) / bin_count);
+                Bar::new(value as f64, *index).width(bin_width as f64)
+            })
             .collect();
         let chart = BarChart::new(bars).color(Color32::LIGHT_BLUE);
 
@@ -25,12 +52,619 @@ impl UserInterface {
             .show(ui, |plot_ui| plot_ui.bar_chart(chart))
             .response
     }
+    pub fn render_load_error(ui: &mut Ui, scene: &Scene) {
+        if let Some(error) = &scene.load_error {
+            ui.colored_label(Color32::RED, format!("Cannot load volume: {}", error));
+        }
+        if !scene.skipped_files.is_empty() {
+            let title = format!("Skipped {} unreadable files", scene.skipped_files.len());
+            ui.collapsing(RichText::new(title).color(Color32::YELLOW), |ui| {
+                for error in &scene.skipped_files {
+                    ui.label(error.to_string());
+                }
+            });
+        }
+    }
+    /// Shows the File menu. Returns the file or directory picked in the native file dialog.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_file_menu(ui: &mut Ui) -> Option<PathBuf> {
+        let mut picked = None;
+        ui.menu_button("File", |ui| {
+            if ui.button("Open file…").clicked() {
+                ui.close_menu();
+                picked = rfd::FileDialog::new()
+                    .set_title("Open volume")
+                    .add_filter("Volumes", VOLUME_EXTENSIONS)
+                    .add_filter("All files", &["*"])
+                    .pick_file();
+            }
+            if ui.button("Open DICOM folder…").clicked() {
+                ui.close_menu();
+                picked = rfd::FileDialog::new()
+                    .set_title("Open DICOM folder")
+                    .pick_folder();
+            }
+        });
+        picked
+    }
+    /// Shows the File menu. The files picked in the browser are read in memory and opened on a
+    /// later frame, as reading them is asynchronous.
+    #[cfg(target_arch = "wasm32")]
+    pub fn render_file_menu(ui: &mut Ui) {
+        ui.menu_button("File", |ui| {
+            if ui.button("Open files…").clicked() {
+                ui.close_menu();
+                wasm_bindgen_futures::spawn_local(async {
+                    let Some(handles) = rfd::AsyncFileDialog::new()
+                        .set_title("Open volume")
+                        .pick_files()
+                        .await
+                    else {
+                        return;
+                    };
+                    let mut files = MemoryFiles::default();
+                    for handle in handles {
+                        files.push(&handle.file_name(), handle.read().await);
+                    }
+                    crate::open_files(files);
+                });
+            }
+        });
+    }
+    /// Shows the progress of a running load. Returns true if the load should be cancelled.
+    pub fn render_loading(ui: &mut Ui, scene: &Scene) -> bool {
+        let Some(task) = &scene.loading else {
+            return false;
+        };
+        let progress = task.progress();
+        let megabytes = |bytes: u64| bytes as f64 / 1_000_000.0;
+        let text = if progress.bytes_total() > 0 {
+            format!(
+                "Loading {:.1}/{:.1} MB",
+                megabytes(progress.bytes_read()),
+                megabytes(progress.bytes_total())
+            )
+        } else if progress.is_scanning() {
+            format!(
+                "Scanning {}/{} files",
+                progress.files_done(),
+                progress.files_total()
+            )
+        } else {
+            format!(
+                "Loading {}/{} files, {:.1} MB",
+                progress.files_done(),
+                progress.files_total(),
+                megabytes(progress.bytes_read())
+            )
+        };
+        ui.horizontal(|ui| {
+            ui.add(
+                ProgressBar::new(progress.fraction())
+                    .text(text)
+                    .animate(true),
+            );
+            ui.button("Cancel").clicked()
+        })
+        .inner
+    }
+    /// Shows a picker if the opened directory contains more than one series. Returns the index of
+    /// a newly picked series.
+    pub fn render_series_picker(ui: &mut Ui, scene: &Scene) -> Option<usize> {
+        if scene.series.len() < 2 {
+            return None;
+        }
+        let mut selected = scene.selected_series;
+        let selected_text = selected
+            .map(|index| scene.series[index].label())
+            .unwrap_or_default();
+        ComboBox::from_label("Series")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                for (index, series) in scene.series.iter().enumerate() {
+                    ui.selectable_value(&mut selected, Some(index), series.label());
+                }
+            });
+        selected.filter(|_| selected != scene.selected_series)
+    }
+    /// Shows the spacing of the volume and, if it is anisotropic, a button to resample it to cubic
+    /// voxels. Returns true if the button was clicked.
+    pub fn render_resampling(ui: &mut Ui, scene: &mut Scene) -> bool {
+        let spacing = scene.volume.texture.spacing;
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "Spacing {:.3} x {:.3} x {:.3} mm",
+                spacing.x, spacing.y, spacing.z
+            ));
+            if spacing.x == spacing.y && spacing.y == spacing.z {
+                return false;
+            }
+            ComboBox::from_label("Interpolation")
+                .selected_text(format!("{:?}", scene.interpolation))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut scene.interpolation,
+                        Interpolation::Trilinear,
+                        "Trilinear",
+                    );
+                    ui.selectable_value(&mut scene.interpolation, Interpolation::Cubic, "Cubic");
+                });
+            ui.add_enabled(scene.loading.is_none(), Button::new("Make isotropic"))
+                .clicked()
+        })
+        .inner
+    }
+    /// Shows a picker that applies a preset to the transfer function and buttons that load presets
+    /// from a file or save the transfer function as one. Returns true if the transfer function
+    /// changed.
+    pub fn render_presets(ui: &mut Ui, scene: &mut Scene) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            let mut picked = None;
+            ComboBox::from_label("Preset")
+                .selected_text("Apply…")
+                .show_ui(ui, |ui| {
+                    for (index, preset) in scene.presets.iter().enumerate() {
+                        if ui.selectable_label(false, &preset.name).clicked() {
+                            picked = Some(index);
+                        }
+                    }
+                });
+            if let Some(index) = picked {
+                scene.transfer_function = scene.presets[index].transfer_function();
+                changed = true;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                if ui.button("Load…").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_title("Load transfer function presets")
+                        .add_filter("Presets, Slicer and ParaView files", PRESET_EXTENSIONS)
+                        .pick_file()
+                    {
+                        changed |= scene.add_presets(Preset::read(&path));
+                    }
+                }
+                if ui.button("Save…").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .set_title("Save transfer function preset")
+                        .add_filter("JSON", &["json"])
+                        .add_filter("TOML", &["toml"])
+                        .set_file_name("preset.json")
+                        .save_file()
+                    {
+                        let name = path
+                            .file_stem()
+                            .map(|stem| stem.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        let preset = Preset::new(&name, &scene.transfer_function);
+                        scene.preset_error = preset.write(&path).err();
+                    }
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            if ui.button("Load…").clicked() {
+                wasm_bindgen_futures::spawn_local(async {
+                    let Some(handle) = rfd::AsyncFileDialog::new()
+                        .set_title("Load transfer function presets")
+                        .pick_file()
+                        .await
+                    else {
+                        return;
+                    };
+                    crate::open_presets(handle.file_name(), handle.read().await);
+                });
+            }
+        });
+        if let Some(error) = &scene.preset_error {
+            ui.colored_label(Color32::RED, error.to_string());
+        }
+        changed
+    }
+    /// Shows the transfer function as a line of its opacity over the histogram. Control points are
+    /// dragged to change their value and opacity, added by double-clicking and removed by
+    /// right-clicking, and their colours are edited in the list below the plot. Returns true if
+    /// the transfer function changed.
+    pub fn render_transfer_function(ui: &mut Ui, scene: &mut Scene) -> bool {
+        const PICK_DISTANCE: f32 = 10.0;
+        let value_range = scene.volume.value_range();
+        let dragged_id = Id::new("transfer_function_dragged_point");
+        let mut dragged: Option<usize> = ui.data(|data| data.get_temp(dragged_id));
+        let mut changed = false;
+
+        // Bin counts on a log scale, so that small peaks next to air are visible.
+        let max_count = scene.volume.histogram.iter().copied().fold(0.0, f64::max);
+        let bin_count = scene.volume.histogram.len() as f32;
+        let bin_width = (value_range.max - value_range.min) / bin_count;
+        let bars = scene
+            .volume
+            .histogram
+            .iter()
+            .enumerate()
+            .map(|(x, count)| {
+                let value = value_range.denormalize((x as f32 + 0.5) / bin_count);
+                let height = (1.0 + count).ln() / (1.0 + max_count).ln().max(f64::EPSILON);
+                Bar::new(value as f64, height).width(bin_width as f64)
+            })
+            .collect();
+        let histogram = BarChart::new(bars).color(Color32::from_gray(90));
+
+        let transfer_function = &mut scene.transfer_function;
+        Plot::new("Transfer function")
+            .allow_zoom(false)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .allow_double_click_reset(false)
+            .allow_boxed_zoom(false)
+            .show_y(false)
+            .height(160.0)
+            .show(ui, |plot_ui| {
+                plot_ui.set_plot_bounds(PlotBounds::from_min_max(
+                    [value_range.min as f64, -0.05],
+                    [value_range.max as f64, 1.05],
+                ));
+                let points = transfer_function.points();
+                let line: PlotPoints = points
+                    .iter()
+                    .map(|point| [point.value as f64, point.color[3] as f64])
+                    .collect();
+                plot_ui.bar_chart(histogram);
+                plot_ui.line(Line::new(line).color(Color32::WHITE));
+                for point in points {
+                    let [r, g, b, _] = point.color.map(|channel| (channel * 255.0) as u8);
+                    plot_ui.points(
+                        Points::new([point.value as f64, point.color[3] as f64])
+                            .shape(MarkerShape::Circle)
+                            .radius(5.0)
+                            .filled(true)
+                            .color(Color32::from_rgb(r, g, b)),
+                    );
+                }
+
+                let response = plot_ui.response().clone();
+                let Some(pointer) = plot_ui.pointer_coordinate() else {
+                    return;
+                };
+                let pointer_screen = plot_ui.screen_from_plot(pointer);
+                let nearest = points
+                    .iter()
+                    .map(|point| {
+                        let position = [point.value as f64, point.color[3] as f64];
+                        plot_ui
+                            .screen_from_plot(position.into())
+                            .distance(pointer_screen)
+                    })
+                    .enumerate()
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .filter(|(_, distance)| *distance < PICK_DISTANCE)
+                    .map(|(index, _)| index);
+                let value = pointer.x as f32;
+                let opacity = pointer.y.clamp(0.0, 1.0) as f32;
+
+                if response.drag_started() {
+                    dragged = nearest;
+                }
+                if let Some(index) = dragged.filter(|_| response.dragged()) {
+                    transfer_function.set_value(index, value);
+                    let mut color = transfer_function.points()[index].color;
+                    color[3] = opacity;
+                    transfer_function.set_color(index, color);
+                    changed = true;
+                }
+                if response.drag_released() {
+                    dragged = None;
+                }
+                if response.double_clicked() && nearest.is_none() {
+                    let mut color = transfer_function.sample(value);
+                    color[3] = opacity;
+                    transfer_function.insert(ControlPoint { value, color });
+                    changed = true;
+                }
+                if let Some(index) = nearest.filter(|_| response.secondary_clicked()) {
+                    transfer_function.remove(index);
+                    changed = true;
+                }
+            });
+        ui.data_mut(|data| match dragged {
+            Some(index) => data.insert_temp(dragged_id, index),
+            None => data.remove::<usize>(dragged_id),
+        });
+
+        ui.collapsing("Control points", |ui| {
+            let mut removed = None;
+            for index in 0..transfer_function.points().len() {
+                let ControlPoint {
+                    mut value,
+                    mut color,
+                } = transfer_function.points()[index];
+                ui.horizontal(|ui| {
+                    if ui
+                        .add(DragValue::new(&mut value).speed(1.0).suffix(" value"))
+                        .changed()
+                    {
+                        transfer_function.set_value(index, value);
+                        changed = true;
+                    }
+                    if ui.color_edit_button_rgba_unmultiplied(&mut color).changed() {
+                        transfer_function.set_color(index, color);
+                        changed = true;
+                    }
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                transfer_function.remove(index);
+                changed = true;
+            }
+        });
+        changed
+    }
+    /// Shows the regions of the 2D transfer function over the joint histogram of values and
+    /// gradient magnitudes. Regions are moved by dragging them, resized by dragging the handle at
+    /// their top right corner and removed by right-clicking, and their colours and exact shapes
+    /// are edited in the list below the plot. Returns true if the transfer function changed.
+    pub fn render_transfer_function_2d(ui: &mut Ui, scene: &mut Scene) -> bool {
+        const PICK_DISTANCE: f32 = 10.0;
+        let value_range = scene.volume.value_range();
+        let (Some(gradient), Some(transfer_function)) =
+            (&scene.volume.gradient, &mut scene.transfer_function_2d)
+        else {
+            return false;
+        };
+        let gradient_range = gradient.magnitude_range();
+        let dragged_id = Id::new("transfer_function_2d_dragged_region");
+        // Index of the dragged region and whether its handle is dragged instead of the region.
+        let mut dragged: Option<(usize, bool)> = ui.data(|data| data.get_temp(dragged_id));
+        let mut changed = false;
+
+        // Voxel counts on a log scale with the lowest gradients at the bottom of the image.
+        let joint_histogram = &gradient.joint_histogram;
+        let histogram_image = scene.joint_histogram_image.get_or_insert_with(|| {
+            let bins = JOINT_HISTOGRAM_BINS;
+            let max_count = joint_histogram.iter().copied().fold(0.0, f64::max);
+            let pixels: Vec<u8> = (0..bins * bins)
+                .map(|index| {
+                    let count = joint_histogram[(bins - 1 - index / bins) * bins + index % bins];
+                    let brightness = (1.0 + count).ln() / (1.0 + max_count).ln().max(f64::EPSILON);
+                    (brightness * 255.0) as u8
+                })
+                .collect();
+            ui.ctx().load_texture(
+                "joint_histogram",
+                ColorImage::from_gray([bins, bins], &pixels),
+                TextureOptions::LINEAR,
+            )
+        });
+        let histogram = PlotImage::new(
+            histogram_image.id(),
+            PlotPoint::new(
+                (value_range.min + value_range.max) as f64 / 2.0,
+                gradient_range.max as f64 / 2.0,
+            ),
+            Vec2::new(value_range.max - value_range.min, gradient_range.max),
+        );
+
+        Plot::new("2D transfer function")
+            .allow_zoom(false)
+            .allow_drag(false)
+            .allow_scroll(false)
+            .allow_double_click_reset(false)
+            .allow_boxed_zoom(false)
+            .x_axis_label("Value")
+            .y_axis_label("Gradient magnitude")
+            .height(200.0)
+            .show(ui, |plot_ui| {
+                plot_ui.set_plot_bounds(PlotBounds::from_min_max(
+                    [value_range.min as f64, 0.0],
+                    [value_range.max as f64, gradient_range.max as f64],
+                ));
+                plot_ui.image(histogram);
+                for region in transfer_function.regions() {
+                    let [r, g, b, a] = region.color.map(|channel| (channel * 255.0) as u8);
+                    let vertices: PlotPoints = region
+                        .shape
+                        .vertices()
+                        .iter()
+                        .map(|vertex| [vertex[0] as f64, vertex[1] as f64])
+                        .collect();
+                    plot_ui.polygon(
+                        Polygon::new(vertices)
+                            .fill_color(Color32::from_rgba_unmultiplied(r, g, b, a / 2))
+                            .stroke(Stroke::new(1.5, Color32::from_rgb(r, g, b))),
+                    );
+                    let [value, gradient] = region.shape.handle();
+                    plot_ui.points(
+                        Points::new([value as f64, gradient as f64])
+                            .shape(MarkerShape::Square)
+                            .radius(4.0)
+                            .filled(true)
+                            .color(Color32::WHITE),
+                    );
+                }
+
+                let response = plot_ui.response().clone();
+                let Some(pointer) = plot_ui.pointer_coordinate() else {
+                    return;
+                };
+                let position = [pointer.x as f32, pointer.y as f32];
+                let pointer_screen = plot_ui.screen_from_plot(pointer);
+                let handle = transfer_function.regions().iter().rposition(|region| {
+                    let [value, gradient] = region.shape.handle();
+                    plot_ui
+                        .screen_from_plot(PlotPoint::new(value, gradient))
+                        .distance(pointer_screen)
+                        < PICK_DISTANCE
+                });
+
+                if response.drag_started() {
+                    dragged = handle.map(|index| (index, true)).or_else(|| {
+                        transfer_function
+                            .region_at(position)
+                            .map(|index| (index, false))
+                    });
+                }
+                let region_count = transfer_function.regions().len();
+                if let Some((index, is_handle)) =
+                    dragged.filter(|(index, _)| response.dragged() && *index < region_count)
+                {
+                    let shape = &mut transfer_function.region_mut(index).shape;
+                    if is_handle {
+                        shape.set_handle(position);
+                    } else {
+                        let delta = plot_ui.pointer_coordinate_drag_delta();
+                        shape.translate([delta.x, delta.y]);
+                    }
+                    changed = true;
+                }
+                if response.drag_released() {
+                    dragged = None;
+                }
+                if let Some(index) = transfer_function
+                    .region_at(position)
+                    .filter(|_| response.secondary_clicked())
+                {
+                    transfer_function.remove(index);
+                    changed = true;
+                }
+            });
+        ui.data_mut(|data| match dragged {
+            Some(region) => data.insert_temp(dragged_id, region),
+            None => data.remove::<(usize, bool)>(dragged_id),
+        });
+
+        ui.horizontal(|ui| {
+            let value = |position: f32| value_range.denormalize(position);
+            let gradient = |position: f32| gradient_range.denormalize(position);
+            if ui.button("Add rectangle").clicked() {
+                transfer_function.insert(Region {
+                    shape: Shape::Rectangle {
+                        min: [value(0.375), gradient(0.25)],
+                        max: [value(0.625), gradient(0.75)],
+                    },
+                    color: [1.0, 0.8, 0.6, 0.5],
+                });
+                changed = true;
+            }
+            if ui.button("Add triangle").clicked() {
+                transfer_function.insert(Region {
+                    shape: Shape::Triangle {
+                        value: value(0.5),
+                        half_width: (value_range.max - value_range.min) / 8.0,
+                        gradient_max: gradient(0.75),
+                    },
+                    color: [1.0, 0.9, 0.8, 0.8],
+                });
+                changed = true;
+            }
+        });
+        ui.collapsing("Regions", |ui| {
+            let mut removed = None;
+            for index in 0..transfer_function.regions().len() {
+                let region = transfer_function.region_mut(index);
+                ui.horizontal(|ui| {
+                    let fields: Vec<(&mut f32, &str)> = match &mut region.shape {
+                        Shape::Rectangle { min, max } => {
+                            let [min_value, min_gradient] = min;
+                            let [max_value, max_gradient] = max;
+                            vec![
+                                (min_value, " min value"),
+                                (max_value, " max value"),
+                                (min_gradient, " min gradient"),
+                                (max_gradient, " max gradient"),
+                            ]
+                        }
+                        Shape::Triangle {
+                            value,
+                            half_width,
+                            gradient_max,
+                        } => vec![
+                            (value, " value"),
+                            (half_width, " half width"),
+                            (gradient_max, " max gradient"),
+                        ],
+                    };
+                    for (field, suffix) in fields {
+                        changed |= ui
+                            .add(DragValue::new(field).speed(1.0).suffix(suffix))
+                            .changed();
+                    }
+                    changed |= ui
+                        .color_edit_button_rgba_unmultiplied(&mut region.color)
+                        .changed();
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                transfer_function.remove(index);
+                changed = true;
+            }
+        });
+        changed
+    }
+    /// Draws the patient directions (L/R, A/P, S/I) in the bottom right corner of the view, rotated
+    /// like the volume by the camera.
+    pub fn render_orientation_marker(ui: &Ui, rect: Rect, camera: &Camera) {
+        const RADIUS: f32 = 32.0;
+        let center = rect.right_bottom() - Vec2::splat(RADIUS + 16.0);
+        let mut axes: Vec<(&str, Vec2, f32)> = PATIENT_DIRECTIONS
+            .iter()
+            .map(|(label, direction)| {
+                let direction = direction.map(|value| value as f32);
+                let view = camera.view() * Vec4::new(direction[0], direction[1], direction[2], 0.0);
+                // The camera looks along -z, and screen coordinates grow downwards.
+                (*label, Vec2::new(view.x, -view.y), view.z)
+            })
+            .collect();
+        // Directions pointing away from the viewer are drawn first and dimmed.
+        axes.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+        let painter = ui.painter_at(rect);
+        for (label, offset, depth) in axes {
+            let color = match label {
+                "L" | "R" => Color32::from_rgb(230, 80, 80),
+                "A" | "P" => Color32::from_rgb(80, 200, 80),
+                _ => Color32::from_rgb(90, 140, 240),
+            };
+            let color = if depth < 0.0 {
+                color.gamma_multiply(0.4)
+            } else {
+                color
+            };
+            let end = center + offset * RADIUS;
+            painter.line_segment([center, end], Stroke::new(2.0, color));
+            painter.circle_filled(end, 8.0, Color32::from_black_alpha(180));
+            painter.text(
+                end,
+                Align2::CENTER_CENTER,
+                label,
+                FontId::proportional(13.0),
+                color,
+            );
+        }
+    }
     pub fn render_controls(ui: &mut Ui, scene: &mut Scene) {
+        let value_range = scene.volume.value_range();
         ui.horizontal(|ui| {
             ui.spacing_mut().item_spacing.x = 10.0;
             ui.vertical(|ui| {
-                ui.add(Slider::new(&mut scene.lower_threshold, 0..=255).text("Lower Threshold"));
-                ui.add(Slider::new(&mut scene.upper_threshold, 0..=255).text("Upper Threshold"));
+                ui.add(
+                    Slider::new(
+                        &mut scene.lower_threshold,
+                        value_range.min..=value_range.max,
+                    )
+                    .text("Lower Threshold"),
+                );
+                ui.add(
+                    Slider::new(
+                        &mut scene.upper_threshold,
+                        value_range.min..=value_range.max,
+                    )
+                    .text("Upper Threshold"),
+                );
                 ui.radio_value(
                     &mut scene.shader_type,
                     ShaderType::DefaultShader,
@@ -38,6 +672,11 @@ impl UserInterface {
                 );
                 ui.radio_value(&mut scene.shader_type, ShaderType::MipShader, "MIP shader");
                 ui.radio_value(&mut scene.shader_type, ShaderType::AipShader, "AIP shader");
+                ui.radio_value(
+                    &mut scene.shader_type,
+                    ShaderType::TransferFunction2DShader,
+                    "2D transfer function shader",
+                );
             });
         });
     }
@@ -1,34 +1,545 @@
-use byteorder::LittleEndian;
-use byteorder::ReadBytesExt;
-use dicom_object::open_file;
-use dicom_object::FileDicomObject;
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::encoding::TransferSyntaxIndex;
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+use dicom_object::mem::InMemElement;
+use dicom_object::DefaultDicomObject;
 use dicom_object::InMemDicomObject;
-use dicom_pixeldata::ndarray;
-use dicom_pixeldata::ndarray::ArrayBase;
-use dicom_pixeldata::ndarray::OwnedRepr;
+use dicom_pixeldata::ConvertOptions;
+use dicom_pixeldata::ModalityLutOption;
 use dicom_pixeldata::PixelDecoder;
+use dicom_series::largest_series;
+use format::LoaderRegistry;
+use gradient::GradientVolume;
+use loading::{file_size, LoadProgress};
+use memory::{FileSource, FileSystem};
+use meta_image::MetaImageError;
+use nifti::NiftiError;
+use nrrd::NrrdError;
+use orientation::Orientation;
 use rayon::prelude::*;
-use std::env;
-use std::ffi::OsString;
-use std::fs::read_to_string;
-use std::fs::DirEntry;
-use std::fs::File;
-use std::io::BufReader;
-use three_d::f16;
-use three_d::TextureData;
-use three_d_asset::Texture3D;
-use tracing::debug;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+use three_d_asset::io::RawAssets;
+use three_d_asset::{TextureData, VoxelGrid};
 
-#[derive(Debug, PartialEq)]
+pub mod archive;
+pub mod dicom_dir;
+pub mod dicom_series;
+pub mod format;
+pub mod gradient;
+pub mod loading;
+pub mod memory;
+pub mod meta_image;
+pub mod nifti;
+pub mod nrrd;
+pub mod orientation;
+pub mod raw;
+pub mod resample;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Dim {
     pub width: i32,
     pub height: i32,
     pub depth: i32,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Spacing {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Default for Spacing {
+    fn default() -> Self {
+        Spacing {
+            x: 1.0,
+            y: 1.0,
+            z: 1.0,
+        }
+    }
+}
+
+/// Range of the voxel values in their native units (e.g. Hounsfield units).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ValueRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for ValueRange {
+    fn default() -> Self {
+        ValueRange {
+            min: 0.0,
+            max: 255.0,
+        }
+    }
+}
+
+impl ValueRange {
+    pub fn from_values<T: Copy + Into<f32> + Sync>(values: &[T]) -> Self {
+        let (min, max) = values
+            .par_iter()
+            .fold(
+                || (f32::MAX, f32::MIN),
+                |(min, max), &value| (min.min(value.into()), max.max(value.into())),
+            )
+            .reduce(
+                || (f32::MAX, f32::MIN),
+                |(a_min, a_max), (b_min, b_max)| (a_min.min(b_min), a_max.max(b_max)),
+            );
+        if min > max {
+            return ValueRange::default();
+        }
+        ValueRange { min, max }
+    }
+
+    /// Maps a value inside the range to [0, 1].
+    pub fn normalize(&self, value: f32) -> f32 {
+        if self.max <= self.min {
+            return 0.0;
+        }
+        ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+
+    /// Maps a value in [0, 1] back into the range.
+    pub fn denormalize(&self, value: f32) -> f32 {
+        self.min + value * (self.max - self.min)
+    }
+}
+
+/// Voxel samples in their native type, so no precision is lost before they reach the GPU.
+#[derive(Debug, PartialEq, Clone)]
+pub enum VoxelData {
+    U8(Vec<u8>),
+    I16(Vec<i16>),
+    U16(Vec<u16>),
+    F32(Vec<f32>),
+}
+
+impl VoxelData {
+    /// Stores the values as i16 if all of them are integers in the i16 range, which is the case
+    /// for most CT data, and as f32 otherwise.
+    pub fn from_values(values: Vec<f32>) -> Self {
+        let fits_i16 = values.par_iter().all(|&value| {
+            value.fract() == 0.0 && value >= i16::MIN as f32 && value <= i16::MAX as f32
+        });
+        if fits_i16 {
+            VoxelData::I16(values.par_iter().map(|&value| value as i16).collect())
+        } else {
+            VoxelData::F32(values)
+        }
+    }
+
+    /// Converts the samples to f32.
+    pub fn to_values(&self) -> Vec<f32> {
+        match self {
+            VoxelData::U8(data) => data.par_iter().map(|&value| value as f32).collect(),
+            VoxelData::I16(data) => data.par_iter().map(|&value| value as f32).collect(),
+            VoxelData::U16(data) => data.par_iter().map(|&value| value as f32).collect(),
+            VoxelData::F32(data) => data.clone(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            VoxelData::U8(data) => data.len(),
+            VoxelData::I16(data) => data.len(),
+            VoxelData::U16(data) => data.len(),
+            VoxelData::F32(data) => data.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn value_range(&self) -> ValueRange {
+        match self {
+            VoxelData::U8(data) => ValueRange::from_values(data),
+            VoxelData::I16(data) => ValueRange::from_values(data),
+            VoxelData::U16(data) => ValueRange::from_values(data),
+            VoxelData::F32(data) => ValueRange::from_values(data),
+        }
+    }
+}
+
+impl From<Vec<u8>> for VoxelData {
+    fn from(data: Vec<u8>) -> Self {
+        VoxelData::U8(data)
+    }
+}
+
+impl From<Vec<i16>> for VoxelData {
+    fn from(data: Vec<i16>) -> Self {
+        VoxelData::I16(data)
+    }
+}
+
+impl From<Vec<u16>> for VoxelData {
+    fn from(data: Vec<u16>) -> Self {
+        VoxelData::U16(data)
+    }
+}
+
+impl From<Vec<f32>> for VoxelData {
+    fn from(data: Vec<f32>) -> Self {
+        VoxelData::F32(data)
+    }
+}
+
+/// Sample type of voxel data as it is stored in a file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ElementType {
+    U8,
+    I8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ElementType {
+    pub fn size(&self) -> usize {
+        match self {
+            ElementType::U8 | ElementType::I8 => 1,
+            ElementType::I16 | ElementType::U16 => 2,
+            ElementType::I32 | ElementType::U32 | ElementType::F32 => 4,
+            ElementType::F64 => 8,
+        }
+    }
+
+    /// Converts `count` raw samples into native voxel data. Types without a matching VoxelData
+    /// variant are widened to i16 or f32.
+    pub fn decode(
+        &self,
+        data: &[u8],
+        big_endian: bool,
+        count: usize,
+    ) -> std::io::Result<VoxelData> {
+        if big_endian {
+            decode_elements::<BigEndian>(data, *self, count)
+        } else {
+            decode_elements::<LittleEndian>(data, *self, count)
+        }
+    }
+}
+
+fn decode_elements<B: ByteOrder>(
+    mut data: &[u8],
+    element_type: ElementType,
+    count: usize,
+) -> std::io::Result<VoxelData> {
+    if data.len() < count * element_type.size() {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(match element_type {
+        ElementType::U8 => VoxelData::U8(data[..count].to_vec()),
+        ElementType::I8 => VoxelData::I16(
+            data[..count]
+                .iter()
+                .map(|&value| value as i8 as i16)
+                .collect(),
+        ),
+        ElementType::I16 => {
+            let mut values = vec![0i16; count];
+            data.read_i16_into::<B>(&mut values)?;
+            VoxelData::I16(values)
+        }
+        ElementType::U16 => {
+            let mut values = vec![0u16; count];
+            data.read_u16_into::<B>(&mut values)?;
+            VoxelData::U16(values)
+        }
+        ElementType::I32 => {
+            let mut values = vec![0i32; count];
+            data.read_i32_into::<B>(&mut values)?;
+            VoxelData::from_values(values.into_iter().map(|value| value as f32).collect())
+        }
+        ElementType::U32 => {
+            let mut values = vec![0u32; count];
+            data.read_u32_into::<B>(&mut values)?;
+            VoxelData::from_values(values.into_iter().map(|value| value as f32).collect())
+        }
+        ElementType::F32 => {
+            let mut values = vec![0f32; count];
+            data.read_f32_into::<B>(&mut values)?;
+            VoxelData::F32(values)
+        }
+        ElementType::F64 => {
+            let mut values = vec![0f64; count];
+            data.read_f64_into::<B>(&mut values)?;
+            VoxelData::F32(values.into_iter().map(|value| value as f32).collect())
+        }
+    })
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Texture {
-    pub texture_data: Vec<u8>,
+    pub texture_data: VoxelData,
     pub dimensions: Dim,
+    pub spacing: Spacing,
+    pub value_range: ValueRange,
+    /// Position and axis directions in patient coordinates.
+    pub orientation: Orientation,
+}
+
+impl Texture {
+    /// Size of the volume along x, y and z in the unit of the spacing, usually millimetres.
+    pub fn physical_size(&self) -> [f32; 3] {
+        [
+            self.dimensions.width as f32 * self.spacing.x,
+            self.dimensions.height as f32 * self.spacing.y,
+            self.dimensions.depth as f32 * self.spacing.z,
+        ]
+    }
+}
+
+/// What the DICOM loader does with a file that cannot be read, for example because its pixel data
+/// uses an unsupported transfer syntax.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum DecodeFailurePolicy {
+    /// Fail with the error of the file.
+    #[default]
+    Abort,
+    /// Leave out the file and report its error with the volume.
+    Skip,
+}
+
+pub struct DicomSlice {
+    pub path: PathBuf,
+    pub position: Option<[f64; 3]>,
+    pub orientation: Option<[f64; 6]>,
+    pub rows: u32,
+    pub columns: u32,
+    pub pixel_spacing: Option<[f64; 2]>,
+    pub slice_thickness: Option<f64>,
+    pub pixel_data: Vec<f32>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SliceGeometryError {
+    MissingGeometry(PathBuf),
+    InconsistentOrientation(PathBuf),
+    InconsistentDimensions(PathBuf),
+    InconsistentPixelSpacing(PathBuf),
+    DuplicatePosition(PathBuf, PathBuf),
+    MissingSlice {
+        after: f64,
+        before: f64,
+        spacing: f64,
+    },
+}
+
+impl fmt::Display for SliceGeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SliceGeometryError::MissingGeometry(path) => write!(
+                f,
+                "{} has no ImagePositionPatient/ImageOrientationPatient",
+                path.display()
+            ),
+            SliceGeometryError::InconsistentOrientation(path) => write!(
+                f,
+                "{} has a different ImageOrientationPatient than the rest of the series",
+                path.display()
+            ),
+            SliceGeometryError::InconsistentDimensions(path) => write!(
+                f,
+                "{} has different Rows/Columns than the rest of the series",
+                path.display()
+            ),
+            SliceGeometryError::InconsistentPixelSpacing(path) => write!(
+                f,
+                "{} has a different PixelSpacing than the rest of the series",
+                path.display()
+            ),
+            SliceGeometryError::DuplicatePosition(first, second) => write!(
+                f,
+                "{} and {} share the same slice position",
+                first.display(),
+                second.display()
+            ),
+            SliceGeometryError::MissingSlice {
+                after,
+                before,
+                spacing,
+            } => write!(
+                f,
+                "gap between slice positions {:.3} and {:.3} exceeds the slice spacing of {:.3}",
+                after, before, spacing
+            ),
+        }
+    }
+}
+
+/// Error returned by the volume loaders.
+#[derive(Debug)]
+pub enum VolumeError {
+    Io(PathBuf, std::io::Error),
+    /// No loader recognizes the file or directory.
+    UnsupportedFormat(PathBuf),
+    /// The directory or file does not contain any image.
+    NoImages(PathBuf),
+    MalformedHeader(PathBuf, String),
+    UnsupportedTransferSyntax {
+        path: PathBuf,
+        transfer_syntax: String,
+    },
+    PixelData {
+        path: PathBuf,
+        transfer_syntax: String,
+        reason: String,
+    },
+    SliceGeometry(SliceGeometryError),
+    MetaImage(MetaImageError),
+    Nrrd(NrrdError),
+    Nifti(NiftiError),
+    /// A zip or tar archive cannot be extracted.
+    Archive(PathBuf, String),
+    /// The load was cancelled through its [LoadProgress].
+    Cancelled,
+}
+
+impl fmt::Display for VolumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VolumeError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            VolumeError::UnsupportedFormat(path) => {
+                write!(f, "{} has an unknown volume format", path.display())
+            }
+            VolumeError::NoImages(path) => write!(f, "{} contains no images", path.display()),
+            VolumeError::MalformedHeader(path, reason) => {
+                write!(f, "{} has a malformed header: {}", path.display(), reason)
+            }
+            VolumeError::UnsupportedTransferSyntax {
+                path,
+                transfer_syntax,
+            } => write!(
+                f,
+                "{} uses the unsupported transfer syntax {}",
+                path.display(),
+                transfer_syntax
+            ),
+            VolumeError::PixelData {
+                path,
+                transfer_syntax,
+                reason,
+            } => write!(
+                f,
+                "cannot decode pixel data of {} with transfer syntax {}: {}",
+                path.display(),
+                transfer_syntax,
+                reason
+            ),
+            VolumeError::SliceGeometry(error) => write!(f, "inconsistent slices: {}", error),
+            VolumeError::MetaImage(error) => write!(f, "invalid MetaImage: {}", error),
+            VolumeError::Nrrd(error) => write!(f, "invalid NRRD: {}", error),
+            VolumeError::Nifti(error) => write!(f, "invalid NIfTI: {}", error),
+            VolumeError::Archive(path, reason) => {
+                write!(f, "cannot extract archive {}: {}", path.display(), reason)
+            }
+            VolumeError::Cancelled => write!(f, "loading was cancelled"),
+        }
+    }
+}
+
+impl From<SliceGeometryError> for VolumeError {
+    fn from(error: SliceGeometryError) -> Self {
+        VolumeError::SliceGeometry(error)
+    }
+}
+
+impl From<MetaImageError> for VolumeError {
+    fn from(error: MetaImageError) -> Self {
+        match error {
+            MetaImageError::Io(path, error) => VolumeError::Io(path, error),
+            error => VolumeError::MetaImage(error),
+        }
+    }
+}
+
+impl From<NrrdError> for VolumeError {
+    fn from(error: NrrdError) -> Self {
+        match error {
+            NrrdError::Io(path, error) => VolumeError::Io(path, error),
+            error => VolumeError::Nrrd(error),
+        }
+    }
+}
+
+impl From<NiftiError> for VolumeError {
+    fn from(error: NiftiError) -> Self {
+        match error {
+            NiftiError::Io(path, error) => VolumeError::Io(path, error),
+            error => VolumeError::Nifti(error),
+        }
+    }
+}
+
+const ORIENTATION_TOLERANCE: f64 = 1e-4;
+const POSITION_TOLERANCE: f64 = 1e-3;
+
+fn slice_normal(orientation: &[f64; 6]) -> [f64; 3] {
+    let row = &orientation[0..3];
+    let column = &orientation[3..6];
+    [
+        row[1] * column[2] - row[2] * column[1],
+        row[2] * column[0] - row[0] * column[2],
+        row[0] * column[1] - row[1] * column[0],
+    ]
+}
+
+fn first_item(object: &InMemDicomObject, sequence: Tag) -> Option<&InMemDicomObject> {
+    object.element(sequence).ok()?.items()?.first()
+}
+
+/// Functional groups of one frame of an enhanced multi-frame object.
+struct FunctionalGroups<'a> {
+    per_frame: Option<&'a InMemDicomObject>,
+    shared: Option<&'a InMemDicomObject>,
+    file: &'a InMemDicomObject,
+}
+
+impl<'a> FunctionalGroups<'a> {
+    /// Looks up an attribute in the functional group macro `sequence` of the frame, then in the
+    /// shared functional groups and finally at the top level, as in single-frame objects.
+    fn element(&self, sequence: Tag, tag: Tag) -> Option<&'a InMemElement> {
+        [self.per_frame, self.shared]
+            .into_iter()
+            .flatten()
+            .filter_map(|groups| first_item(groups, sequence))
+            .chain(std::iter::once(self.file))
+            .find_map(|object| object.element(tag).ok())
+    }
+
+    fn read_multi_float64<const N: usize>(&self, sequence: Tag, tag: Tag) -> Option<[f64; N]> {
+        let values = self.element(sequence, tag)?.to_multi_float64().ok()?;
+        values.try_into().ok()
+    }
+}
+
+/// Describes the transfer syntax of a file and whether a decoder for its pixel data is built in.
+/// JPEG baseline, extended and lossless as well as RLE are decoded by the native codecs of
+/// dicom-pixeldata. JPEG 2000 and JPEG-LS need codecs that are not enabled.
+fn transfer_syntax(file: &DefaultDicomObject) -> (String, bool) {
+    let uid = file.meta().transfer_syntax();
+    match TransferSyntaxRegistry.get(uid) {
+        Some(transfer_syntax) => (
+            format!("{} ({})", transfer_syntax.name(), uid),
+            !transfer_syntax.is_unsupported_pixel_encapsulation(),
+        ),
+        None => (uid.to_string(), false),
+    }
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
 }
 
 pub struct Volume {
@@ -36,10 +547,40 @@ pub struct Volume {
     pub indices: [u32; 36],
     pub texture: Texture,
     pub histogram: Vec<f64>,
+    /// Gradient magnitudes sampled by the 2D transfer function, which are computed in the
+    /// background when they are first needed, see [loading::LoadRequest::Gradient].
+    pub gradient: Option<GradientVolume>,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Volume {
+    /// Creates an empty volume with a single voxel, which is shown until a dataset is loaded.
     pub fn new() -> Self {
+        let texture_data = VoxelData::U8(vec![0]);
+        Volume::from_texture(Texture {
+            value_range: ValueRange::default(),
+            dimensions: Dim {
+                width: 1,
+                height: 1,
+                depth: 1,
+            },
+            spacing: Spacing::default(),
+            texture_data,
+            orientation: Orientation::default(),
+        })
+    }
+
+    /// Detects the format of a file or directory and reads it with the matching loader.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, VolumeError> {
+        LoaderRegistry::default().open(path.as_ref())
+    }
+
+    pub fn from_texture(texture: Texture) -> Self {
         let vertex_data = [
             -0.5, -0.5, 0.5, 0.5, -0.5, 0.5, 0.5, 0.5, 0.5, -0.5, 0.5, 0.5, -0.5, -0.5, -0.5, 0.5,
             -0.5, -0.5, 0.5, 0.5, -0.5, -0.5, 0.5, -0.5,
@@ -54,177 +595,551 @@ impl Volume {
             4, 5, 1, 4, 1, 0, // bottom
         ];
 
-        // TODO: Automatically detect file format and use specific loader.
-        // let result = Volume::read_vol("assets/data/Skull.vol");
-        // let texture = Volume::read_raw(
-        //     "assets/data/FullHead.raw",
-        //     "assets/data/FullHead.mhd",
-        // );
-        let texture = Volume::read_dicom("assets/data/DCM_0000/");
-
-        let histogram = Volume::calculate_histogram(&texture.texture_data);
+        let histogram = Volume::calculate_histogram(&texture.texture_data, &texture.value_range);
 
         Volume {
             vertex_data,
             indices,
             texture,
             histogram,
+            gradient: None,
+        }
+    }
+
+    pub fn value_range(&self) -> ValueRange {
+        self.texture.value_range
+    }
+
+    /// Size of the volume scaled so that its longest axis is 1, which is the scale of the unit cube
+    /// the volume is rendered on. Volumes without a valid spacing are drawn as a cube.
+    pub fn extent(&self) -> [f32; 3] {
+        let size = self.texture.physical_size();
+        let longest = size[0].max(size[1]).max(size[2]);
+        if !longest.is_finite() || size.iter().any(|&length| length <= 0.0) {
+            return [1.0; 3];
         }
+        size.map(|length| length / longest)
     }
 
-    pub fn read_dicom(directory_path: &str) -> Texture {
-        println!("{:?}", env::current_dir());
-        let directory_path = OsString::from(directory_path);
-        let directory_files = std::fs::read_dir(&directory_path).unwrap();
-        let mut sorted_files: Vec<DirEntry> = directory_files.filter_map(Result::ok).collect();
-        // TODO: Sort by capture direction
-        sorted_files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    /// Reads the DICOM series with the most slices in a directory and its subdirectories. Use
+    /// [Volume::scan_dicom_series] to choose another series.
+    pub fn read_dicom(directory_path: &str) -> Result<Texture, VolumeError> {
+        let directory_path = Path::new(directory_path);
+        let series = Volume::scan_dicom_series(directory_path)?;
+        let index = largest_series(&series)
+            .ok_or_else(|| VolumeError::NoImages(directory_path.to_path_buf()))?;
+        if series.len() > 1 {
+            log::info!(
+                "{} contains {} series, reading {}",
+                directory_path.display(),
+                series.len(),
+                series[index].label()
+            );
+        }
+        Volume::read_dicom_series(&series[index])
+    }
 
-        let decoded_pixel_data: Vec<_> = sorted_files
+    /// Reads the given DICOM files as the slices of one volume. Files that are not DICOM or contain
+    /// no image are skipped, and any other file that cannot be read fails the whole volume.
+    pub fn read_dicom_files(files: &[PathBuf]) -> Result<Texture, VolumeError> {
+        Volume::read_dicom_files_with(files, DecodeFailurePolicy::Abort).map(|(texture, _)| texture)
+    }
+
+    /// Reads the given DICOM files as the slices of one volume. Files that cannot be read, such as
+    /// files with an unsupported transfer syntax, are handled according to the policy. Returns the
+    /// volume together with the errors of the skipped files.
+    pub fn read_dicom_files_with(
+        files: &[PathBuf],
+        policy: DecodeFailurePolicy,
+    ) -> Result<(Texture, Vec<VolumeError>), VolumeError> {
+        Volume::read_dicom_files_with_progress(files, policy, &LoadProgress::default())
+    }
+
+    /// Like [Volume::read_dicom_files_with], but counts the files and bytes read in `progress` and
+    /// stops with [VolumeError::Cancelled] once the load is cancelled, whatever the policy.
+    pub fn read_dicom_files_with_progress(
+        files: &[PathBuf],
+        policy: DecodeFailurePolicy,
+        progress: &LoadProgress,
+    ) -> Result<(Texture, Vec<VolumeError>), VolumeError> {
+        Volume::read_dicom_slices(&FileSystem, files, policy, progress)
+    }
+
+    fn read_dicom_slices(
+        source: &dyn FileSource,
+        files: &[PathBuf],
+        policy: DecodeFailurePolicy,
+        progress: &LoadProgress,
+    ) -> Result<(Texture, Vec<VolumeError>), VolumeError> {
+        progress.set_files_total(files.len());
+        let results: Vec<Result<Vec<DicomSlice>, VolumeError>> = files
             .par_iter()
-            .flat_map(|file| {
-                let mut path = OsString::from(&directory_path);
-                let file_name = file.file_name();
-                path.push(&file_name);
-
-                let file = open_file(path.to_str().unwrap()).unwrap();
-                let pixel_data = file.decode_pixel_data().unwrap();
-
-                let vec = pixel_data.to_vec::<f32>().unwrap();
-                vec.iter()
-                    .map(|pixel_data| {
-                        // Shift from -1024-1024 to 0-255
-                        return ((pixel_data + 1024.0) / 2048.0 * 255.0) as u8;
-                    })
-                    .collect::<Vec<u8>>()
+            .map(|path| {
+                progress.check_cancelled()?;
+                let frames = Volume::read_dicom_frames(source, path);
+                progress.file_done(file_size(path));
+                frames
             })
             .collect();
+        progress.check_cancelled()?;
+        let mut slices = Vec::new();
+        let mut skipped = Vec::new();
+        for result in results {
+            match (result, policy) {
+                (Ok(frames), _) => slices.extend(frames),
+                (Err(error), DecodeFailurePolicy::Skip) => {
+                    log::warn!("Skipping file: {}", error);
+                    skipped.push(error);
+                }
+                (Err(error), DecodeFailurePolicy::Abort) => return Err(error),
+            }
+        }
+        if slices.is_empty() {
+            let path = files.first().cloned().unwrap_or_default();
+            return Err(VolumeError::NoImages(path));
+        }
 
-        Texture {
+        let (rows, columns, pixel_spacing) = Volume::validate_slice_dimensions(&slices)?;
+        let (order, slice_distance) = Volume::sort_slices_by_position(&slices)?;
+        let slice_spacing = slice_distance
+            .or_else(|| slices.first().and_then(|slice| slice.slice_thickness))
+            .unwrap_or(1.0);
+        let depth = order.len() as i32;
+        let orientation = Volume::slice_orientation(&slices[order[0]]);
+        let values: Vec<f32> = order
+            .into_iter()
+            .flat_map(|index| slices[index].pixel_data.iter().copied())
+            .collect();
+        let value_range = ValueRange::from_values(&values);
+        let texture_data = VoxelData::from_values(values);
+
+        let texture = Texture {
             dimensions: Dim {
-                width: 512,
-                height: 512,
-                depth: sorted_files.len() as i32,
+                width: columns as i32,
+                height: rows as i32,
+                depth,
+            },
+            spacing: Spacing {
+                x: pixel_spacing[1] as f32,
+                y: pixel_spacing[0] as f32,
+                z: slice_spacing as f32,
             },
-            texture_data: decoded_pixel_data,
-        }
-    }
-
-    // pub fn read_vol(file_path: &str) -> Texture {
-    //     let texture_3d: Texture3D = three_d_asset::io::load(&[file_path])
-    //         .unwrap()
-    //         .deserialize("")
-    //         .unwrap();
-    //     let width = texture_3d.width as i32;
-    //     let height = texture_3d.height as i32;
-    //     let depth = texture_3d.depth as i32;
-    //     let texture_data = match texture_3d.data {
-    //         TextureData::RU8(data) => data,
-    //         _ => panic!("Expected RU8 texture data format"),
-    //     };
-    //
-    //     Texture {
-    //         dimensions: Dim {
-    //             width,
-    //             height,
-    //             depth,
-    //         },
-    //         texture_data,
-    //     }
-    // }
-
-    // pub fn read_raw(file_path: &str, meta_file_path: &str) -> Texture {
-    //     #[cfg(not(target_arch = "wasm32"))]
-    //     let meta_data = read_to_string(meta_file_path).expect("Unable to read MHD file");
-    //     #[cfg(target_arch = "wasm32")]
-    //     let meta_data = include_str!("../assets/data/sinus.mhd");
-    //     let dimensions = Volume::parse_meta_data_dim(&meta_data);
-    //
-    //     let num_elements = dimensions.height * dimensions.width * dimensions.depth;
-    //     #[cfg(not(target_arch = "wasm32"))]
-    //     let file = File::open(file_path).expect("Unable to open RAW file");
-    //     #[cfg(target_arch = "wasm32")]
-    //     let file: &[u8] = include_bytes!("../assets/data/sinus.raw");
-    //     let mut reader = BufReader::new(file);
-    //
-    //     let mut raw_data = vec![0u16; num_elements as usize];
-    //     reader
-    //         .read_u16_into::<LittleEndian>(&mut raw_data)
-    //         .expect("Unable to read u16 from RAW file");
-    //
-    //     let buffer: Vec<u8> = raw_data
-    //         .par_iter()
-    //         .map(|&value| Volume::normalize_hounsfield_units(value))
-    //         .collect();
-    //
-    //     Texture {
-    //         dimensions: Dim {
-    //             width: dimensions.width,
-    //             height: dimensions.height,
-    //             depth: dimensions.depth,
-    //         },
-    //         texture_data: buffer,
-    //     }
-    // }
+            value_range,
+            texture_data,
+            orientation,
+        };
+        Ok((texture, skipped))
+    }
 
-    pub fn normalize_hounsfield_units(value: u16) -> u8 {
-        let normalized_hu_value = (value as f32 / 4095.0) * 255.0; // Normalize to [0, 255]
-        normalized_hu_value as u8
+    /// Returns the orientation of a volume whose first slice is `first`. The x and y axes follow
+    /// the rows and columns of ImageOrientationPatient, and the slices are stacked along the slice
+    /// normal, as ordered by [Volume::sort_slices_by_position].
+    fn slice_orientation(first: &DicomSlice) -> Orientation {
+        match (first.position, first.orientation) {
+            (Some(position), Some(orientation)) => Orientation {
+                directions: [
+                    [orientation[0], orientation[1], orientation[2]],
+                    [orientation[3], orientation[4], orientation[5]],
+                    slice_normal(&orientation),
+                ],
+                origin: position,
+            },
+            _ => Orientation::default(),
+        }
     }
 
-    pub fn parse_meta_data_dim(meta_data: &str) -> Dim {
-        let mut dimensions = Dim {
-            width: 0,
-            height: 0,
-            depth: 0,
+    /// Reads the geometry and pixel data of every frame of a file. Enhanced multi-frame objects
+    /// take the geometry and rescale values of each frame from the per-frame and shared functional
+    /// groups. Returns no slices if the file is not DICOM or has no pixel data.
+    fn read_dicom_frames(
+        source: &dyn FileSource,
+        path: &Path,
+    ) -> Result<Vec<DicomSlice>, VolumeError> {
+        let Ok(file) = source.open_dicom(path, None) else {
+            log::warn!("Skipping {}, which is not a DICOM file", path.display());
+            return Ok(Vec::new());
         };
+        if file.element(tags::PIXEL_DATA).is_err() {
+            log::info!("Skipping {}, which has no pixel data", path.display());
+            return Ok(Vec::new());
+        }
+        let read_int = |tag: Tag, name: &str| {
+            file.element(tag)
+                .ok()
+                .and_then(|element| element.to_int::<u32>().ok())
+                .ok_or_else(|| {
+                    VolumeError::MalformedHeader(path.to_path_buf(), format!("missing {}", name))
+                })
+        };
+        let rows = read_int(tags::ROWS, "Rows")?;
+        let columns = read_int(tags::COLUMNS, "Columns")?;
+        let frame_count = read_int(tags::NUMBER_OF_FRAMES, "NumberOfFrames").unwrap_or(1);
 
-        meta_data.lines().for_each(|line| {
-            let mut data = line.split(" = ");
-
-            match data.next() {
-                Some("NDims") => {
-                    let _value = data.next().unwrap().parse::<i32>().unwrap();
-                    // TODO: Use NDims info.
-                }
-                Some("DimSize") => {
-                    let value = data
-                        .next()
-                        .unwrap()
-                        .split(" ")
-                        .map(|x| x.parse::<i32>().unwrap())
-                        .collect::<Vec<i32>>();
-                    dimensions.width = value[0];
-                    dimensions.height = value[1];
-                    dimensions.depth = value[2];
-                }
-                Some("ElementSpacing") => {
-                    let _value = data.next().unwrap();
-                    // TODO: Use ElementSpacing info.
+        let options = ConvertOptions::new().with_modality_lut(ModalityLutOption::None);
+        let pixel_data = file
+            .decode_pixel_data()
+            .map_err(|error| error.to_string())
+            .and_then(|decoded| {
+                decoded
+                    .to_vec_with_options::<f32>(&options)
+                    .map_err(|error| error.to_string())
+            })
+            .map_err(|reason| {
+                let (transfer_syntax, supported) = transfer_syntax(&file);
+                if supported {
+                    VolumeError::PixelData {
+                        path: path.to_path_buf(),
+                        transfer_syntax,
+                        reason,
+                    }
+                } else {
+                    VolumeError::UnsupportedTransferSyntax {
+                        path: path.to_path_buf(),
+                        transfer_syntax,
+                    }
                 }
-                _ => {
-                    println!("Unknown field");
+            })?;
+        let frame_size = (rows * columns) as usize;
+        if pixel_data.len() < frame_size * frame_count as usize {
+            return Err(VolumeError::PixelData {
+                path: path.to_path_buf(),
+                transfer_syntax: transfer_syntax(&file).0,
+                reason: format!(
+                    "expected {} frames of {}x{} pixels",
+                    frame_count, columns, rows
+                ),
+            });
+        }
+
+        let shared_groups = first_item(&file, tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE);
+        let per_frame_groups = file
+            .element(tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE)
+            .ok()
+            .and_then(|element| element.items());
+
+        let slices = pixel_data
+            .chunks_exact(frame_size)
+            .take(frame_count as usize)
+            .enumerate()
+            .map(|(frame, frame_data)| {
+                let groups = FunctionalGroups {
+                    per_frame: per_frame_groups.and_then(|items| items.get(frame)),
+                    shared: shared_groups,
+                    file: &file,
+                };
+                let position = groups.read_multi_float64(
+                    tags::PLANE_POSITION_SEQUENCE,
+                    tags::IMAGE_POSITION_PATIENT,
+                );
+                let orientation = groups.read_multi_float64(
+                    tags::PLANE_ORIENTATION_SEQUENCE,
+                    tags::IMAGE_ORIENTATION_PATIENT,
+                );
+                let pixel_spacing =
+                    groups.read_multi_float64(tags::PIXEL_MEASURES_SEQUENCE, tags::PIXEL_SPACING);
+                let slice_thickness = groups
+                    .element(tags::PIXEL_MEASURES_SEQUENCE, tags::SLICE_THICKNESS)
+                    .and_then(|element| element.to_float64().ok());
+                let rescale_slope = groups
+                    .element(
+                        tags::PIXEL_VALUE_TRANSFORMATION_SEQUENCE,
+                        tags::RESCALE_SLOPE,
+                    )
+                    .and_then(|element| element.to_float32().ok())
+                    .unwrap_or(1.0);
+                let rescale_intercept = groups
+                    .element(
+                        tags::PIXEL_VALUE_TRANSFORMATION_SEQUENCE,
+                        tags::RESCALE_INTERCEPT,
+                    )
+                    .and_then(|element| element.to_float32().ok())
+                    .unwrap_or(0.0);
+
+                let mut pixel_data = frame_data.to_vec();
+                Volume::apply_modality_lut(&mut pixel_data, rescale_slope, rescale_intercept);
+
+                DicomSlice {
+                    // The frame number is appended so that geometry errors name the frame.
+                    path: if frame_count > 1 {
+                        PathBuf::from(format!("{} frame {}", path.display(), frame + 1))
+                    } else {
+                        path.to_path_buf()
+                    },
+                    position,
+                    orientation,
+                    rows,
+                    columns,
+                    pixel_spacing,
+                    slice_thickness,
+                    pixel_data,
                 }
+            })
+            .collect();
+        Ok(slices)
+    }
+
+    /// Applies the linear modality LUT (RescaleSlope/RescaleIntercept), which turns stored pixel
+    /// values into output units such as Hounsfield units.
+    pub fn apply_modality_lut(values: &mut [f32], slope: f32, intercept: f32) {
+        values
+            .par_iter_mut()
+            .for_each(|value| *value = *value * slope + intercept);
+    }
+
+    /// Checks that all slices share Rows, Columns and PixelSpacing and returns them. PixelSpacing
+    /// is given as (row spacing, column spacing) and defaults to 1mm if it is absent.
+    pub fn validate_slice_dimensions(
+        slices: &[DicomSlice],
+    ) -> Result<(u32, u32, [f64; 2]), SliceGeometryError> {
+        let Some(first) = slices.first() else {
+            return Ok((0, 0, [1.0, 1.0]));
+        };
+        for slice in slices {
+            if slice.rows != first.rows || slice.columns != first.columns {
+                return Err(SliceGeometryError::InconsistentDimensions(
+                    slice.path.clone(),
+                ));
             }
-        });
+            let spacing_matches = match (slice.pixel_spacing, first.pixel_spacing) {
+                (Some(a), Some(b)) => a
+                    .iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| (a - b).abs() <= POSITION_TOLERANCE),
+                (None, None) => true,
+                _ => false,
+            };
+            if !spacing_matches {
+                return Err(SliceGeometryError::InconsistentPixelSpacing(
+                    slice.path.clone(),
+                ));
+            }
+        }
 
-        dimensions
+        Ok((
+            first.rows,
+            first.columns,
+            first.pixel_spacing.unwrap_or([1.0, 1.0]),
+        ))
     }
 
-    pub fn calculate_histogram(texture_data: &Vec<u8>) -> Vec<f64> {
-        let mut histogram = vec![0.0_f64; 256];
+    /// Returns the slice indices ordered along the slice normal, which is the cross product of the
+    /// row and column direction cosines of ImageOrientationPatient, together with the distance
+    /// between neighbouring slices if there is more than one.
+    pub fn sort_slices_by_position(
+        slices: &[DicomSlice],
+    ) -> Result<(Vec<usize>, Option<f64>), SliceGeometryError> {
+        let mut geometry = Vec::with_capacity(slices.len());
+        for slice in slices {
+            match (slice.position, slice.orientation) {
+                (Some(position), Some(orientation)) => geometry.push((position, orientation)),
+                _ => return Err(SliceGeometryError::MissingGeometry(slice.path.clone())),
+            }
+        }
+        let Some(&(_, orientation)) = geometry.first() else {
+            return Ok((Vec::new(), None));
+        };
+        if let Some(index) = geometry.iter().position(|(_, other)| {
+            other
+                .iter()
+                .zip(orientation.iter())
+                .any(|(a, b)| (a - b).abs() > ORIENTATION_TOLERANCE)
+        }) {
+            return Err(SliceGeometryError::InconsistentOrientation(
+                slices[index].path.clone(),
+            ));
+        }
 
-        texture_data.iter().for_each(|&value| {
-            histogram[value as usize] += 1.0_f64;
-        });
+        let normal = slice_normal(&orientation);
+        let distances: Vec<f64> = geometry
+            .iter()
+            .map(|(position, _)| dot(position, &normal))
+            .collect();
+        let mut order: Vec<usize> = (0..slices.len()).collect();
+        order.sort_by(|&a, &b| distances[a].total_cmp(&distances[b]));
 
-        histogram
+        let gaps: Vec<f64> = order
+            .windows(2)
+            .map(|pair| distances[pair[1]] - distances[pair[0]])
+            .collect();
+        if let Some(index) = gaps.iter().position(|&gap| gap < POSITION_TOLERANCE) {
+            return Err(SliceGeometryError::DuplicatePosition(
+                slices[order[index]].path.clone(),
+                slices[order[index + 1]].path.clone(),
+            ));
+        }
+
+        // The lower median, so that with one gap missing out of two the larger gap is reported.
+        let mut sorted_gaps = gaps.clone();
+        sorted_gaps.sort_by(f64::total_cmp);
+        let spacing = sorted_gaps
+            .get(sorted_gaps.len().saturating_sub(1) / 2)
+            .copied();
+        if let Some(spacing) = spacing {
+            if let Some(index) = gaps.iter().position(|&gap| gap > spacing * 1.5) {
+                return Err(SliceGeometryError::MissingSlice {
+                    after: distances[order[index]],
+                    before: distances[order[index + 1]],
+                    spacing,
+                });
+            }
+        }
+
+        Ok((order, spacing))
+    }
+
+    /// Reads a three-d-asset .vol file, which contains 8-bit voxels and the size of the volume.
+    pub fn read_vol(file_path: &str) -> Result<Texture, VolumeError> {
+        Volume::read_vol_assets(Path::new(file_path), three_d_asset::io::load(&[file_path]))
+    }
+
+    /// Deserializes the .vol file at `path` from loaded assets.
+    fn read_vol_assets(
+        path: &Path,
+        assets: three_d_asset::Result<RawAssets>,
+    ) -> Result<Texture, VolumeError> {
+        let malformed = |reason: String| VolumeError::MalformedHeader(path.to_path_buf(), reason);
+        let voxel_grid: VoxelGrid = assets
+            .and_then(|mut assets| assets.deserialize(path))
+            .map_err(|error| malformed(error.to_string()))?;
+        let voxels = voxel_grid.voxels;
+        let texture_data = match voxels.data {
+            TextureData::RU8(data) => VoxelData::U8(data),
+            _ => return Err(malformed("expected a single 8-bit channel".to_string())),
+        };
+        let spacing = |size: f32, count: u32| {
+            if size > 0.0 && count > 0 {
+                size / count as f32
+            } else {
+                1.0
+            }
+        };
+
+        Ok(Texture {
+            value_range: texture_data.value_range(),
+            dimensions: Dim {
+                width: voxels.width as i32,
+                height: voxels.height as i32,
+                depth: voxels.depth as i32,
+            },
+            spacing: Spacing {
+                x: spacing(voxel_grid.size.x, voxels.width),
+                y: spacing(voxel_grid.size.y, voxels.height),
+                z: spacing(voxel_grid.size.z, voxels.depth),
+            },
+            texture_data,
+            orientation: Orientation::default(),
+        })
+    }
+
+    pub fn normalize_hounsfield_units(value: u16) -> u8 {
+        let normalized_hu_value = (value as f32 / 4095.0) * 255.0; // Normalize to [0, 255]
+        normalized_hu_value as u8
+    }
+
+    /// Counts the texture values in 256 bins, which are spread evenly over the value range.
+    pub fn calculate_histogram(texture_data: &VoxelData, value_range: &ValueRange) -> Vec<f64> {
+        match texture_data {
+            VoxelData::U8(data) => histogram_of(data, value_range),
+            VoxelData::I16(data) => histogram_of(data, value_range),
+            VoxelData::U16(data) => histogram_of(data, value_range),
+            VoxelData::F32(data) => histogram_of(data, value_range),
+        }
+    }
+}
+
+fn histogram_of<T: Copy + Into<f32>>(values: &[T], value_range: &ValueRange) -> Vec<f64> {
+    let mut histogram = vec![0.0_f64; 256];
+
+    values.iter().for_each(|&value| {
+        let bin = (value_range.normalize(value.into()) * 255.0).round() as usize;
+        histogram[bin] += 1.0_f64;
+    });
+
+    histogram
+}
+
+/// Temporary files, the example CT slices and small textures, shared by the tests of the loaders.
+#[cfg(test)]
+pub(crate) mod test_files {
+    use super::orientation::Orientation;
+    use super::{Dim, Spacing, Texture, VoxelData};
+    use std::path::{Path, PathBuf};
+
+    /// Builds a texture with the given width, height and depth whose voxels take the value of
+    /// `voxel` at their index, in the sample type that `voxel` returns.
+    pub fn test_texture<T>(
+        dimensions: [i32; 3],
+        spacing: Spacing,
+        voxel: impl Fn(usize) -> T,
+    ) -> Texture
+    where
+        Vec<T>: Into<VoxelData>,
+    {
+        let [width, height, depth] = dimensions;
+        let count = (width * height * depth) as usize;
+        let texture_data: VoxelData = (0..count).map(voxel).collect::<Vec<T>>().into();
+        Texture {
+            value_range: texture_data.value_range(),
+            dimensions: Dim {
+                width,
+                height,
+                depth,
+            },
+            spacing,
+            texture_data,
+            orientation: Orientation::default(),
+        }
+    }
+
+    /// File names of the first slices of the example CT, in order.
+    pub const CT_SLICES: [&str; 3] = ["CT000001", "CT000002", "CT000003"];
+
+    pub fn slice_path(name: &str) -> PathBuf {
+        Path::new("assets/data/DCM_0000").join(name)
+    }
+
+    /// Creates a directory for a test under the temporary directory. Tests run in parallel, so
+    /// tests that write different files with the same name use different directories.
+    pub fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("med_ray_caster_{}", name));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    pub fn test_path(directory: &str, name: &str) -> PathBuf {
+        test_directory(directory).join(name)
+    }
+
+    pub fn write_test_file(directory: &str, name: &str, content: &[u8]) -> PathBuf {
+        let path = test_path(directory, name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// Creates a test directory holding the slices in [CT_SLICES].
+    pub fn ct_directory(name: &str) -> PathBuf {
+        let directory = test_directory(name);
+        copy_slices(&directory, &CT_SLICES);
+        directory
+    }
+
+    /// Copies slices of the example CT into a directory, keeping their names.
+    pub fn copy_slices(directory: &Path, names: &[&str]) {
+        std::fs::create_dir_all(directory).unwrap();
+        for name in names {
+            std::fs::copy(slice_path(name), directory.join(name)).unwrap();
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
+    use super::test_files::{ct_directory, test_directory};
     use super::*;
+    use dicom::core::value::{DataSetSequence, PixelFragmentSequence};
+    use dicom::core::{DataElement, PrimitiveValue, VR};
+    use dicom::dictionary_std::uids;
+    use dicom_object::meta::FileMetaTableBuilder;
 
     #[test]
     fn test_hounsfield_normalization() {
@@ -239,18 +1154,57 @@ mod test {
     }
 
     #[test]
-    fn test_parse_dim() {
-        let input = "NDims = 3\nDimSize = 512 512 333\nElementSpacing = 0.402344 0.402344 0.899994";
-        let expected = Dim {
+    fn test_modality_lut() {
+        let mut input: Vec<f32> = vec![0.0, 1024.0, 4095.0];
+        let expected: Vec<f32> = vec![-1024.0, 0.0, 3071.0];
+        Volume::apply_modality_lut(&mut input, 1.0, -1024.0);
+
+        assert_eq!(expected, input);
+    }
+
+    #[test]
+    fn test_extent() {
+        let mut texture = Volume::new().texture;
+        texture.dimensions = Dim {
             width: 512,
             height: 512,
-            depth: 333,
+            depth: 295,
         };
-        let result = Volume::parse_meta_data_dim(input);
+        texture.spacing = Spacing {
+            x: 0.5,
+            y: 0.5,
+            z: 1.25,
+        };
+
+        assert_eq!([256.0, 256.0, 368.75], texture.physical_size());
+        let extent = Volume::from_texture(texture).extent();
+        assert_eq!([256.0 / 368.75, 256.0 / 368.75, 1.0], extent);
+        assert_eq!([1.0; 3], Volume::new().extent());
+    }
+
+    #[test]
+    fn test_value_range() {
+        let input: Vec<f32> = vec![-1024.0, 0.0, 3071.0];
+        let expected = ValueRange {
+            min: -1024.0,
+            max: 3071.0,
+        };
+        let result = ValueRange::from_values(&input);
 
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_voxel_data_from_values() {
+        let input: Vec<f32> = vec![-1024.0, 0.0, 3071.0];
+        let expected = VoxelData::I16(vec![-1024, 0, 3071]);
+        assert_eq!(expected, VoxelData::from_values(input));
+
+        let input: Vec<f32> = vec![-1024.0, 0.5, 3071.0];
+        let expected = VoxelData::F32(vec![-1024.0, 0.5, 3071.0]);
+        assert_eq!(expected, VoxelData::from_values(input));
+    }
+
     #[test]
     fn test_histogram_calculation() {
         let input: Vec<u8> = vec![0, 0, 0, 128, 128, 128, 255, 255, 255];
@@ -258,13 +1212,423 @@ mod test {
         expected[0] = 3.0;
         expected[128] = 3.0;
         expected[255] = 3.0;
-        let result = Volume::calculate_histogram(&input);
+        let result = Volume::calculate_histogram(
+            &VoxelData::U8(input),
+            &ValueRange {
+                min: 0.0,
+                max: 255.0,
+            },
+        );
 
         assert_eq!(expected, result);
     }
 
+    fn axial_slice(name: &str, z: f64) -> DicomSlice {
+        DicomSlice {
+            path: PathBuf::from(name),
+            position: Some([-120.0, -120.0, z]),
+            orientation: Some([1.0, 0.0, 0.0, 0.0, 1.0, 0.0]),
+            rows: 512,
+            columns: 512,
+            pixel_spacing: Some([0.5, 0.5]),
+            slice_thickness: Some(2.5),
+            pixel_data: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_sort_slices_by_position() {
+        let input = vec![
+            axial_slice("a", 2.5),
+            axial_slice("b", -2.5),
+            axial_slice("c", 0.0),
+        ];
+        let result = Volume::sort_slices_by_position(&input);
+
+        assert_eq!(Ok((vec![1, 2, 0], Some(2.5))), result);
+    }
+
+    #[test]
+    fn test_slice_orientation() {
+        let mut slice = axial_slice("coronal", 0.0);
+        slice.position = Some([-120.0, 30.0, 80.0]);
+        slice.orientation = Some([1.0, 0.0, 0.0, 0.0, 0.0, -1.0]);
+        let orientation = Volume::slice_orientation(&slice);
+
+        assert_eq!(
+            [[1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+            orientation.directions
+        );
+        assert_eq!([-120.0, 30.0, 80.0], orientation.origin);
+    }
+
+    #[test]
+    fn test_sort_slices_duplicate_position() {
+        let input = vec![
+            axial_slice("a", 0.0),
+            axial_slice("b", 2.5),
+            axial_slice("c", 2.5),
+        ];
+        let result = Volume::sort_slices_by_position(&input);
+
+        assert_eq!(
+            Err(SliceGeometryError::DuplicatePosition(
+                PathBuf::from("b"),
+                PathBuf::from("c")
+            )),
+            result
+        );
+    }
+
+    #[test]
+    fn test_sort_slices_missing_slice() {
+        let input = vec![
+            axial_slice("a", 0.0),
+            axial_slice("b", 2.5),
+            axial_slice("c", 5.0),
+            axial_slice("d", 10.0),
+        ];
+        let result = Volume::sort_slices_by_position(&input);
+
+        assert_eq!(
+            Err(SliceGeometryError::MissingSlice {
+                after: 5.0,
+                before: 10.0,
+                spacing: 2.5
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn test_sort_slices_missing_middle_slice() {
+        let input = vec![
+            axial_slice("a", 0.0),
+            axial_slice("b", 1.0),
+            axial_slice("c", 4.0),
+        ];
+        let result = Volume::sort_slices_by_position(&input);
+
+        assert_eq!(
+            Err(SliceGeometryError::MissingSlice {
+                after: 1.0,
+                before: 4.0,
+                spacing: 1.0
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn test_validate_slice_dimensions() {
+        let mut input = vec![axial_slice("a", 0.0), axial_slice("b", 2.5)];
+        assert_eq!(
+            Ok((512, 512, [0.5, 0.5])),
+            Volume::validate_slice_dimensions(&input)
+        );
+
+        input[1].columns = 256;
+        assert_eq!(
+            Err(SliceGeometryError::InconsistentDimensions(PathBuf::from(
+                "b"
+            ))),
+            Volume::validate_slice_dimensions(&input)
+        );
+    }
+
     #[test]
     fn test_load_dicom_directory() {
-        Volume::read_dicom("assets/data/DCM_0000");
+        let texture = Volume::read_dicom("assets/data/DCM_0000").unwrap();
+
+        assert_eq!(
+            Dim {
+                width: 512,
+                height: 512,
+                depth: 295,
+            },
+            texture.dimensions
+        );
+        assert!(matches!(texture.texture_data, VoxelData::I16(_)));
+        assert!(texture.value_range.min < -1000.0);
+        assert!(texture.value_range.max > 1024.0);
+        assert_eq!(
+            texture.texture_data.len(),
+            (texture.dimensions.width * texture.dimensions.height * texture.dimensions.depth)
+                as usize
+        );
+    }
+
+    #[test]
+    fn test_skip_non_dicom_files() {
+        let directory = ct_directory("dicom_skip");
+        std::fs::write(directory.join("notes.txt"), "not an image").unwrap();
+        let texture = Volume::read_dicom(directory.to_str().unwrap()).unwrap();
+
+        assert_eq!(3, texture.dimensions.depth);
+    }
+
+    #[test]
+    fn test_read_dicom_without_images() {
+        let directory = test_directory("dicom_empty");
+        std::fs::write(directory.join("notes.txt"), "not an image").unwrap();
+
+        assert!(matches!(
+            Volume::read_dicom(directory.to_str().unwrap()),
+            Err(VolumeError::NoImages(_))
+        ));
+        assert!(matches!(
+            Volume::read_dicom("assets/data/missing"),
+            Err(VolumeError::Io(..))
+        ));
+    }
+
+    fn functional_group(sequence: Tag, elements: Vec<InMemElement>) -> InMemDicomObject {
+        let item = InMemDicomObject::from_element_iter(elements);
+        InMemDicomObject::from_element_iter([DataElement::new(
+            sequence,
+            VR::SQ,
+            DataSetSequence::from(vec![item]),
+        )])
+    }
+
+    /// Writes an enhanced CT object with three 2x2 frames, whose positions are in reverse order and
+    /// whose rescale intercept differs per frame.
+    fn write_enhanced_ct(path: &Path) {
+        let per_frame: Vec<InMemDicomObject> = (0..3)
+            .map(|frame| {
+                let mut groups = functional_group(
+                    tags::PLANE_POSITION_SEQUENCE,
+                    vec![DataElement::new(
+                        tags::IMAGE_POSITION_PATIENT,
+                        VR::DS,
+                        PrimitiveValue::from(format!("0\\0\\{}", 4 - frame * 2)),
+                    )],
+                );
+                groups.put(DataElement::new(
+                    tags::PIXEL_VALUE_TRANSFORMATION_SEQUENCE,
+                    VR::SQ,
+                    DataSetSequence::from(vec![InMemDicomObject::from_element_iter([
+                        DataElement::new(tags::RESCALE_SLOPE, VR::DS, PrimitiveValue::from("1")),
+                        DataElement::new(
+                            tags::RESCALE_INTERCEPT,
+                            VR::DS,
+                            PrimitiveValue::from(format!("{}", -1000 * frame)),
+                        ),
+                    ])]),
+                ));
+                groups
+            })
+            .collect();
+        let mut shared = functional_group(
+            tags::PLANE_ORIENTATION_SEQUENCE,
+            vec![DataElement::new(
+                tags::IMAGE_ORIENTATION_PATIENT,
+                VR::DS,
+                PrimitiveValue::from("1\\0\\0\\0\\1\\0"),
+            )],
+        );
+        shared.put(DataElement::new(
+            tags::PIXEL_MEASURES_SEQUENCE,
+            VR::SQ,
+            DataSetSequence::from(vec![InMemDicomObject::from_element_iter([
+                DataElement::new(
+                    tags::PIXEL_SPACING,
+                    VR::DS,
+                    PrimitiveValue::from("0.5\\0.5"),
+                ),
+                DataElement::new(tags::SLICE_THICKNESS, VR::DS, PrimitiveValue::from("2")),
+            ])]),
+        ));
+        let pixel_data: Vec<u16> = (0..12).collect();
+
+        InMemDicomObject::from_element_iter([
+            DataElement::new(
+                tags::SOP_CLASS_UID,
+                VR::UI,
+                PrimitiveValue::from(uids::ENHANCED_CT_IMAGE_STORAGE),
+            ),
+            DataElement::new(
+                tags::SOP_INSTANCE_UID,
+                VR::UI,
+                PrimitiveValue::from("1.2.3.4.5"),
+            ),
+            DataElement::new(tags::SAMPLES_PER_PIXEL, VR::US, PrimitiveValue::from(1_u16)),
+            DataElement::new(
+                tags::PHOTOMETRIC_INTERPRETATION,
+                VR::CS,
+                PrimitiveValue::from("MONOCHROME2"),
+            ),
+            DataElement::new(tags::NUMBER_OF_FRAMES, VR::IS, PrimitiveValue::from("3")),
+            DataElement::new(tags::ROWS, VR::US, PrimitiveValue::from(2_u16)),
+            DataElement::new(tags::COLUMNS, VR::US, PrimitiveValue::from(2_u16)),
+            DataElement::new(tags::BITS_ALLOCATED, VR::US, PrimitiveValue::from(16_u16)),
+            DataElement::new(tags::BITS_STORED, VR::US, PrimitiveValue::from(16_u16)),
+            DataElement::new(tags::HIGH_BIT, VR::US, PrimitiveValue::from(15_u16)),
+            DataElement::new(
+                tags::PIXEL_REPRESENTATION,
+                VR::US,
+                PrimitiveValue::from(0_u16),
+            ),
+            DataElement::new(
+                tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(vec![shared]),
+            ),
+            DataElement::new(
+                tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+                VR::SQ,
+                DataSetSequence::from(per_frame),
+            ),
+            DataElement::new(
+                tags::PIXEL_DATA,
+                VR::OW,
+                PrimitiveValue::U16(pixel_data.into()),
+            ),
+        ])
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN))
+        .unwrap()
+        .write_to_file(path)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_read_enhanced_multi_frame() {
+        let directory = test_directory("enhanced_ct");
+        let path = directory.join("enhanced.dcm");
+        write_enhanced_ct(&path);
+        let texture = Volume::read_dicom_files(&[path]).unwrap();
+
+        assert_eq!(
+            Dim {
+                width: 2,
+                height: 2,
+                depth: 3
+            },
+            texture.dimensions
+        );
+        assert_eq!(
+            Spacing {
+                x: 0.5,
+                y: 0.5,
+                z: 2.0
+            },
+            texture.spacing
+        );
+        assert_eq!(Orientation::default(), texture.orientation);
+        // Frames are ordered by position, so the last frame comes first.
+        assert_eq!(
+            VoxelData::I16(vec![
+                -1992, -1991, -1990, -1989, -996, -995, -994, -993, 0, 1, 2, 3
+            ]),
+            texture.texture_data
+        );
+        assert_eq!(
+            3,
+            Volume::scan_dicom_series(&directory).unwrap()[0].slice_count()
+        );
+    }
+
+    /// Writes a single 2x2 16-bit slice whose pixel data is encapsulated in one fragment.
+    fn write_encapsulated_slice(path: &Path, transfer_syntax: &str, fragment: Vec<u8>) {
+        InMemDicomObject::from_element_iter([
+            DataElement::new(
+                tags::SOP_CLASS_UID,
+                VR::UI,
+                PrimitiveValue::from(uids::CT_IMAGE_STORAGE),
+            ),
+            DataElement::new(
+                tags::SOP_INSTANCE_UID,
+                VR::UI,
+                PrimitiveValue::from("1.2.3.4.6"),
+            ),
+            DataElement::new(
+                tags::IMAGE_POSITION_PATIENT,
+                VR::DS,
+                PrimitiveValue::from("0\\0\\0"),
+            ),
+            DataElement::new(
+                tags::IMAGE_ORIENTATION_PATIENT,
+                VR::DS,
+                PrimitiveValue::from("1\\0\\0\\0\\1\\0"),
+            ),
+            DataElement::new(tags::SAMPLES_PER_PIXEL, VR::US, PrimitiveValue::from(1_u16)),
+            DataElement::new(
+                tags::PHOTOMETRIC_INTERPRETATION,
+                VR::CS,
+                PrimitiveValue::from("MONOCHROME2"),
+            ),
+            DataElement::new(tags::ROWS, VR::US, PrimitiveValue::from(2_u16)),
+            DataElement::new(tags::COLUMNS, VR::US, PrimitiveValue::from(2_u16)),
+            DataElement::new(tags::BITS_ALLOCATED, VR::US, PrimitiveValue::from(16_u16)),
+            DataElement::new(tags::BITS_STORED, VR::US, PrimitiveValue::from(16_u16)),
+            DataElement::new(tags::HIGH_BIT, VR::US, PrimitiveValue::from(15_u16)),
+            DataElement::new(
+                tags::PIXEL_REPRESENTATION,
+                VR::US,
+                PrimitiveValue::from(0_u16),
+            ),
+            DataElement::new(
+                tags::PIXEL_DATA,
+                VR::OB,
+                PixelFragmentSequence::new_fragments(vec![fragment]),
+            ),
+        ])
+        .with_meta(FileMetaTableBuilder::new().transfer_syntax(transfer_syntax))
+        .unwrap()
+        .write_to_file(path)
+        .unwrap();
+    }
+
+    /// Encodes four 16-bit pixels as two RLE segments, holding the high and the low bytes, with a
+    /// single literal run each.
+    fn rle_fragment(pixels: [u16; 4]) -> Vec<u8> {
+        let mut fragment = vec![0u8; 64];
+        LittleEndian::write_u32(&mut fragment[0..], 2);
+        LittleEndian::write_u32(&mut fragment[4..], 64);
+        LittleEndian::write_u32(&mut fragment[8..], 69);
+        fragment.push(3);
+        fragment.extend(pixels.map(|pixel| (pixel >> 8) as u8));
+        fragment.push(3);
+        fragment.extend(pixels.map(|pixel| pixel as u8));
+        fragment
+    }
+
+    #[test]
+    fn test_transfer_syntaxes() {
+        let directory = test_directory("transfer_syntax");
+        let rle_path = directory.join("rle.dcm");
+        let jpeg2000_path = directory.join("jpeg2000.dcm");
+        write_encapsulated_slice(
+            &rle_path,
+            uids::RLE_LOSSLESS,
+            rle_fragment([1, 2, 300, 4000]),
+        );
+        write_encapsulated_slice(&jpeg2000_path, uids::JPEG2000_LOSSLESS, vec![0; 16]);
+
+        let texture = Volume::read_dicom_files(std::slice::from_ref(&rle_path)).unwrap();
+        assert_eq!(VoxelData::I16(vec![1, 2, 300, 4000]), texture.texture_data);
+
+        let files = vec![rle_path, jpeg2000_path.clone()];
+        match Volume::read_dicom_files(&files) {
+            Err(VolumeError::UnsupportedTransferSyntax {
+                path,
+                transfer_syntax,
+            }) => {
+                assert_eq!(jpeg2000_path, path);
+                assert!(transfer_syntax.contains(uids::JPEG2000_LOSSLESS));
+            }
+            result => panic!(
+                "Expected an unsupported transfer syntax, got {:?}",
+                result.map(|_| ())
+            ),
+        }
+
+        let (texture, skipped) =
+            Volume::read_dicom_files_with(&files, DecodeFailurePolicy::Skip).unwrap();
+        assert_eq!(1, texture.dimensions.depth);
+        assert!(matches!(
+            skipped.as_slice(),
+            [VolumeError::UnsupportedTransferSyntax { .. }]
+        ));
     }
 }
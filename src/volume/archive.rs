@@ -0,0 +1,232 @@
+use super::memory::MemoryFiles;
+use super::VolumeError;
+use flate2::read::MultiGzDecoder;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Returns true if `header`, the first bytes of a file inflated if it is gzip compressed, starts a
+/// zip or tar archive.
+pub fn is_archive(header: &[u8]) -> bool {
+    header.starts_with(ZIP_MAGIC) || header.get(257..262) == Some(b"ustar")
+}
+
+/// Extracts the files of a .zip, .tar or .tar.gz archive into memory, keeping their paths within
+/// the archive. Directories and the resource forks that macOS adds to zip files are left out.
+pub fn read_archive(path: &Path, data: &[u8]) -> Result<MemoryFiles, VolumeError> {
+    let error = |reason: String| VolumeError::Archive(path.to_path_buf(), reason);
+    if data.starts_with(ZIP_MAGIC) {
+        return read_zip(data).map_err(|reason| error(reason.to_string()));
+    }
+    let result = if data.starts_with(GZIP_MAGIC) {
+        read_tar(MultiGzDecoder::new(data))
+    } else {
+        read_tar(data)
+    };
+    result.map_err(|reason| error(reason.to_string()))
+}
+
+/// Extracts an archive into a directory of its own under the temporary directory, so that its
+/// DICOM series can be scanned and read like a directory. An earlier extraction of the same
+/// archive is replaced. Returns the directory.
+pub fn extract_archive(path: &Path) -> Result<PathBuf, VolumeError> {
+    let data = std::fs::read(path).map_err(|error| VolumeError::Io(path.to_path_buf(), error))?;
+    let files = read_archive(path, &data)?;
+    let directory = extraction_directory(path);
+    let io_error = |path: &Path, error| VolumeError::Io(path.to_path_buf(), error);
+    if directory.exists() {
+        std::fs::remove_dir_all(&directory).map_err(|error| io_error(&directory, error))?;
+    }
+    for file in files.files() {
+        let name = Path::new(&file.name);
+        // Entries that would end up outside of the directory, such as "../name", are left out.
+        if !name
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            log::warn!("Skipping archive entry {}", file.name);
+            continue;
+        }
+        let file_path = directory.join(name);
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|error| io_error(parent, error))?;
+        }
+        std::fs::write(&file_path, &file.data).map_err(|error| io_error(&file_path, error))?;
+    }
+    Ok(directory)
+}
+
+/// Names the directory after the archive and a hash of its path, as archives with the same name
+/// in different directories are extracted side by side.
+fn extraction_directory(path: &Path) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    std::fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .hash(&mut hasher);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    std::env::temp_dir()
+        .join("med_ray_caster_archives")
+        .join(format!("{}-{:016x}", name, hasher.finish()))
+}
+
+fn read_zip(data: &[u8]) -> zip::result::ZipResult<MemoryFiles> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+    let mut files = MemoryFiles::default();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let name = file.name().to_string();
+        if !file.is_file() || is_ignored(&name) {
+            continue;
+        }
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data)?;
+        files.push(&name, data);
+    }
+    Ok(files)
+}
+
+fn read_tar<R: Read>(reader: R) -> std::io::Result<MemoryFiles> {
+    let mut archive = tar::Archive::new(reader);
+    let mut files = MemoryFiles::default();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        if !entry.header().entry_type().is_file() || is_ignored(&name) {
+            continue;
+        }
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data)?;
+        files.push(&name, data);
+    }
+    Ok(files)
+}
+
+fn is_ignored(name: &str) -> bool {
+    name.starts_with("__MACOSX/")
+        || Path::new(name)
+            .file_name()
+            .is_some_and(|file_name| file_name.to_string_lossy().starts_with("._"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::{slice_path, test_path, CT_SLICES};
+    use crate::volume::Volume;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn write_zip(path: &Path) {
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for name in CT_SLICES.iter().rev() {
+            writer
+                .start_file(format!("study/series/{}", name), options)
+                .unwrap();
+            writer
+                .write_all(&std::fs::read(slice_path(name)).unwrap())
+                .unwrap();
+        }
+        writer.start_file("study/README.txt", options).unwrap();
+        writer.write_all(b"not an image").unwrap();
+        writer
+            .start_file("__MACOSX/study/._README.txt", options)
+            .unwrap();
+        writer.write_all(b"resource fork").unwrap();
+        writer.finish().unwrap();
+    }
+
+    fn write_tar_gz(path: &Path) {
+        let encoder = GzEncoder::new(std::fs::File::create(path).unwrap(), Compression::fast());
+        let mut builder = tar::Builder::new(encoder);
+        for name in CT_SLICES {
+            builder
+                .append_path_with_name(slice_path(name), format!("series/{}", name))
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_read_zip() {
+        let path = test_path("archive", "series.zip");
+        write_zip(&path);
+        let files = read_archive(&path, &std::fs::read(&path).unwrap()).unwrap();
+        let names: Vec<&str> = files
+            .files()
+            .iter()
+            .map(|file| file.name.as_str())
+            .collect();
+
+        assert_eq!(
+            vec![
+                "study/series/CT000003",
+                "study/series/CT000002",
+                "study/series/CT000001",
+                "study/README.txt"
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn test_open_archives() {
+        let slices: Vec<PathBuf> = CT_SLICES.iter().map(|name| slice_path(name)).collect();
+        let expected = Volume::read_dicom_files(&slices).unwrap();
+        let zip_path = test_path("archive", "open.zip");
+        write_zip(&zip_path);
+        let tar_path = test_path("archive", "open.tar.gz");
+        write_tar_gz(&tar_path);
+
+        for path in [zip_path, tar_path] {
+            let volume = Volume::open(&path).unwrap();
+            assert_eq!(expected.dimensions, volume.texture.dimensions);
+            assert_eq!(expected.texture_data, volume.texture.texture_data);
+        }
+    }
+
+    #[test]
+    fn test_extract_archive() {
+        let path = test_path("archive", "extract.zip");
+        write_zip(&path);
+        let directory = extract_archive(&path).unwrap();
+        let series = Volume::scan_dicom_series(&directory).unwrap();
+
+        assert!(directory.join("study/README.txt").is_file());
+        assert!(!directory.join("__MACOSX").exists());
+        assert_eq!(1, series.len());
+        assert_eq!(3, series[0].slice_count());
+    }
+
+    #[test]
+    fn test_extract_archive_outside_directory() {
+        let path = test_path("archive", "escape.tar");
+        let mut header = tar::Header::new_gnu();
+        // The tar builder refuses to set such a name, so it is written into the header.
+        header.as_old_mut().name[..9].copy_from_slice(b"../escape");
+        header.set_size(4);
+        header.set_cksum();
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append(&header, &b"data"[..]).unwrap();
+        std::fs::write(&path, builder.into_inner().unwrap()).unwrap();
+        let directory = extract_archive(&path).unwrap();
+
+        assert!(!directory.join("../escape").exists());
+    }
+
+    #[test]
+    fn test_read_corrupt_archive() {
+        let path = Path::new("broken.zip");
+        let result = read_archive(path, b"PK\x03\x04 truncated");
+        assert!(matches!(result, Err(VolumeError::Archive(..))));
+    }
+}
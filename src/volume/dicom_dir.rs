@@ -0,0 +1,721 @@
+use super::dicom_series::{largest_series, read_string, DicomSeries};
+use super::loading::LoadProgress;
+use super::{Texture, Volume, VolumeError};
+use dicom::core::Tag;
+use dicom::dictionary_std::{tags, uids};
+use dicom_object::{open_file, DefaultDicomObject, InMemDicomObject, OpenFileOptions};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// File name of the media index on DICOM CDs and USB exports.
+pub const DICOMDIR_FILE_NAME: &str = "DICOMDIR";
+
+/// Media index that lists the patients, studies, series and images of a DICOM export.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DicomDir {
+    pub patients: Vec<DicomDirPatient>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DicomDirPatient {
+    pub patient_id: String,
+    pub patient_name: String,
+    pub studies: Vec<DicomDirStudy>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DicomDirStudy {
+    pub study_instance_uid: String,
+    pub study_date: String,
+    pub description: String,
+    pub series: Vec<DicomDirSeries>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DicomDirSeries {
+    pub series_instance_uid: String,
+    pub series_number: Option<i32>,
+    pub modality: String,
+    pub description: String,
+    /// Image files resolved against the directory of the DICOMDIR.
+    pub files: Vec<PathBuf>,
+}
+
+impl DicomDir {
+    /// Reads the directory records of a DICOMDIR. The patients, studies, series and images are
+    /// found by following the offset links of the records from the first record of the root
+    /// directory entity, so records that were appended when the media was updated end up in the
+    /// right series, and records that are no longer in use are left out. DICOMDIRs without valid
+    /// offsets are read in the depth-first order in which the records are usually written.
+    pub fn read(path: &Path) -> Result<DicomDir, VolumeError> {
+        let malformed =
+            |reason: &str| VolumeError::MalformedHeader(path.to_path_buf(), reason.to_string());
+        let file = open_file(path).map_err(|error| malformed(&error.to_string()))?;
+        if file.meta().media_storage_sop_class_uid() != uids::MEDIA_STORAGE_DIRECTORY_STORAGE {
+            return Err(malformed("not a DICOMDIR"));
+        }
+        let records = file
+            .element(tags::DIRECTORY_RECORD_SEQUENCE)
+            .ok()
+            .and_then(|element| element.items())
+            .ok_or_else(|| malformed("missing DirectoryRecordSequence"))?;
+        let base_directory = path.parent().unwrap_or(Path::new("."));
+
+        let root = read_offset(
+            &file,
+            tags::OFFSET_OF_THE_FIRST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY,
+        );
+        let offsets = std::fs::read(path)
+            .ok()
+            .and_then(|bytes| record_offsets(&bytes))
+            .filter(|offsets| offsets.len() == records.len());
+        match (root, offsets) {
+            (1.., Some(offsets)) => {
+                let links = RecordLinks {
+                    records,
+                    by_offset: offsets.into_iter().zip(0..).collect(),
+                };
+                links
+                    .read(root, base_directory)
+                    .map_err(|reason| malformed(&reason))
+            }
+            _ => DicomDir::read_in_order(records, base_directory).map_err(malformed),
+        }
+    }
+
+    /// Reads the records in their physical order, where every study belongs to the patient before
+    /// it, every series to the study before it and every image to the series before it.
+    fn read_in_order(
+        records: &[InMemDicomObject],
+        base_directory: &Path,
+    ) -> Result<DicomDir, &'static str> {
+        let mut dicom_dir = DicomDir::default();
+        for record in records {
+            match read_string(record, tags::DIRECTORY_RECORD_TYPE).as_str() {
+                "PATIENT" => dicom_dir
+                    .patients
+                    .push(DicomDirPatient::from_record(record)),
+                "STUDY" => dicom_dir
+                    .last_patient()
+                    .ok_or("STUDY record before any PATIENT record")?
+                    .studies
+                    .push(DicomDirStudy::from_record(record)),
+                "SERIES" => dicom_dir
+                    .last_study()
+                    .ok_or("SERIES record before any STUDY record")?
+                    .series
+                    .push(DicomDirSeries::from_record(record)),
+                "IMAGE" => {
+                    let series = dicom_dir
+                        .last_series()
+                        .ok_or("IMAGE record before any SERIES record")?;
+                    if let Some(file) = referenced_file(record, base_directory) {
+                        series.files.push(file);
+                    }
+                }
+                // Other record types, such as presentation states or reports, are not volumes.
+                _ => {}
+            }
+        }
+        Ok(dicom_dir)
+    }
+
+    /// Lists all series with images, in the same form as a scanned directory.
+    pub fn series(&self) -> Vec<DicomSeries> {
+        self.series_with_progress(&LoadProgress::default())
+            .unwrap_or_default()
+    }
+
+    /// Like [DicomDir::series], but counts the image headers read in `progress` and stops with
+    /// [VolumeError::Cancelled] once the load is cancelled.
+    pub fn series_with_progress(
+        &self,
+        progress: &LoadProgress,
+    ) -> Result<Vec<DicomSeries>, VolumeError> {
+        let all_series: Vec<(&DicomDirStudy, &DicomDirSeries)> = self
+            .patients
+            .iter()
+            .flat_map(|patient| &patient.studies)
+            .flat_map(|study| study.series.iter().map(move |series| (study, series)))
+            .filter(|(_, series)| !series.files.is_empty())
+            .collect();
+        progress.start_scan(
+            all_series
+                .iter()
+                .map(|(_, series)| series.files.len())
+                .sum(),
+        );
+        let scan = |path: &Path| {
+            progress.check_cancelled()?;
+            let header = read_header(path);
+            progress.file_done(0);
+            Ok::<_, VolumeError>(header)
+        };
+
+        let mut result = Vec::new();
+        for (study, series) in all_series {
+            let first = scan(&series.files[0])?;
+            let (rows, columns) = first.as_ref().map_or((0, 0), image_size);
+            let mut frames = first.as_ref().map_or(1, frame_count);
+            for file in &series.files[1..] {
+                frames += scan(file)?.as_ref().map_or(1, frame_count);
+            }
+            result.push(DicomSeries {
+                study_instance_uid: study.study_instance_uid.clone(),
+                series_instance_uid: series.series_instance_uid.clone(),
+                series_number: series.series_number,
+                modality: series.modality.clone(),
+                description: series.description.clone(),
+                rows,
+                columns,
+                frame_count: frames,
+                files: series.files.clone(),
+            });
+        }
+        Ok(result)
+    }
+
+    fn last_patient(&mut self) -> Option<&mut DicomDirPatient> {
+        self.patients.last_mut()
+    }
+
+    fn last_study(&mut self) -> Option<&mut DicomDirStudy> {
+        self.last_patient()?.studies.last_mut()
+    }
+
+    fn last_series(&mut self) -> Option<&mut DicomDirSeries> {
+        self.last_study()?.series.last_mut()
+    }
+}
+
+impl DicomDirPatient {
+    fn from_record(record: &InMemDicomObject) -> Self {
+        DicomDirPatient {
+            patient_id: read_string(record, tags::PATIENT_ID),
+            patient_name: read_string(record, tags::PATIENT_NAME),
+            studies: Vec::new(),
+        }
+    }
+}
+
+impl DicomDirStudy {
+    fn from_record(record: &InMemDicomObject) -> Self {
+        DicomDirStudy {
+            study_instance_uid: read_string(record, tags::STUDY_INSTANCE_UID),
+            study_date: read_string(record, tags::STUDY_DATE),
+            description: read_string(record, tags::STUDY_DESCRIPTION),
+            series: Vec::new(),
+        }
+    }
+}
+
+impl DicomDirSeries {
+    fn from_record(record: &InMemDicomObject) -> Self {
+        DicomDirSeries {
+            series_instance_uid: read_string(record, tags::SERIES_INSTANCE_UID),
+            series_number: record
+                .element(tags::SERIES_NUMBER)
+                .ok()
+                .and_then(|element| element.to_int::<i32>().ok()),
+            modality: read_string(record, tags::MODALITY),
+            description: read_string(record, tags::SERIES_DESCRIPTION),
+            files: Vec::new(),
+        }
+    }
+}
+
+/// Directory records by the byte offset of their item in the file, which is how the records link
+/// to the next record of their entity and to the first record of their lower level entity.
+struct RecordLinks<'a> {
+    records: &'a [InMemDicomObject],
+    by_offset: HashMap<u32, usize>,
+}
+
+impl RecordLinks<'_> {
+    fn read(&self, root: u32, base_directory: &Path) -> Result<DicomDir, String> {
+        let mut dicom_dir = DicomDir::default();
+        for patient_record in self.entity(root, "PATIENT")? {
+            let mut patient = DicomDirPatient::from_record(patient_record);
+            for study_record in self.children(patient_record, "STUDY")? {
+                let mut study = DicomDirStudy::from_record(study_record);
+                for series_record in self.children(study_record, "SERIES")? {
+                    let mut series = DicomDirSeries::from_record(series_record);
+                    series.files = self
+                        .children(series_record, "IMAGE")?
+                        .into_iter()
+                        .filter_map(|record| referenced_file(record, base_directory))
+                        .collect();
+                    study.series.push(series);
+                }
+                patient.studies.push(study);
+            }
+            dicom_dir.patients.push(patient);
+        }
+        Ok(dicom_dir)
+    }
+
+    fn children(
+        &self,
+        record: &InMemDicomObject,
+        record_type: &str,
+    ) -> Result<Vec<&InMemDicomObject>, String> {
+        match read_offset(
+            record,
+            tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY,
+        ) {
+            0 => Ok(Vec::new()),
+            offset => self.entity(offset, record_type),
+        }
+    }
+
+    /// Returns the records of the given type in use in the entity that starts at an offset, in
+    /// the order of their OffsetOfTheNextDirectoryRecord links. Other record types, such as
+    /// presentation states or reports, are not volumes.
+    fn entity(&self, first: u32, record_type: &str) -> Result<Vec<&InMemDicomObject>, String> {
+        let mut records = Vec::new();
+        let mut visited = HashSet::new();
+        let mut offset = first;
+        while offset != 0 {
+            if !visited.insert(offset) {
+                return Err(format!("directory records link back to offset {}", offset));
+            }
+            let record = self
+                .by_offset
+                .get(&offset)
+                .map(|&index| &self.records[index])
+                .ok_or_else(|| format!("no directory record at offset {}", offset))?;
+            let in_use = record
+                .element(tags::RECORD_IN_USE_FLAG)
+                .ok()
+                .and_then(|element| element.to_int::<u16>().ok())
+                != Some(0);
+            if in_use && read_string(record, tags::DIRECTORY_RECORD_TYPE) == record_type {
+                records.push(record);
+            }
+            offset = read_offset(record, tags::OFFSET_OF_THE_NEXT_DIRECTORY_RECORD);
+        }
+        Ok(records)
+    }
+}
+
+impl Volume {
+    /// Reads the series with the most slices that is listed in a DICOMDIR.
+    pub fn read_dicom_dir(path: &str) -> Result<Texture, VolumeError> {
+        let path = Path::new(path);
+        let series = DicomDir::read(path)?.series();
+        let index =
+            largest_series(&series).ok_or_else(|| VolumeError::NoImages(path.to_path_buf()))?;
+        Volume::read_dicom_series(&series[index])
+    }
+}
+
+/// Returns true if the file is a DICOMDIR, judged by its name.
+pub fn is_dicom_dir(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .file_name()
+            .is_some_and(|name| name.eq_ignore_ascii_case(DICOMDIR_FILE_NAME))
+}
+
+/// Resolves the ReferencedFileID of a record, whose components are relative to the DICOMDIR.
+/// Media often stores the files with lower case names, so those are tried as well.
+fn referenced_file(record: &InMemDicomObject, base_directory: &Path) -> Option<PathBuf> {
+    let components = record
+        .element(tags::REFERENCED_FILE_ID)
+        .ok()?
+        .to_multi_str()
+        .ok()?
+        .iter()
+        .map(|component| component.trim_end_matches([' ', '\0']).to_string())
+        .collect::<Vec<_>>();
+    let path: PathBuf = components.iter().collect();
+    let lower_case_path: PathBuf = components
+        .iter()
+        .map(|component| component.to_lowercase())
+        .collect();
+    let file = [path, lower_case_path]
+        .into_iter()
+        .map(|path| base_directory.join(path))
+        .find(|path| path.is_file());
+    if file.is_none() {
+        log::warn!(
+            "Skipping missing file {} referenced by DICOMDIR",
+            components.join("\\")
+        );
+    }
+    file
+}
+
+fn image_size(file: &DefaultDicomObject) -> (u32, u32) {
+    let read_int = |tag| {
+        file.element(tag)
+            .ok()
+            .and_then(|element| element.to_int::<u32>().ok())
+            .unwrap_or(0)
+    };
+    (read_int(tags::ROWS), read_int(tags::COLUMNS))
+}
+
+/// Returns the NumberOfFrames of an image file, which is 1 for single-frame images.
+fn frame_count(file: &DefaultDicomObject) -> usize {
+    file.element(tags::NUMBER_OF_FRAMES)
+        .ok()
+        .and_then(|element| element.to_int::<usize>().ok())
+        .unwrap_or(1)
+}
+
+/// Reads an offset link of a record, where 0 means that there is no linked record.
+fn read_offset(object: &InMemDicomObject, tag: Tag) -> u32 {
+    object
+        .element(tag)
+        .ok()
+        .and_then(|element| element.to_int::<u32>().ok())
+        .unwrap_or(0)
+}
+
+fn read_header(path: &Path) -> Option<DefaultDicomObject> {
+    OpenFileOptions::new()
+        .read_until(tags::PIXEL_DATA)
+        .open_file(path)
+        .ok()
+}
+
+const UNDEFINED_LENGTH: u32 = u32::MAX;
+const ITEM: (u16, u16) = (0xFFFE, 0xE000);
+const ITEM_DELIMITATION: (u16, u16) = (0xFFFE, 0xE00D);
+const SEQUENCE_DELIMITATION: (u16, u16) = (0xFFFE, 0xE0DD);
+/// Value representations whose explicit VR header has a 4-byte length.
+const LONG_LENGTH_VRS: [&[u8]; 13] = [
+    b"OB", b"OD", b"OF", b"OL", b"OV", b"OW", b"SQ", b"SV", b"UC", b"UN", b"UR", b"UT", b"UV",
+];
+
+/// Finds the byte offsets of the items of the DirectoryRecordSequence, counted from the start of
+/// the file like the offset links of the records. DICOMDIRs are always written in explicit VR
+/// little endian, so that is the only encoding this reads.
+fn record_offsets(bytes: &[u8]) -> Option<Vec<u32>> {
+    if bytes.get(128..132)? != b"DICM" {
+        return None;
+    }
+    let mut position = 132;
+    while position < bytes.len() {
+        let (tag, header, length) = element_header(bytes, position)?;
+        if tag == (0x0004, 0x1220) {
+            return sequence_items(bytes, position + header, length);
+        }
+        position = skip_value(bytes, position + header, length)?;
+    }
+    None
+}
+
+/// Reads the tag, the header size and the value length of an element. Items and delimiters have
+/// no VR.
+fn element_header(bytes: &[u8], position: usize) -> Option<((u16, u16), usize, u32)> {
+    let read_u16 = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let read_u32 = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let tag = (read_u16(position)?, read_u16(position + 2)?);
+    if tag.0 == 0xFFFE {
+        return Some((tag, 8, read_u32(position + 4)?));
+    }
+    let vr = bytes.get(position + 4..position + 6)?;
+    if LONG_LENGTH_VRS.contains(&vr) {
+        Some((tag, 12, read_u32(position + 8)?))
+    } else {
+        Some((tag, 8, read_u16(position + 6)? as u32))
+    }
+}
+
+fn sequence_items(bytes: &[u8], mut position: usize, length: u32) -> Option<Vec<u32>> {
+    let end = (length != UNDEFINED_LENGTH).then(|| position + length as usize);
+    let mut offsets = Vec::new();
+    loop {
+        if end == Some(position) {
+            return Some(offsets);
+        }
+        let (tag, header, length) = element_header(bytes, position)?;
+        match tag {
+            SEQUENCE_DELIMITATION if end.is_none() => return Some(offsets),
+            ITEM => {
+                offsets.push(u32::try_from(position).ok()?);
+                position = skip_item(bytes, position + header, length)?;
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Returns the position after a value. Values of undefined length are sequences, which are
+/// walked item by item up to their delimiter.
+fn skip_value(bytes: &[u8], mut position: usize, length: u32) -> Option<usize> {
+    if length != UNDEFINED_LENGTH {
+        return Some(position + length as usize).filter(|&end| end <= bytes.len());
+    }
+    loop {
+        let (tag, header, length) = element_header(bytes, position)?;
+        position += header;
+        match tag {
+            SEQUENCE_DELIMITATION => return Some(position),
+            ITEM => position = skip_item(bytes, position, length)?,
+            _ => return None,
+        }
+    }
+}
+
+fn skip_item(bytes: &[u8], mut position: usize, length: u32) -> Option<usize> {
+    if length != UNDEFINED_LENGTH {
+        return Some(position + length as usize).filter(|&end| end <= bytes.len());
+    }
+    loop {
+        let (tag, header, length) = element_header(bytes, position)?;
+        if tag == ITEM_DELIMITATION {
+            return Some(position + header);
+        }
+        position = skip_value(bytes, position + header, length)?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::{slice_path, test_directory};
+    use dicom::core::value::DataSetSequence;
+    use dicom::core::{DataElement, PrimitiveValue, VR};
+    use dicom_object::meta::FileMetaTableBuilder;
+
+    type ElementArgs = (dicom::core::Tag, VR, PrimitiveValue);
+
+    fn record(record_type: &str, elements: Vec<ElementArgs>) -> InMemDicomObject {
+        let mut record = InMemDicomObject::new_empty();
+        record.put(DataElement::new(
+            tags::DIRECTORY_RECORD_TYPE,
+            VR::CS,
+            PrimitiveValue::from(record_type),
+        ));
+        for (tag, vr, value) in elements {
+            record.put(DataElement::new(tag, vr, value));
+        }
+        record
+    }
+
+    fn image(components: &[&str]) -> InMemDicomObject {
+        let components: Vec<String> = components.iter().map(|c| c.to_string()).collect();
+        record(
+            "IMAGE",
+            vec![(
+                tags::REFERENCED_FILE_ID,
+                VR::CS,
+                PrimitiveValue::Strs(components.into()),
+            )],
+        )
+    }
+
+    fn write_dicom_dir(path: &Path, records: Vec<InMemDicomObject>) {
+        write_dicom_dir_with_root(path, records, 0);
+    }
+
+    fn write_dicom_dir_with_root(path: &Path, records: Vec<InMemDicomObject>, root: u32) {
+        let mut dicom_dir = InMemDicomObject::new_empty();
+        dicom_dir.put(DataElement::new(
+            tags::OFFSET_OF_THE_FIRST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY,
+            VR::UL,
+            PrimitiveValue::from(root),
+        ));
+        dicom_dir.put(DataElement::new(
+            tags::DIRECTORY_RECORD_SEQUENCE,
+            VR::SQ,
+            DataSetSequence::from(records),
+        ));
+        dicom_dir
+            .with_meta(
+                FileMetaTableBuilder::new()
+                    .transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN)
+                    .media_storage_sop_class_uid(uids::MEDIA_STORAGE_DIRECTORY_STORAGE)
+                    .media_storage_sop_instance_uid("1.2.3.4"),
+            )
+            .unwrap()
+            .write_to_file(path)
+            .unwrap();
+    }
+
+    /// Writes a DICOMDIR whose root entity starts at the first record, with the indices of the
+    /// next and of the first lower level record of every record. The offsets are fixed size, so
+    /// they are found in a first write and filled in by a second one.
+    fn write_linked_dicom_dir(
+        path: &Path,
+        mut records: Vec<InMemDicomObject>,
+        links: &[(Option<usize>, Option<usize>)],
+    ) {
+        let mut offsets = vec![0; records.len()];
+        for _ in 0..2 {
+            for (record, &(next, lower)) in records.iter_mut().zip(links) {
+                for (tag, link) in [
+                    (tags::OFFSET_OF_THE_NEXT_DIRECTORY_RECORD, next),
+                    (
+                        tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY,
+                        lower,
+                    ),
+                ] {
+                    let offset = link.map_or(0, |index| offsets[index]);
+                    record.put(DataElement::new(tag, VR::UL, PrimitiveValue::from(offset)));
+                }
+            }
+            write_dicom_dir_with_root(path, records.clone(), offsets[0]);
+            offsets = record_offsets(&std::fs::read(path).unwrap()).unwrap();
+        }
+    }
+
+    fn series_record(series_instance_uid: &str) -> InMemDicomObject {
+        record(
+            "SERIES",
+            vec![
+                (
+                    tags::SERIES_INSTANCE_UID,
+                    VR::UI,
+                    PrimitiveValue::from(series_instance_uid),
+                ),
+                (tags::MODALITY, VR::CS, PrimitiveValue::from("CT")),
+            ],
+        )
+    }
+
+    fn copy_images(directory: &Path, files: &[&str]) {
+        let image_directory = directory.join("DICOM");
+        std::fs::create_dir_all(&image_directory).unwrap();
+        for (index, file) in files.iter().enumerate() {
+            std::fs::copy(
+                slice_path(file),
+                image_directory.join(format!("IM{:05}", index + 1)),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_read_dicom_dir() {
+        let directory = test_directory("dicom_dir");
+        copy_images(&directory, &["CT000001", "CT000002"]);
+        let path = directory.join(DICOMDIR_FILE_NAME);
+        write_linked_dicom_dir(
+            &path,
+            vec![
+                record(
+                    "PATIENT",
+                    vec![(tags::PATIENT_ID, VR::LO, PrimitiveValue::from("P1"))],
+                ),
+                record(
+                    "STUDY",
+                    vec![(
+                        tags::STUDY_INSTANCE_UID,
+                        VR::UI,
+                        PrimitiveValue::from("1.2"),
+                    )],
+                ),
+                series_record("1.2.1"),
+                image(&["DICOM", "IM00001"]),
+                image(&["DICOM", "IM00002"]),
+                image(&["DICOM", "MISSING"]),
+            ],
+            &[
+                (None, Some(1)),
+                (None, Some(2)),
+                (None, Some(3)),
+                (Some(4), None),
+                (Some(5), None),
+                (None, None),
+            ],
+        );
+        let dicom_dir = DicomDir::read(&path).unwrap();
+
+        assert_eq!(1, dicom_dir.patients.len());
+        assert_eq!("P1", dicom_dir.patients[0].patient_id);
+        let series = dicom_dir.series();
+        assert_eq!(1, series.len());
+        assert_eq!("CT", series[0].modality);
+        assert_eq!(2, series[0].slice_count());
+        assert_eq!(512, series[0].rows);
+        assert!(is_dicom_dir(&path));
+
+        let texture = Volume::read_dicom_dir(path.to_str().unwrap()).unwrap();
+        assert_eq!(2, texture.dimensions.depth);
+        assert_eq!(series, Volume::scan_dicom_series(&directory).unwrap());
+    }
+
+    #[test]
+    fn test_read_updated_dicom_dir() {
+        let directory = test_directory("dicom_dir_updated");
+        copy_images(&directory, &["CT000001", "CT000002", "CT000003"]);
+        let path = directory.join(DICOMDIR_FILE_NAME);
+        let mut removed = image(&["DICOM", "IM00002"]);
+        removed.put(DataElement::new(
+            tags::RECORD_IN_USE_FLAG,
+            VR::US,
+            PrimitiveValue::from(0_u16),
+        ));
+        // The second image of the first series was appended after the second series.
+        write_linked_dicom_dir(
+            &path,
+            vec![
+                record("PATIENT", vec![]),
+                record("STUDY", vec![]),
+                series_record("1.2.1"),
+                image(&["DICOM", "IM00001"]),
+                series_record("1.2.2"),
+                image(&["DICOM", "IM00002"]),
+                removed,
+                image(&["DICOM", "IM00003"]),
+            ],
+            &[
+                (None, Some(1)),
+                (None, Some(2)),
+                (Some(4), Some(3)),
+                (Some(7), None),
+                (None, Some(6)),
+                (None, None),
+                (Some(5), None),
+                (None, None),
+            ],
+        );
+        let dicom_dir = DicomDir::read(&path).unwrap();
+        let series = &dicom_dir.patients[0].studies[0].series;
+
+        assert_eq!(2, series.len());
+        assert_eq!(
+            vec![
+                directory.join("DICOM").join("IM00001"),
+                directory.join("DICOM").join("IM00003")
+            ],
+            series[0].files
+        );
+        assert_eq!(
+            vec![directory.join("DICOM").join("IM00002")],
+            series[1].files
+        );
+    }
+
+    #[test]
+    fn test_dicom_dir_link_loop() {
+        let directory = test_directory("dicom_dir_loop");
+        let path = directory.join(DICOMDIR_FILE_NAME);
+        write_linked_dicom_dir(
+            &path,
+            vec![record("PATIENT", vec![]), record("PATIENT", vec![])],
+            &[(Some(1), None), (Some(0), None)],
+        );
+
+        assert!(matches!(
+            DicomDir::read(&path),
+            Err(VolumeError::MalformedHeader(..))
+        ));
+    }
+
+    #[test]
+    fn test_image_before_series() {
+        let directory = test_directory("dicom_dir_invalid");
+        let path = directory.join(DICOMDIR_FILE_NAME);
+        write_dicom_dir(&path, vec![image(&["IM00001"])]);
+
+        assert!(matches!(
+            DicomDir::read(&path),
+            Err(VolumeError::MalformedHeader(..))
+        ));
+    }
+}
@@ -0,0 +1,310 @@
+use super::dicom_dir::{is_dicom_dir, DicomDir, DICOMDIR_FILE_NAME};
+use super::loading::LoadProgress;
+use super::memory::{FileSource, FileSystem};
+use super::{Texture, Volume, VolumeError};
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom_object::InMemDicomObject;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Summary of one DICOM series found by [Volume::scan_dicom_series].
+#[derive(Debug, PartialEq, Clone)]
+pub struct DicomSeries {
+    pub study_instance_uid: String,
+    pub series_instance_uid: String,
+    pub series_number: Option<i32>,
+    pub modality: String,
+    pub description: String,
+    pub rows: u32,
+    pub columns: u32,
+    /// Number of frames in all files, as enhanced multi-frame objects hold many slices per file.
+    pub frame_count: usize,
+    pub files: Vec<PathBuf>,
+}
+
+impl DicomSeries {
+    pub fn slice_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Short description of the series for the series picker.
+    pub fn label(&self) -> String {
+        let number = self
+            .series_number
+            .map(|number| format!("#{} ", number))
+            .unwrap_or_default();
+        format!(
+            "{}{} {} ({} slices, {}x{})",
+            number,
+            self.modality,
+            self.description,
+            self.slice_count(),
+            self.columns,
+            self.rows
+        )
+    }
+}
+
+/// Header fields of one image file that are needed to group it into a series.
+struct SeriesFile {
+    path: PathBuf,
+    study_instance_uid: String,
+    series_instance_uid: String,
+    series_number: Option<i32>,
+    modality: String,
+    description: String,
+    rows: u32,
+    columns: u32,
+    frame_count: usize,
+}
+
+impl Volume {
+    /// Recursively scans a directory for DICOM images and groups them by StudyInstanceUID and
+    /// SeriesInstanceUID. Files that are not DICOM or contain no image are skipped. Only the
+    /// headers are read, so scanning is much faster than loading.
+    ///
+    /// If the path is a DICOMDIR or a directory containing one, the series are taken from its
+    /// records instead.
+    pub fn scan_dicom_series(directory_path: &Path) -> Result<Vec<DicomSeries>, VolumeError> {
+        Volume::scan_dicom_series_with_progress(directory_path, &LoadProgress::default())
+    }
+
+    /// Like [Volume::scan_dicom_series], but counts the scanned files in `progress` and stops
+    /// with [VolumeError::Cancelled] once the load is cancelled.
+    pub fn scan_dicom_series_with_progress(
+        directory_path: &Path,
+        progress: &LoadProgress,
+    ) -> Result<Vec<DicomSeries>, VolumeError> {
+        if is_dicom_dir(directory_path) {
+            return DicomDir::read(directory_path)?.series_with_progress(progress);
+        }
+        let dicom_dir_path = directory_path.join(DICOMDIR_FILE_NAME);
+        if is_dicom_dir(&dicom_dir_path) {
+            return DicomDir::read(&dicom_dir_path)?.series_with_progress(progress);
+        }
+        let mut files = Vec::new();
+        collect_files(directory_path, &mut files)?;
+        group_dicom_files(&FileSystem, &files, progress)
+    }
+
+    /// Finds the series a single DICOM file belongs to among the files next to it, without
+    /// descending into subdirectories. Files of other series in the same directory are left out.
+    pub fn scan_series_of_file(path: &Path) -> Result<DicomSeries, VolumeError> {
+        let series_instance_uid = read_series_file(&FileSystem, path)
+            .ok_or_else(|| VolumeError::NoImages(path.to_path_buf()))?
+            .series_instance_uid;
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let entries = std::fs::read_dir(directory)
+            .map_err(|error| VolumeError::Io(directory.to_path_buf(), error))?;
+        let files: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        group_dicom_files(&FileSystem, &files, &LoadProgress::default())?
+            .into_iter()
+            .find(|series| series.series_instance_uid == series_instance_uid)
+            .ok_or_else(|| VolumeError::NoImages(path.to_path_buf()))
+    }
+
+    /// Reads the slices of one series found by [Volume::scan_dicom_series].
+    pub fn read_dicom_series(series: &DicomSeries) -> Result<Texture, VolumeError> {
+        Volume::read_dicom_files(&series.files)
+    }
+}
+
+/// Returns the index of the series with the most slices, which is loaded if no series is chosen.
+pub fn largest_series(series: &[DicomSeries]) -> Option<usize> {
+    series
+        .iter()
+        .enumerate()
+        .max_by_key(|(index, series)| (series.slice_count(), std::cmp::Reverse(*index)))
+        .map(|(index, _)| index)
+}
+
+/// Reads the headers of the files and groups the DICOM images into series.
+pub(super) fn group_dicom_files(
+    source: &dyn FileSource,
+    files: &[PathBuf],
+    progress: &LoadProgress,
+) -> Result<Vec<DicomSeries>, VolumeError> {
+    progress.start_scan(files.len());
+    let series_files: Vec<Option<SeriesFile>> = files
+        .par_iter()
+        .map(|path| {
+            progress.check_cancelled()?;
+            let series_file = read_series_file(source, path);
+            progress.file_done(0);
+            Ok(series_file)
+        })
+        .collect::<Result<_, VolumeError>>()?;
+    Ok(group_series(series_files.into_iter().flatten().collect()))
+}
+
+fn collect_files(directory_path: &Path, files: &mut Vec<PathBuf>) -> Result<(), VolumeError> {
+    let entries = std::fs::read_dir(directory_path)
+        .map_err(|error| VolumeError::Io(directory_path.to_path_buf(), error))?;
+    for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_series_file(source: &dyn FileSource, path: &Path) -> Option<SeriesFile> {
+    let file = source.open_dicom(path, Some(tags::PIXEL_DATA)).ok()?;
+    let read_int = |tag: Tag| {
+        file.element(tag)
+            .ok()
+            .and_then(|element| element.to_int::<u32>().ok())
+    };
+    // Objects without Rows/Columns, such as a DICOMDIR or structured reports, are not images.
+    let rows = read_int(tags::ROWS)?;
+    let columns = read_int(tags::COLUMNS)?;
+    let frame_count = read_int(tags::NUMBER_OF_FRAMES).unwrap_or(1) as usize;
+
+    Some(SeriesFile {
+        path: path.to_path_buf(),
+        study_instance_uid: read_string(&file, tags::STUDY_INSTANCE_UID),
+        series_instance_uid: read_string(&file, tags::SERIES_INSTANCE_UID),
+        series_number: file
+            .element(tags::SERIES_NUMBER)
+            .ok()
+            .and_then(|element| element.to_int::<i32>().ok()),
+        modality: read_string(&file, tags::MODALITY),
+        description: read_string(&file, tags::SERIES_DESCRIPTION),
+        rows,
+        columns,
+        frame_count,
+    })
+}
+
+pub(super) fn read_string(file: &InMemDicomObject, tag: Tag) -> String {
+    file.element(tag)
+        .ok()
+        .and_then(|element| element.to_str().ok())
+        .map(|value| value.trim_end_matches([' ', '\0']).to_string())
+        .unwrap_or_default()
+}
+
+/// Groups the files by study and series, ordered by study, series number and file path.
+fn group_series(series_files: Vec<SeriesFile>) -> Vec<DicomSeries> {
+    let mut groups: HashMap<(String, String), DicomSeries> = HashMap::new();
+    for file in series_files {
+        let key = (
+            file.study_instance_uid.clone(),
+            file.series_instance_uid.clone(),
+        );
+        let series = groups.entry(key).or_insert_with(|| DicomSeries {
+            study_instance_uid: file.study_instance_uid,
+            series_instance_uid: file.series_instance_uid,
+            series_number: file.series_number,
+            modality: file.modality,
+            description: file.description,
+            rows: file.rows,
+            columns: file.columns,
+            frame_count: 0,
+            files: Vec::new(),
+        });
+        series.frame_count += file.frame_count;
+        series.files.push(file.path);
+    }
+
+    let mut series: Vec<DicomSeries> = groups.into_values().collect();
+    for series in series.iter_mut() {
+        series.files.sort();
+    }
+    series.sort_by(|a, b| {
+        (
+            &a.study_instance_uid,
+            a.series_number,
+            &a.series_instance_uid,
+        )
+            .cmp(&(
+                &b.study_instance_uid,
+                b.series_number,
+                &b.series_instance_uid,
+            ))
+    });
+    series
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::{copy_slices, slice_path, test_directory};
+    use dicom::core::{DataElement, PrimitiveValue, VR};
+    use dicom_object::open_file;
+
+    fn series_file(series: &str, number: i32, name: &str) -> SeriesFile {
+        SeriesFile {
+            path: PathBuf::from(name),
+            study_instance_uid: "1.2.3".to_string(),
+            series_instance_uid: series.to_string(),
+            series_number: Some(number),
+            modality: "CT".to_string(),
+            description: format!("Series {}", number),
+            rows: 512,
+            columns: 512,
+            frame_count: 1,
+        }
+    }
+
+    #[test]
+    fn test_group_series() {
+        let series = group_series(vec![
+            series_file("1.2.3.2", 2, "b"),
+            series_file("1.2.3.1", 1, "scout"),
+            series_file("1.2.3.2", 2, "a"),
+        ]);
+
+        assert_eq!(2, series.len());
+        assert_eq!("1.2.3.1", series[0].series_instance_uid);
+        assert_eq!(
+            vec![PathBuf::from("a"), PathBuf::from("b")],
+            series[1].files
+        );
+        assert_eq!(Some(1), largest_series(&series));
+        assert_eq!("#2 CT Series 2 (2 slices, 512x512)", series[1].label());
+    }
+
+    #[test]
+    fn test_scan_dicom_series_recursively() {
+        let directory = test_directory("dicom_series");
+        copy_slices(&directory, &["CT000001"]);
+        copy_slices(&directory.join("series"), &["CT000002"]);
+        std::fs::write(directory.join("README.txt"), "not an image").unwrap();
+        let series = Volume::scan_dicom_series(&directory).unwrap();
+
+        assert_eq!(1, series.len());
+        assert_eq!(2, series[0].slice_count());
+        assert_eq!(512, series[0].rows);
+        assert_eq!("CT", series[0].modality);
+    }
+
+    #[test]
+    fn test_scan_series_of_file() {
+        let directory = test_directory("series_of_file");
+        copy_slices(&directory, &["CT000001", "CT000002"]);
+        copy_slices(&directory.join("series"), &["CT000003"]);
+        let mut other_series = open_file(slice_path("CT000003")).unwrap();
+        other_series.put(DataElement::new(
+            tags::SERIES_INSTANCE_UID,
+            VR::UI,
+            PrimitiveValue::from("1.2.3.4"),
+        ));
+        other_series.write_to_file(directory.join("other")).unwrap();
+        let series = Volume::scan_series_of_file(&directory.join("CT000001")).unwrap();
+
+        assert_eq!(2, series.slice_count());
+        assert!(series.files.contains(&directory.join("CT000002")));
+    }
+}
@@ -0,0 +1,501 @@
+use super::archive::{is_archive, read_archive};
+use super::dicom_dir::is_dicom_dir;
+use super::memory::MemoryFiles;
+use super::raw::{Downsampling, MAX_TEXTURE_VOXELS};
+use super::{Texture, Volume, VolumeError};
+use flate2::read::MultiGzDecoder;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use three_d_asset::io::RawAssets;
+
+/// Number of bytes at the start of a file that are passed to [VolumeLoader::can_load].
+const SNIFF_SIZE: u64 = 1024;
+
+/// File extensions of the default loaders, offered by the file dialog. DICOM files often have no
+/// extension, so they are found by content.
+pub const VOLUME_EXTENSIONS: &[&str] = &[
+    "dcm", "mhd", "mha", "nrrd", "nhdr", "nii", "gz", "hdr", "vol", "zip", "tar", "tgz",
+];
+
+/// Reads one volume file format. Loaders are registered with a [LoaderRegistry], which
+/// picks the first loader that accepts a path.
+pub trait VolumeLoader: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns true if the loader can read the path. `header` holds the first bytes of the file,
+    /// inflated if the file is gzip compressed, and is empty for directories.
+    fn can_load(&self, path: &Path, header: &[u8]) -> bool;
+
+    /// Reads the volume at `path`. `registry` is the registry that picked the loader, with which
+    /// containers such as archives read the files they hold.
+    fn load(&self, path: &Path, registry: &LoaderRegistry) -> Result<Texture, VolumeError>;
+
+    /// Reads the file at `path` from a set of in-memory files, which also holds any detached data
+    /// files. Loaders that can only read from disk keep this default, which rejects the file.
+    fn load_memory(
+        &self,
+        path: &Path,
+        _files: &MemoryFiles,
+        _registry: &LoaderRegistry,
+    ) -> Result<Texture, VolumeError> {
+        Err(VolumeError::UnsupportedFormat(path.to_path_buf()))
+    }
+}
+
+pub struct LoaderRegistry {
+    loaders: Vec<Box<dyn VolumeLoader>>,
+    max_texture_size: usize,
+}
+
+impl Default for LoaderRegistry {
+    /// Creates a registry with the DICOM, archive, MetaImage, NRRD, NIfTI and .vol loaders.
+    fn default() -> Self {
+        let mut registry = LoaderRegistry::new();
+        registry.loaders = vec![
+            Box::new(DicomLoader),
+            Box::new(ArchiveLoader),
+            Box::new(NrrdLoader),
+            Box::new(NiftiLoader),
+            Box::new(MetaImageLoader),
+            Box::new(VolLoader),
+        ];
+        registry
+    }
+}
+
+impl LoaderRegistry {
+    /// Creates a registry without any loaders.
+    pub fn new() -> Self {
+        LoaderRegistry {
+            loaders: Vec::new(),
+            max_texture_size: usize::MAX,
+        }
+    }
+
+    /// Largest size along any axis of the volumes that loaders downsample while reading, e.g. the
+    /// GL_MAX_3D_TEXTURE_SIZE of the GPU. There is no limit until it is set.
+    pub fn max_texture_size(&self) -> usize {
+        self.max_texture_size
+    }
+
+    pub fn set_max_texture_size(&mut self, max_texture_size: usize) {
+        self.max_texture_size = max_texture_size;
+    }
+
+    /// Adds a loader, which takes precedence over the loaders registered before it.
+    pub fn register<L: VolumeLoader + 'static>(&mut self, loader: L) {
+        self.loaders.insert(0, Box::new(loader));
+    }
+
+    pub fn find(&self, path: &Path) -> Option<&dyn VolumeLoader> {
+        let header = read_header(path);
+        self.loaders
+            .iter()
+            .find(|loader| loader.can_load(path, &header))
+            .map(|loader| loader.as_ref())
+    }
+
+    pub fn open(&self, path: &Path) -> Result<Volume, VolumeError> {
+        if !path.exists() {
+            let error = std::io::Error::from(std::io::ErrorKind::NotFound);
+            return Err(VolumeError::Io(path.to_path_buf(), error));
+        }
+        let loader = self
+            .find(path)
+            .ok_or_else(|| VolumeError::UnsupportedFormat(path.to_path_buf()))?;
+        Ok(Volume::from_texture(loader.load(path, self)?))
+    }
+
+    /// Reads in-memory files with the loader that accepts the first of them that any loader
+    /// accepts, so data files without a header of their own are passed over.
+    pub fn open_memory(&self, files: &MemoryFiles) -> Result<Volume, VolumeError> {
+        Ok(Volume::from_texture(self.load_memory(files)?))
+    }
+
+    fn load_memory(&self, files: &MemoryFiles) -> Result<Texture, VolumeError> {
+        for file in files.files() {
+            let path = Path::new(&file.name);
+            let header = inflate_header(file.data.as_slice());
+            if let Some(loader) = self
+                .loaders
+                .iter()
+                .find(|loader| loader.can_load(path, &header))
+            {
+                return loader.load_memory(path, files, self);
+            }
+        }
+        match files.files().first() {
+            Some(file) => Err(VolumeError::UnsupportedFormat(PathBuf::from(&file.name))),
+            None => Err(VolumeError::NoImages(PathBuf::new())),
+        }
+    }
+}
+
+/// Reads the first bytes of a file and inflates them if the file is gzip compressed.
+fn read_header(path: &Path) -> Vec<u8> {
+    if path.is_dir() {
+        return Vec::new();
+    }
+    match std::fs::File::open(path) {
+        Ok(file) => inflate_header(file),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Reads the first bytes of a stream and inflates them if the stream is gzip compressed.
+fn inflate_header<R: Read>(reader: R) -> Vec<u8> {
+    let mut reader = BufReader::new(reader);
+    let Ok(start) = reader.fill_buf() else {
+        return Vec::new();
+    };
+    let compressed = start.starts_with(&[0x1f, 0x8b]);
+    let mut header = Vec::new();
+    // A truncated read still yields the bytes that could be read or inflated.
+    if compressed {
+        let _ = MultiGzDecoder::new(reader)
+            .take(SNIFF_SIZE)
+            .read_to_end(&mut header);
+    } else {
+        let _ = reader.take(SNIFF_SIZE).read_to_end(&mut header);
+    }
+    header
+}
+
+/// Returns true if the path is a file that [ArchiveLoader] reads.
+pub fn is_archive_file(path: &Path) -> bool {
+    !path.is_dir() && ArchiveLoader.can_load(path, &read_header(path))
+}
+
+/// Returns the lower case file name, so that double extensions like .nii.gz can be matched.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    let file_name = file_name(path);
+    extensions
+        .iter()
+        .any(|extension| file_name.ends_with(&format!(".{}", extension)))
+}
+
+/// Reads a directory of DICOM slices or a DICOMDIR. A single DICOM file opens the series it belongs
+/// to from the files next to it.
+pub struct DicomLoader;
+
+impl VolumeLoader for DicomLoader {
+    fn name(&self) -> &'static str {
+        "DICOM"
+    }
+
+    fn can_load(&self, path: &Path, header: &[u8]) -> bool {
+        path.is_dir() || header.get(128..132) == Some(b"DICM") || has_extension(path, &["dcm"])
+    }
+
+    fn load(&self, path: &Path, _registry: &LoaderRegistry) -> Result<Texture, VolumeError> {
+        if path.is_dir() {
+            return Volume::read_dicom(&path.to_string_lossy());
+        }
+        if is_dicom_dir(path) {
+            return Volume::read_dicom_dir(&path.to_string_lossy());
+        }
+        Volume::read_dicom_series(&Volume::scan_series_of_file(path)?)
+    }
+
+    /// Reads the largest series of all in-memory DICOM files, as there is no directory to find the
+    /// series of a single file in.
+    fn load_memory(
+        &self,
+        _path: &Path,
+        files: &MemoryFiles,
+        _registry: &LoaderRegistry,
+    ) -> Result<Texture, VolumeError> {
+        Volume::read_dicom_memory(files)
+    }
+}
+
+/// Reads the volume in a .zip, .tar or .tar.gz archive, e.g. a zipped DICOM folder. The files are
+/// extracted into memory and read like [LoaderRegistry::open_memory] reads them, with the loaders
+/// of the registry that opened the archive.
+pub struct ArchiveLoader;
+
+impl VolumeLoader for ArchiveLoader {
+    fn name(&self) -> &'static str {
+        "archive"
+    }
+
+    fn can_load(&self, path: &Path, header: &[u8]) -> bool {
+        is_archive(header) || has_extension(path, &["zip", "tar", "tar.gz", "tgz"])
+    }
+
+    fn load(&self, path: &Path, registry: &LoaderRegistry) -> Result<Texture, VolumeError> {
+        let data =
+            std::fs::read(path).map_err(|error| VolumeError::Io(path.to_path_buf(), error))?;
+        registry.load_memory(&read_archive(path, &data)?)
+    }
+
+    fn load_memory(
+        &self,
+        path: &Path,
+        files: &MemoryFiles,
+        registry: &LoaderRegistry,
+    ) -> Result<Texture, VolumeError> {
+        let data = files
+            .get(path)
+            .ok_or_else(|| VolumeError::NoImages(path.to_path_buf()))?;
+        registry.load_memory(&read_archive(path, data)?)
+    }
+}
+
+pub struct MetaImageLoader;
+
+impl VolumeLoader for MetaImageLoader {
+    fn name(&self) -> &'static str {
+        "MetaImage"
+    }
+
+    fn can_load(&self, path: &Path, header: &[u8]) -> bool {
+        has_extension(path, &["mhd", "mha"])
+            || header.starts_with(b"ObjectType")
+            || header.starts_with(b"NDims")
+    }
+
+    /// Downsamples volumes that do not fit into a texture.
+    fn load(&self, path: &Path, registry: &LoaderRegistry) -> Result<Texture, VolumeError> {
+        Volume::read_meta_image_within(
+            path,
+            MAX_TEXTURE_VOXELS,
+            registry.max_texture_size(),
+            Downsampling::Box,
+        )
+    }
+
+    fn load_memory(
+        &self,
+        path: &Path,
+        files: &MemoryFiles,
+        _registry: &LoaderRegistry,
+    ) -> Result<Texture, VolumeError> {
+        Ok(Volume::try_read_meta_image(files, path)?)
+    }
+}
+
+pub struct NrrdLoader;
+
+impl VolumeLoader for NrrdLoader {
+    fn name(&self) -> &'static str {
+        "NRRD"
+    }
+
+    fn can_load(&self, path: &Path, header: &[u8]) -> bool {
+        header.starts_with(b"NRRD000") || has_extension(path, &["nrrd", "nhdr"])
+    }
+
+    fn load(&self, path: &Path, _registry: &LoaderRegistry) -> Result<Texture, VolumeError> {
+        Volume::read_nrrd(&path.to_string_lossy())
+    }
+
+    fn load_memory(
+        &self,
+        path: &Path,
+        files: &MemoryFiles,
+        _registry: &LoaderRegistry,
+    ) -> Result<Texture, VolumeError> {
+        Ok(Volume::try_read_nrrd(files, path)?)
+    }
+}
+
+pub struct NiftiLoader;
+
+impl VolumeLoader for NiftiLoader {
+    fn name(&self) -> &'static str {
+        "NIfTI"
+    }
+
+    fn can_load(&self, path: &Path, header: &[u8]) -> bool {
+        let nifti1 = matches!(header.get(344..347), Some(b"n+1") | Some(b"ni1"));
+        let nifti2 = matches!(header.get(4..7), Some(b"n+2") | Some(b"ni2"));
+        nifti1 || nifti2 || has_extension(path, &["nii", "nii.gz"])
+    }
+
+    fn load(&self, path: &Path, _registry: &LoaderRegistry) -> Result<Texture, VolumeError> {
+        Volume::read_nifti(&path.to_string_lossy())
+    }
+
+    fn load_memory(
+        &self,
+        path: &Path,
+        files: &MemoryFiles,
+        _registry: &LoaderRegistry,
+    ) -> Result<Texture, VolumeError> {
+        Ok(Volume::try_read_nifti(files, path)?)
+    }
+}
+
+/// Reads the .vol format of three-d-asset, which has no magic number.
+pub struct VolLoader;
+
+impl VolumeLoader for VolLoader {
+    fn name(&self) -> &'static str {
+        "vol"
+    }
+
+    fn can_load(&self, path: &Path, _header: &[u8]) -> bool {
+        has_extension(path, &["vol"])
+    }
+
+    fn load(&self, path: &Path, _registry: &LoaderRegistry) -> Result<Texture, VolumeError> {
+        Volume::read_vol(&path.to_string_lossy())
+    }
+
+    fn load_memory(
+        &self,
+        path: &Path,
+        files: &MemoryFiles,
+        _registry: &LoaderRegistry,
+    ) -> Result<Texture, VolumeError> {
+        let data = files
+            .get(path)
+            .ok_or_else(|| VolumeError::NoImages(path.to_path_buf()))?;
+        let mut assets = RawAssets::new();
+        assets.insert(path, data.to_vec());
+        Volume::read_vol_assets(path, Ok(assets))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::nrrd::NrrdEncoding;
+    use crate::volume::test_files::{test_path, test_texture};
+    use crate::volume::{Dim, Spacing};
+
+    fn cube_texture() -> Texture {
+        test_texture([2, 2, 2], Spacing::default(), |index| index as u8)
+    }
+
+    struct TestLoader;
+
+    impl VolumeLoader for TestLoader {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        fn can_load(&self, path: &Path, _header: &[u8]) -> bool {
+            has_extension(path, &["test", "nrrd"])
+        }
+
+        fn load(&self, _path: &Path, _registry: &LoaderRegistry) -> Result<Texture, VolumeError> {
+            Ok(cube_texture())
+        }
+
+        fn load_memory(
+            &self,
+            _path: &Path,
+            _files: &MemoryFiles,
+            _registry: &LoaderRegistry,
+        ) -> Result<Texture, VolumeError> {
+            Ok(cube_texture())
+        }
+    }
+
+    #[test]
+    fn test_find_loader() {
+        let registry = LoaderRegistry::default();
+        let name = |path: &str| registry.find(Path::new(path)).map(|loader| loader.name());
+
+        assert_eq!(Some("DICOM"), name("assets/data/DCM_0000"));
+        assert_eq!(Some("MetaImage"), name("assets/data/FullHead.mhd"));
+        assert_eq!(Some("NIfTI"), name("brain.NII.GZ"));
+        assert_eq!(Some("vol"), name("Skull.vol"));
+        assert_eq!(None, name("notes.txt"));
+    }
+
+    #[test]
+    fn test_open_errors() {
+        let path = test_path("format", "notes.txt");
+        std::fs::write(&path, "not a volume").unwrap();
+
+        assert!(matches!(
+            Volume::open(&path),
+            Err(VolumeError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            Volume::open(test_path("format", "missing.nrrd")),
+            Err(VolumeError::Io(..))
+        ));
+    }
+
+    #[test]
+    fn test_sniff_nrrd_without_extension() {
+        let path = test_path("format", "volume");
+        let file = std::fs::File::create(&path).unwrap();
+        Volume::write_nrrd_to(&cube_texture(), file, NrrdEncoding::Gzip).unwrap();
+        let volume = Volume::open(&path).unwrap();
+
+        assert_eq!(cube_texture().texture_data, volume.texture.texture_data);
+    }
+
+    #[test]
+    fn test_open_vol() {
+        let path = test_path("format", "cube.vol");
+        let mut data = Vec::new();
+        for value in [2u32, 2, 2, 0] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        for size in [1.0f32, 2.0, 4.0] {
+            data.extend_from_slice(&size.to_be_bytes());
+        }
+        data.extend(0..8u8);
+        std::fs::write(&path, data).unwrap();
+        let volume = Volume::open(&path).unwrap();
+
+        assert_eq!(8, volume.texture.texture_data.len());
+        assert_eq!(
+            Dim {
+                width: 2,
+                height: 2,
+                depth: 2
+            },
+            volume.texture.dimensions
+        );
+    }
+
+    #[test]
+    fn test_registered_loader_takes_precedence() {
+        let mut registry = LoaderRegistry::default();
+        registry.register(TestLoader);
+
+        assert_eq!(
+            Some("test"),
+            registry
+                .find(Path::new("volume.nrrd"))
+                .map(|loader| loader.name())
+        );
+        let path = test_path("format", "volume.test");
+        std::fs::write(&path, []).unwrap();
+        assert_eq!(
+            cube_texture().texture_data,
+            registry.open(&path).unwrap().texture.texture_data
+        );
+    }
+
+    #[test]
+    fn test_registered_loader_in_archive() {
+        let mut registry = LoaderRegistry::default();
+        registry.register(TestLoader);
+        let path = test_path("format", "volume.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        writer
+            .start_file("volume.test", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            cube_texture().texture_data,
+            registry.open(&path).unwrap().texture.texture_data
+        );
+        assert!(Volume::open(&path).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use super::loading::LoadProgress;
+use super::{Dim, Spacing, Texture, ValueRange, VolumeError};
+use rayon::prelude::*;
+
+/// Number of bins along each axis of the joint histogram of values and gradient magnitudes.
+pub const JOINT_HISTOGRAM_BINS: usize = 128;
+
+/// Share of the voxels whose gradient magnitude is below the top of the range. The strongest
+/// gradients, e.g. at metal implants, are clamped so that they don't squash all other
+/// boundaries into the lowest bins.
+const GRADIENT_QUANTILE: f64 = 0.999;
+
+/// Gradient magnitude of every voxel in native units per millimetre, computed with central
+/// differences. The magnitudes are stored in 8 bits spread over [0, max], so that they can be
+/// uploaded next to the volume and sampled by the 2D transfer function.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GradientVolume {
+    pub magnitudes: Vec<u8>,
+    /// Gradient magnitude of the highest stored value. Magnitudes above it are clamped.
+    pub max: f32,
+    /// Voxel counts by value and gradient magnitude. The bins are stored row by row with
+    /// [JOINT_HISTOGRAM_BINS] value bins per gradient bin, starting at the lowest gradient.
+    pub joint_histogram: Vec<f64>,
+}
+
+impl GradientVolume {
+    pub fn from_texture(texture: &Texture) -> Self {
+        GradientVolume::from_texture_with_progress(texture, &LoadProgress::default())
+            .expect("gradient without a load to cancel")
+    }
+
+    /// Like [GradientVolume::from_texture], but stops with [VolumeError::Cancelled] once the load
+    /// is cancelled, which is checked before every slice.
+    pub fn from_texture_with_progress(
+        texture: &Texture,
+        progress: &LoadProgress,
+    ) -> Result<Self, VolumeError> {
+        let values = texture.texture_data.to_values();
+        let (magnitudes, max) = quantize(gradient_magnitudes(
+            &values,
+            texture.dimensions,
+            texture.spacing,
+            progress,
+        )?);
+        let joint_histogram = joint_histogram(&values, &magnitudes, texture.value_range);
+        Ok(GradientVolume {
+            magnitudes,
+            max,
+            joint_histogram,
+        })
+    }
+
+    pub fn magnitude_range(&self) -> ValueRange {
+        ValueRange {
+            min: 0.0,
+            max: self.max,
+        }
+    }
+}
+
+/// Stores the magnitudes in 8 bits and returns them with the magnitude of the highest value.
+fn quantize(magnitudes: Vec<f32>) -> (Vec<u8>, f32) {
+    let max = quantile(&magnitudes, GRADIENT_QUANTILE);
+    let max = if max > 0.0 { max } else { 1.0 };
+    let quantized = magnitudes
+        .par_iter()
+        .map(|&magnitude| ((magnitude / max).min(1.0) * 255.0).round() as u8)
+        .collect();
+    (quantized, max)
+}
+
+fn joint_histogram(values: &[f32], magnitudes: &[u8], value_range: ValueRange) -> Vec<f64> {
+    let bins = JOINT_HISTOGRAM_BINS;
+    let scale = (bins - 1) as f32;
+    let mut histogram = vec![0.0_f64; bins * bins];
+    for (&value, &magnitude) in values.iter().zip(magnitudes) {
+        let value_bin = (value_range.normalize(value) * scale).round() as usize;
+        let gradient_bin = (magnitude as f32 / 255.0 * scale).round() as usize;
+        histogram[gradient_bin * bins + value_bin] += 1.0;
+    }
+    histogram
+}
+
+fn gradient_magnitudes(
+    values: &[f32],
+    dimensions: Dim,
+    spacing: Spacing,
+    progress: &LoadProgress,
+) -> Result<Vec<f32>, VolumeError> {
+    let size = [
+        dimensions.width as usize,
+        dimensions.height as usize,
+        dimensions.depth as usize,
+    ];
+    let spacing = [spacing.x, spacing.y, spacing.z];
+    let slice_size = size[0] * size[1];
+    // Data that doesn't fill its dimensions, e.g. a placeholder texture, has no gradient.
+    if values.len() != slice_size * size[2] {
+        return Ok(vec![0.0; values.len()]);
+    }
+    let index =
+        |position: [usize; 3]| position[0] + position[1] * size[0] + position[2] * slice_size;
+
+    let mut magnitudes = vec![0.0; values.len()];
+    magnitudes
+        .par_chunks_mut(slice_size.max(1))
+        .enumerate()
+        .try_for_each(|(z, slice)| -> Result<(), VolumeError> {
+            progress.check_cancelled()?;
+            for (offset, magnitude) in slice.iter_mut().enumerate() {
+                let position = [offset % size[0], offset / size[0], z];
+                let squared_sum: f32 = (0..3)
+                    .map(|axis| {
+                        // One-sided differences at the borders of the volume.
+                        let mut previous = position;
+                        let mut next = position;
+                        previous[axis] = position[axis].saturating_sub(1);
+                        next[axis] = (position[axis] + 1).min(size[axis] - 1);
+                        let distance = (next[axis] - previous[axis]) as f32 * spacing[axis];
+                        if distance <= 0.0 {
+                            return 0.0;
+                        }
+                        let derivative = (values[index(next)] - values[index(previous)]) / distance;
+                        derivative * derivative
+                    })
+                    .sum();
+                *magnitude = squared_sum.sqrt();
+            }
+            Ok(())
+        })?;
+    Ok(magnitudes)
+}
+
+/// Finds the value below which the given share of the values lies, up to 1/1024 of their maximum.
+fn quantile(values: &[f32], share: f64) -> f32 {
+    const BINS: usize = 1024;
+    let max = values.par_iter().copied().reduce(|| 0.0, f32::max);
+    if max <= 0.0 {
+        return 0.0;
+    }
+    let mut histogram = [0_usize; BINS];
+    for &value in values {
+        histogram[((value / max) * (BINS - 1) as f32) as usize] += 1;
+    }
+    let target = (values.len() as f64 * share).ceil() as usize;
+    let mut count = 0;
+    for (bin, &bin_count) in histogram.iter().enumerate() {
+        count += bin_count;
+        if count >= target {
+            return (bin + 1) as f32 / BINS as f32 * max;
+        }
+    }
+    max
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::test_texture;
+    use crate::volume::VoxelData;
+
+    /// Volume whose values rise by 10 per voxel along x.
+    fn ramp_texture(spacing: Spacing) -> Texture {
+        test_texture([4, 3, 2], spacing, |index| (index % 4) as f32 * 10.0)
+    }
+
+    #[test]
+    fn test_gradient_magnitude() {
+        let texture = ramp_texture(Spacing {
+            x: 2.0,
+            y: 1.0,
+            z: 1.0,
+        });
+        let gradient = GradientVolume::from_texture(&texture);
+
+        // 10 per voxel over 2 mm voxels, also at the borders.
+        assert_eq!(5.0, gradient.max);
+        assert!(gradient
+            .magnitudes
+            .iter()
+            .all(|&magnitude| magnitude == 255));
+    }
+
+    #[test]
+    fn test_joint_histogram() {
+        let texture = ramp_texture(Spacing::default());
+        let histogram = GradientVolume::from_texture(&texture).joint_histogram;
+        let top_row = (JOINT_HISTOGRAM_BINS - 1) * JOINT_HISTOGRAM_BINS;
+
+        assert_eq!(JOINT_HISTOGRAM_BINS * JOINT_HISTOGRAM_BINS, histogram.len());
+        assert_eq!(24.0, histogram.iter().sum::<f64>());
+        assert_eq!(6.0, histogram[top_row]);
+        assert_eq!(6.0, histogram[top_row + JOINT_HISTOGRAM_BINS - 1]);
+    }
+
+    #[test]
+    fn test_constant_volume() {
+        let mut texture = ramp_texture(Spacing::default());
+        texture.texture_data = VoxelData::from_values(vec![7.0; 24]);
+        let gradient = GradientVolume::from_texture(&texture);
+
+        assert_eq!(1.0, gradient.max);
+        assert!(gradient.magnitudes.iter().all(|&magnitude| magnitude == 0));
+    }
+
+    #[test]
+    fn test_cancel_gradient() {
+        let progress = LoadProgress::default();
+        progress.cancel();
+        let result = GradientVolume::from_texture_with_progress(
+            &ramp_texture(Spacing::default()),
+            &progress,
+        );
+
+        assert!(matches!(result, Err(VolumeError::Cancelled)));
+    }
+}
@@ -0,0 +1,451 @@
+use super::archive::extract_archive;
+use super::dicom_dir::is_dicom_dir;
+use super::dicom_series::{largest_series, DicomSeries};
+use super::format::{is_archive_file, LoaderRegistry};
+use super::gradient::GradientVolume;
+use super::memory::MemoryFiles;
+use super::resample::{resample_with_progress, Interpolation};
+use super::{DecodeFailurePolicy, Spacing, Texture, Volume, VolumeError};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Arc;
+
+/// Progress of a volume load, shared between the loading thread and the user interface.
+#[derive(Debug, Default)]
+pub struct LoadProgress {
+    files_total: AtomicUsize,
+    files_done: AtomicUsize,
+    bytes_total: AtomicU64,
+    bytes_read: AtomicU64,
+    scanning: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl LoadProgress {
+    /// Starts counting the files whose headers are scanned for series.
+    pub fn start_scan(&self, files_total: usize) {
+        self.scanning.store(true, Ordering::Relaxed);
+        self.files_total.store(files_total, Ordering::Relaxed);
+        self.files_done.store(0, Ordering::Relaxed);
+    }
+
+    /// Starts counting the files that are read, after any scan.
+    pub fn set_files_total(&self, files_total: usize) {
+        self.scanning.store(false, Ordering::Relaxed);
+        self.files_total.store(files_total, Ordering::Relaxed);
+        self.files_done.store(0, Ordering::Relaxed);
+    }
+
+    /// Measures the load in bytes instead of files, for formats that store a volume in one file.
+    pub fn set_bytes_total(&self, bytes_total: u64) {
+        self.bytes_total.store(bytes_total, Ordering::Relaxed);
+    }
+
+    /// Records a file that has been read.
+    pub fn file_done(&self, bytes: u64) {
+        self.files_done.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn files_total(&self) -> usize {
+        self.files_total.load(Ordering::Relaxed)
+    }
+
+    pub fn files_done(&self) -> usize {
+        self.files_done.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_total(&self) -> u64 {
+        self.bytes_total.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.scanning.load(Ordering::Relaxed)
+    }
+
+    /// Fraction of the bytes or files that have been read, or of the files that have been
+    /// scanned, in [0, 1].
+    pub fn fraction(&self) -> f32 {
+        let (done, total) = match self.bytes_total() {
+            0 => (self.files_done() as f64, self.files_total() as f64),
+            bytes_total => (self.bytes_read() as f64, bytes_total as f64),
+        };
+        if total == 0.0 {
+            return 0.0;
+        }
+        (done / total).min(1.0) as f32
+    }
+
+    /// Asks the loader to stop. It returns [VolumeError::Cancelled] before reading the next file.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns [VolumeError::Cancelled] if the load has been cancelled.
+    pub fn check_cancelled(&self) -> Result<(), VolumeError> {
+        if self.is_cancelled() {
+            return Err(VolumeError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+pub enum LoadRequest {
+    /// Opens a file or directory. DICOM directories, DICOMDIRs and archives are scanned for
+    /// series and the series with the most slices is read. Formats that can be downsampled while
+    /// they are read are reduced to the given largest texture size along each axis.
+    Open(PathBuf, usize),
+    /// Reads a series found by an earlier [LoadRequest::Open].
+    Series(DicomSeries),
+    /// Reads in-memory files, such as files opened in the browser.
+    Memory(MemoryFiles),
+    /// Resamples a loaded volume to another spacing.
+    Resample(Texture, Spacing, Interpolation),
+    /// Computes the gradient magnitudes and the joint histogram of a loaded volume for the 2D
+    /// transfer function.
+    Gradient(Texture),
+}
+
+/// How the volume of a finished load relates to the current one.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum LoadKind {
+    /// A new volume, for which the scene is reset.
+    #[default]
+    New,
+    /// A resample of the current volume, so that the thresholds and transfer functions chosen
+    /// for the current volume still apply to it.
+    Resample,
+    /// The current volume together with its gradient magnitudes.
+    Gradient,
+}
+
+pub struct LoadOutcome {
+    pub result: Result<Volume, VolumeError>,
+    /// Files that were left out because they could not be read.
+    pub skipped_files: Vec<VolumeError>,
+    /// Series found when a DICOM directory was opened, together with the index of the loaded one.
+    pub series: Option<(Vec<DicomSeries>, Option<usize>)>,
+    pub kind: LoadKind,
+}
+
+/// Loads a volume on a background thread. WebAssembly has no threads, so there the volume is
+/// loaded when the task is created.
+pub struct VolumeLoadTask {
+    progress: Arc<LoadProgress>,
+    receiver: Receiver<LoadOutcome>,
+}
+
+impl VolumeLoadTask {
+    pub fn spawn(request: LoadRequest) -> Self {
+        let progress = Arc::new(LoadProgress::default());
+        let (sender, receiver) = channel();
+        let task_progress = progress.clone();
+        let job = move || {
+            let outcome = load(request, &task_progress);
+            // The receiver is gone if the task was dropped, and then nobody needs the volume.
+            let _ = sender.send(outcome);
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(job);
+        #[cfg(target_arch = "wasm32")]
+        job();
+
+        VolumeLoadTask { progress, receiver }
+    }
+
+    pub fn progress(&self) -> &LoadProgress {
+        &self.progress
+    }
+
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+
+    /// Returns the outcome once loading has finished, without blocking.
+    pub fn try_take(&self) -> Option<LoadOutcome> {
+        match self.receiver.try_recv() {
+            Ok(outcome) => Some(outcome),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                log::error!("Volume loading stopped without a result");
+                Some(LoadOutcome {
+                    result: Err(VolumeError::Cancelled),
+                    skipped_files: Vec::new(),
+                    series: None,
+                    kind: LoadKind::New,
+                })
+            }
+        }
+    }
+}
+
+impl Drop for VolumeLoadTask {
+    /// Stops a load that is replaced by another one.
+    fn drop(&mut self) {
+        self.progress.cancel();
+    }
+}
+
+fn load(request: LoadRequest, progress: &LoadProgress) -> LoadOutcome {
+    match request {
+        LoadRequest::Open(path, _) if path.is_dir() || is_dicom_dir(&path) => {
+            match Volume::scan_dicom_series_with_progress(&path, progress) {
+                Ok(series) => load_largest_series(&path, series, progress),
+                Err(error) => failed(error),
+            }
+        }
+        LoadRequest::Open(path, max_texture_size) if is_archive_file(&path) => {
+            load_archive(&path, max_texture_size, progress)
+        }
+        LoadRequest::Open(path, max_texture_size) => load_file(&path, max_texture_size, progress),
+        LoadRequest::Series(series) => load_series(&series, progress),
+        LoadRequest::Memory(files) => {
+            progress.set_files_total(files.files().len());
+            let result = Volume::from_memory(&files);
+            for file in files.files() {
+                progress.file_done(file.data.len() as u64);
+            }
+            LoadOutcome {
+                result,
+                skipped_files: Vec::new(),
+                series: None,
+                kind: LoadKind::New,
+            }
+        }
+        LoadRequest::Resample(texture, spacing, interpolation) => {
+            progress.set_files_total(1);
+            let result = resample_with_progress(&texture, spacing, interpolation, progress);
+            progress.file_done(0);
+            LoadOutcome {
+                result: result.map(Volume::from_texture),
+                skipped_files: Vec::new(),
+                series: None,
+                kind: LoadKind::Resample,
+            }
+        }
+        LoadRequest::Gradient(texture) => {
+            progress.set_files_total(1);
+            let result = GradientVolume::from_texture_with_progress(&texture, progress);
+            progress.file_done(0);
+            LoadOutcome {
+                result: result.map(|gradient| Volume {
+                    gradient: Some(gradient),
+                    ..Volume::from_texture(texture)
+                }),
+                skipped_files: Vec::new(),
+                series: None,
+                kind: LoadKind::Gradient,
+            }
+        }
+    }
+}
+
+fn load_largest_series(
+    path: &Path,
+    series: Vec<DicomSeries>,
+    progress: &LoadProgress,
+) -> LoadOutcome {
+    let Some(index) = largest_series(&series) else {
+        return failed(VolumeError::NoImages(path.to_path_buf()));
+    };
+    let mut outcome = load_series(&series[index], progress);
+    outcome.series = Some((series, Some(index)));
+    outcome
+}
+
+/// Extracts an archive and scans it like a directory, so that its series can be picked.
+/// Archives without DICOM images, such as a zipped NRRD file, are read like a single file.
+fn load_archive(path: &Path, max_texture_size: usize, progress: &LoadProgress) -> LoadOutcome {
+    let series = extract_archive(path)
+        .and_then(|directory| Volume::scan_dicom_series_with_progress(&directory, progress));
+    match series {
+        Ok(series) if series.is_empty() => load_file(path, max_texture_size, progress),
+        Ok(series) => load_largest_series(path, series, progress),
+        Err(error) => failed(error),
+    }
+}
+
+fn load_file(path: &Path, max_texture_size: usize, progress: &LoadProgress) -> LoadOutcome {
+    let size = file_size(path);
+    progress.set_bytes_total(size);
+    if let Err(error) = progress.check_cancelled() {
+        return failed(error);
+    }
+    let mut registry = LoaderRegistry::default();
+    registry.set_max_texture_size(max_texture_size);
+    let result = registry.open(path);
+    progress.file_done(size);
+    LoadOutcome {
+        result,
+        skipped_files: Vec::new(),
+        series: None,
+        kind: LoadKind::New,
+    }
+}
+
+fn load_series(series: &DicomSeries, progress: &LoadProgress) -> LoadOutcome {
+    let result =
+        Volume::read_dicom_files_with_progress(&series.files, DecodeFailurePolicy::Skip, progress);
+    match result {
+        Ok((texture, skipped_files)) => LoadOutcome {
+            result: Ok(Volume::from_texture(texture)),
+            skipped_files,
+            series: None,
+            kind: LoadKind::New,
+        },
+        Err(error) => failed(error),
+    }
+}
+
+fn failed(error: VolumeError) -> LoadOutcome {
+    LoadOutcome {
+        result: Err(error),
+        skipped_files: Vec::new(),
+        series: None,
+        kind: LoadKind::New,
+    }
+}
+
+pub(super) fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::{ct_directory, slice_path, test_path, write_test_file};
+
+    fn wait(task: &VolumeLoadTask) -> LoadOutcome {
+        loop {
+            if let Some(outcome) = task.try_take() {
+                return outcome;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_load_in_background() {
+        let directory = ct_directory("background");
+        let task = VolumeLoadTask::spawn(LoadRequest::Open(directory, usize::MAX));
+        let outcome = wait(&task);
+
+        assert_eq!(3, outcome.result.unwrap().texture.dimensions.depth);
+        assert_eq!(3, task.progress().files_done());
+        assert_eq!(3, task.progress().files_total());
+        assert_eq!(1.0, task.progress().fraction());
+        assert!(task.progress().bytes_read() > 3 * 512 * 512);
+        assert_eq!(Some(0), outcome.series.unwrap().1);
+    }
+
+    #[test]
+    fn test_cancel_loading() {
+        let directory = ct_directory("cancel");
+        let series = Volume::scan_dicom_series(&directory).unwrap();
+        let progress = LoadProgress::default();
+        progress.cancel();
+        let outcome = load(LoadRequest::Series(series[0].clone()), &progress);
+
+        assert!(matches!(outcome.result, Err(VolumeError::Cancelled)));
+        assert_eq!(0, progress.files_done());
+    }
+
+    #[test]
+    fn test_cancel_scan() {
+        let directory = ct_directory("cancel_scan");
+        let progress = LoadProgress::default();
+        progress.cancel();
+        let outcome = load(LoadRequest::Open(directory, usize::MAX), &progress);
+
+        assert!(matches!(outcome.result, Err(VolumeError::Cancelled)));
+        assert!(progress.is_scanning());
+        assert_eq!(3, progress.files_total());
+        assert_eq!(0, progress.files_done());
+    }
+
+    #[test]
+    fn test_single_file_progress_in_bytes() {
+        let mut nrrd =
+            b"NRRD0004\ntype: uchar\ndimension: 3\nsizes: 2 2 2\nencoding: raw\n\n".to_vec();
+        nrrd.extend([0, 1, 2, 3, 4, 5, 6, 7]);
+        let path = write_test_file("loading", "progress.nrrd", &nrrd);
+        let progress = LoadProgress::default();
+        let outcome = load(LoadRequest::Open(path.clone(), usize::MAX), &progress);
+
+        assert!(outcome.result.is_ok());
+        assert_eq!(file_size(&path), progress.bytes_total());
+        assert_eq!(1.0, progress.fraction());
+    }
+
+    #[test]
+    fn test_load_archive() {
+        let path = test_path("loading", "archive.zip");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        for name in ["CT000001", "CT000002"] {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            let data = std::fs::read(slice_path(name)).unwrap();
+            std::io::Write::write_all(&mut writer, &data).unwrap();
+        }
+        writer.finish().unwrap();
+        let outcome = load(
+            LoadRequest::Open(path, usize::MAX),
+            &LoadProgress::default(),
+        );
+        let (series, selected_series) = outcome.series.unwrap();
+
+        assert_eq!(2, outcome.result.unwrap().texture.dimensions.depth);
+        assert_eq!(1, series.len());
+        assert_eq!(Some(0), selected_series);
+        assert!(series[0].files.iter().all(|file| file.is_file()));
+    }
+
+    #[test]
+    fn test_resample_is_marked() {
+        let directory = ct_directory("resample");
+        let series = Volume::scan_dicom_series(&directory).unwrap();
+        let progress = LoadProgress::default();
+        let texture = load(LoadRequest::Series(series[0].clone()), &progress)
+            .result
+            .unwrap()
+            .texture;
+        let spacing = texture.spacing;
+        let outcome = load(
+            LoadRequest::Resample(texture, spacing, Interpolation::Trilinear),
+            &progress,
+        );
+
+        assert!(outcome.result.is_ok());
+        assert_eq!(LoadKind::Resample, outcome.kind);
+    }
+
+    #[test]
+    fn test_gradient_in_background() {
+        let directory = ct_directory("gradient");
+        let series = Volume::scan_dicom_series(&directory).unwrap();
+        let progress = LoadProgress::default();
+        let texture = load(LoadRequest::Series(series[0].clone()), &progress)
+            .result
+            .unwrap()
+            .texture;
+        let voxels = texture.texture_data.len();
+        let outcome = load(LoadRequest::Gradient(texture), &progress);
+
+        assert_eq!(LoadKind::Gradient, outcome.kind);
+        let gradient = outcome.result.unwrap().gradient.unwrap();
+        assert_eq!(voxels, gradient.magnitudes.len());
+    }
+}
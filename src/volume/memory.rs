@@ -0,0 +1,218 @@
+use super::dicom_series::{group_dicom_files, largest_series};
+use super::format::LoaderRegistry;
+use super::loading::LoadProgress;
+use super::{DecodeFailurePolicy, Texture, Volume, VolumeError};
+use dicom::core::Tag;
+use dicom_object::{DefaultDicomObject, OpenFileOptions, ReadError};
+use std::borrow::Cow;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A file held in memory, such as a file dropped into the browser or fetched by JavaScript.
+#[derive(Debug, Clone)]
+pub struct MemoryFile {
+    /// File name or path relative to the other files, which is used to detect the format and to
+    /// find detached data files.
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// A set of files that is read without a file system.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFiles {
+    files: Vec<MemoryFile>,
+}
+
+impl MemoryFiles {
+    pub fn new(files: Vec<MemoryFile>) -> Self {
+        MemoryFiles { files }
+    }
+
+    pub fn push(&mut self, name: &str, data: Vec<u8>) {
+        self.files.push(MemoryFile {
+            name: name.to_string(),
+            data,
+        });
+    }
+
+    pub fn files(&self) -> &[MemoryFile] {
+        &self.files
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the file with the given path or, as browsers only pass file names, the file with
+    /// the same file name.
+    pub fn get(&self, path: &Path) -> Option<&[u8]> {
+        let file_name = path.file_name()?;
+        self.files
+            .iter()
+            .find(|file| Path::new(&file.name) == path)
+            .or_else(|| {
+                self.files
+                    .iter()
+                    .find(|file| Path::new(&file.name).file_name() == Some(file_name))
+            })
+            .map(|file| file.data.as_slice())
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .map(|file| PathBuf::from(&file.name))
+            .collect()
+    }
+}
+
+/// Where the loaders read files from, so that the same code reads from disk and from memory.
+pub(super) trait FileSource: Sync {
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>>;
+
+    /// Opens a DICOM file, reading it up to the given tag.
+    fn open_dicom(
+        &self,
+        path: &Path,
+        read_until: Option<Tag>,
+    ) -> Result<DefaultDicomObject, ReadError> {
+        let data = self.read(path).unwrap_or_default();
+        dicom_options(read_until).from_reader(data.as_ref())
+    }
+}
+
+/// Reads files from disk.
+pub(super) struct FileSystem;
+
+impl FileSource for FileSystem {
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        std::fs::read(path).map(Cow::Owned)
+    }
+
+    fn open_dicom(
+        &self,
+        path: &Path,
+        read_until: Option<Tag>,
+    ) -> Result<DefaultDicomObject, ReadError> {
+        dicom_options(read_until).open_file(path)
+    }
+}
+
+impl FileSource for MemoryFiles {
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        self.get(path)
+            .map(Cow::Borrowed)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+fn dicom_options(read_until: Option<Tag>) -> OpenFileOptions {
+    match read_until {
+        Some(tag) => OpenFileOptions::new().read_until(tag),
+        None => OpenFileOptions::new(),
+    }
+}
+
+impl Volume {
+    /// Detects the format of a set of in-memory files and reads the volume they contain. DICOM
+    /// files are grouped into series and the series with the most slices is read. MetaImage, NRRD
+    /// and NIfTI headers find their detached data files among the other files.
+    pub fn from_memory(files: &MemoryFiles) -> Result<Volume, VolumeError> {
+        LoaderRegistry::default().open_memory(files)
+    }
+
+    /// Reads a volume from the bytes of a single file, e.g. an .mha, .nrrd, .nii.gz or DICOM file.
+    /// The name is used to detect the format.
+    pub fn from_bytes(name: &str, data: &[u8]) -> Result<Volume, VolumeError> {
+        let mut files = MemoryFiles::default();
+        files.push(name, data.to_vec());
+        Volume::from_memory(&files)
+    }
+
+    /// Reads the DICOM series with the most slices from in-memory DICOM files. Files that are not
+    /// DICOM or contain no image are skipped.
+    pub fn read_dicom_memory(files: &MemoryFiles) -> Result<Texture, VolumeError> {
+        let series = group_dicom_files(files, &files.paths(), &LoadProgress::default())?;
+        let first_path = || {
+            PathBuf::from(
+                files
+                    .files()
+                    .first()
+                    .map(|file| file.name.as_str())
+                    .unwrap_or_default(),
+            )
+        };
+        let index = largest_series(&series).ok_or_else(|| VolumeError::NoImages(first_path()))?;
+        let (texture, _) = Volume::read_dicom_slices(
+            files,
+            &series[index].files,
+            DecodeFailurePolicy::Abort,
+            &LoadProgress::default(),
+        )?;
+        Ok(texture)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::VoxelData;
+
+    fn read(path: &str) -> Vec<u8> {
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn test_memory_files_lookup() {
+        let mut files = MemoryFiles::default();
+        files.push("head.mhd", b"header".to_vec());
+        files.push("data/head.raw", b"data".to_vec());
+
+        assert_eq!(Some(&b"data"[..]), files.get(Path::new("data/head.raw")));
+        assert_eq!(Some(&b"data"[..]), files.get(Path::new("head.raw")));
+        assert_eq!(None, files.get(Path::new("other.raw")));
+    }
+
+    #[test]
+    fn test_read_dicom_from_memory() {
+        let mut files = MemoryFiles::default();
+        for name in ["CT000003", "CT000001", "CT000002"] {
+            files.push(name, read(&format!("assets/data/DCM_0000/{}", name)));
+        }
+        files.push("README.txt", b"not an image".to_vec());
+        let volume = Volume::from_memory(&files).unwrap();
+        let texture = Volume::read_dicom_files(&[
+            PathBuf::from("assets/data/DCM_0000/CT000001"),
+            PathBuf::from("assets/data/DCM_0000/CT000002"),
+            PathBuf::from("assets/data/DCM_0000/CT000003"),
+        ])
+        .unwrap();
+
+        assert_eq!(texture.dimensions, volume.texture.dimensions);
+        assert_eq!(texture.texture_data, volume.texture.texture_data);
+    }
+
+    #[test]
+    fn test_read_meta_image_from_memory() {
+        let mut files = MemoryFiles::default();
+        files.push("data/head.raw", (1..=8).collect());
+        files.push(
+            "head.mhd",
+            b"NDims = 3\nDimSize = 2 2 2\nElementType = MET_UCHAR\nElementDataFile = head.raw\n"
+                .to_vec(),
+        );
+        let volume = Volume::from_memory(&files).unwrap();
+
+        assert_eq!(2, volume.texture.dimensions.depth);
+        assert_eq!(
+            VoxelData::U8((1..=8).collect()),
+            volume.texture.texture_data
+        );
+    }
+
+    #[test]
+    fn test_unknown_bytes() {
+        let result = Volume::from_bytes("notes.txt", b"not a volume");
+        assert!(matches!(result, Err(VolumeError::UnsupportedFormat(_))));
+    }
+}
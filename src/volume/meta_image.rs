@@ -0,0 +1,713 @@
+use super::memory::{FileSource, FileSystem};
+use super::orientation::Orientation;
+use super::raw::{downsample, downsampling_factors, map_file, Downsampling, RawLayout};
+use super::{Dim, ElementType, Spacing, Texture, Volume, VolumeError, VoxelData};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+fn parse_element_type(value: &str) -> Option<ElementType> {
+    match value {
+        "MET_UCHAR" => Some(ElementType::U8),
+        "MET_CHAR" => Some(ElementType::I8),
+        "MET_SHORT" => Some(ElementType::I16),
+        "MET_USHORT" => Some(ElementType::U16),
+        "MET_INT" => Some(ElementType::I32),
+        "MET_UINT" => Some(ElementType::U32),
+        "MET_FLOAT" => Some(ElementType::F32),
+        "MET_DOUBLE" => Some(ElementType::F64),
+        _ => None,
+    }
+}
+
+/// Where the voxel data of a MetaImage is stored.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ElementDataFile {
+    /// The data directly follows the header in the same file.
+    Local,
+    /// A single file, relative to the header.
+    File(String),
+    /// One file per slice, relative to the header.
+    List(Vec<String>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MetaImageHeader {
+    pub dimensions: Dim,
+    pub element_type: ElementType,
+    pub spacing: Spacing,
+    pub offset: [f64; 3],
+    /// Direction cosines in row-major order.
+    pub transform_matrix: [f64; 9],
+    pub anatomical_orientation: Option<String>,
+    pub byte_order_msb: bool,
+    /// Number of bytes to skip at the start of each data file, -1 means the data sits at the end.
+    pub header_size: i64,
+    /// The voxel data is zlib compressed, optionally with the size of the compressed stream.
+    pub compressed_data: bool,
+    pub compressed_data_size: Option<usize>,
+    pub element_data_file: ElementDataFile,
+}
+
+#[derive(Debug)]
+pub enum MetaImageError {
+    Io(PathBuf, std::io::Error),
+    MissingField(&'static str),
+    InvalidValue(String, String),
+    UnsupportedElementType(String),
+    TruncatedData { expected: usize, actual: usize },
+    Decompression(std::io::Error),
+}
+
+impl fmt::Display for MetaImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetaImageError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            MetaImageError::MissingField(field) => write!(f, "missing field {}", field),
+            MetaImageError::InvalidValue(field, value) => {
+                write!(f, "invalid value '{}' for field {}", value, field)
+            }
+            MetaImageError::UnsupportedElementType(element_type) => {
+                write!(f, "unsupported ElementType {}", element_type)
+            }
+            MetaImageError::TruncatedData { expected, actual } => write!(
+                f,
+                "expected {} bytes of voxel data but found {}",
+                expected, actual
+            ),
+            MetaImageError::Decompression(error) => {
+                write!(f, "cannot decompress voxel data: {}", error)
+            }
+        }
+    }
+}
+
+impl MetaImageHeader {
+    /// Parses the `Key = Value` lines of a MetaImage header. Parsing stops after ElementDataFile,
+    /// which is always the last field; the returned offset is where the voxel data starts for
+    /// `ElementDataFile = LOCAL`.
+    pub fn parse(meta_data: &[u8]) -> Result<(MetaImageHeader, usize), MetaImageError> {
+        let mut n_dims = 3;
+        let mut dim_size: Option<Vec<i32>> = None;
+        let mut element_type = None;
+        let mut element_spacing: Option<Vec<f64>> = None;
+        let mut element_size: Option<Vec<f64>> = None;
+        let mut offset = [0.0; 3];
+        let mut transform_matrix = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let mut anatomical_orientation = None;
+        let mut byte_order_msb = false;
+        let mut header_size = 0;
+        let mut compressed_data = false;
+        let mut compressed_data_size = None;
+        let mut element_data_file = None;
+
+        let mut position = 0;
+        while position < meta_data.len() && element_data_file.is_none() {
+            let line_end = meta_data[position..]
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .map_or(meta_data.len(), |end| position + end);
+            let line = String::from_utf8_lossy(&meta_data[position..line_end]);
+            position = (line_end + 1).min(meta_data.len());
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            let value = value.trim();
+
+            match key {
+                "NDims" => n_dims = parse_value(key, value)?,
+                "DimSize" => dim_size = Some(parse_values(key, value)?),
+                "ElementType" => {
+                    element_type = Some(
+                        parse_element_type(value)
+                            .ok_or(MetaImageError::UnsupportedElementType(value.to_string()))?,
+                    )
+                }
+                "ElementSpacing" => element_spacing = Some(parse_values(key, value)?),
+                "ElementSize" => element_size = Some(parse_values(key, value)?),
+                "Offset" | "Position" | "Origin" => {
+                    let values: Vec<f64> = parse_values(key, value)?;
+                    for (target, value) in offset.iter_mut().zip(values) {
+                        *target = value;
+                    }
+                }
+                "TransformMatrix" | "Rotation" | "Orientation" => {
+                    let values: Vec<f64> = parse_values(key, value)?;
+                    if values.len() == 9 {
+                        transform_matrix.copy_from_slice(&values);
+                    } else if values.len() == 4 {
+                        transform_matrix = [
+                            values[0], values[1], 0.0, values[2], values[3], 0.0, 0.0, 0.0, 1.0,
+                        ];
+                    } else {
+                        return Err(invalid_value(key, value));
+                    }
+                }
+                "AnatomicalOrientation" => anatomical_orientation = Some(value.to_string()),
+                "BinaryDataByteOrderMSB" | "ElementByteOrderMSB" => {
+                    byte_order_msb = parse_bool(key, value)?
+                }
+                "HeaderSize" => header_size = parse_value(key, value)?,
+                "CompressedData" => compressed_data = parse_bool(key, value)?,
+                "CompressedDataSize" => compressed_data_size = Some(parse_value(key, value)?),
+                "ElementNumberOfChannels" if parse_value::<u32>(key, value)? != 1 => {
+                    return Err(invalid_value(key, value));
+                }
+                "ElementDataFile" => {
+                    element_data_file = Some(if value == "LOCAL" {
+                        ElementDataFile::Local
+                    } else if value.starts_with("LIST") {
+                        let files = String::from_utf8_lossy(&meta_data[position..])
+                            .lines()
+                            .map(str::trim)
+                            .filter(|line| !line.is_empty())
+                            .map(str::to_string)
+                            .collect();
+                        position = meta_data.len();
+                        ElementDataFile::List(files)
+                    } else if let Some(files) = expand_file_pattern(value) {
+                        ElementDataFile::List(files)
+                    } else {
+                        ElementDataFile::File(value.to_string())
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        let dim_size = dim_size.ok_or(MetaImageError::MissingField("DimSize"))?;
+        if !(2..=3).contains(&n_dims) || dim_size.len() != n_dims {
+            return Err(invalid_value("NDims", &n_dims.to_string()));
+        }
+        if dim_size.iter().any(|&size| size < 1) {
+            return Err(MetaImageError::InvalidValue(
+                "DimSize".to_string(),
+                format!("{:?}", dim_size),
+            ));
+        }
+        let spacing = element_spacing
+            .or(element_size)
+            .unwrap_or_else(|| vec![1.0; n_dims]);
+        if spacing.len() != n_dims {
+            return Err(MetaImageError::InvalidValue(
+                "ElementSpacing".to_string(),
+                format!("{:?}", spacing),
+            ));
+        }
+
+        let header = MetaImageHeader {
+            dimensions: Dim {
+                width: dim_size[0],
+                height: dim_size[1],
+                depth: dim_size.get(2).copied().unwrap_or(1),
+            },
+            element_type: element_type.ok_or(MetaImageError::MissingField("ElementType"))?,
+            spacing: Spacing {
+                x: spacing[0] as f32,
+                y: spacing[1] as f32,
+                z: spacing.get(2).copied().unwrap_or(1.0) as f32,
+            },
+            offset,
+            transform_matrix,
+            anatomical_orientation,
+            byte_order_msb,
+            header_size,
+            compressed_data,
+            compressed_data_size,
+            element_data_file: element_data_file
+                .ok_or(MetaImageError::MissingField("ElementDataFile"))?,
+        };
+
+        Ok((header, position))
+    }
+
+    /// Returns the position and axis directions of the image. MetaImage coordinates are LPS, as in
+    /// ITK. The AnatomicalOrientation is only used if there is no TransformMatrix.
+    pub fn orientation(&self) -> Orientation {
+        let transform = &self.transform_matrix;
+        let mut directions = [
+            [transform[0], transform[1], transform[2]],
+            [transform[3], transform[4], transform[5]],
+            [transform[6], transform[7], transform[8]],
+        ];
+        if directions == Orientation::default().directions {
+            if let Some(code) = &self.anatomical_orientation {
+                directions = Orientation::from_anatomical_code(code).unwrap_or(directions);
+            }
+        }
+        Orientation {
+            directions,
+            origin: self.offset,
+        }
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.dimensions.width as usize
+            * self.dimensions.height as usize
+            * self.dimensions.depth as usize
+    }
+
+    pub fn data_size(&self) -> usize {
+        self.element_count() * self.element_type.size()
+    }
+
+    /// Converts the raw voxel bytes into native samples, honoring the byte order.
+    pub fn decode(&self, data: &[u8]) -> Result<VoxelData, MetaImageError> {
+        let expected = self.data_size();
+        if data.len() < expected {
+            return Err(MetaImageError::TruncatedData {
+                expected,
+                actual: data.len(),
+            });
+        }
+        let result =
+            self.element_type
+                .decode(&data[..expected], self.byte_order_msb, self.element_count());
+        // The length is checked above, so reading from the slice cannot fail.
+        Ok(result.expect("Voxel data has the expected length"))
+    }
+}
+
+impl Volume {
+    /// Reads a MetaImage file (.mhd header with separate data or single-file .mha).
+    pub fn read_meta_image(header_path: &str) -> Result<Texture, VolumeError> {
+        Ok(Volume::try_read_meta_image(
+            &FileSystem,
+            Path::new(header_path),
+        )?)
+    }
+
+    pub(super) fn try_read_meta_image(
+        source: &dyn FileSource,
+        header_path: &Path,
+    ) -> Result<Texture, MetaImageError> {
+        let meta_data = read_file(source, header_path)?;
+        let (header, data_offset) = MetaImageHeader::parse(&meta_data)?;
+        let data = read_element_data(source, header_path, &meta_data, &header, data_offset)?;
+
+        let texture_data = header.decode(&data)?;
+        Ok(Texture {
+            value_range: texture_data.value_range(),
+            dimensions: header.dimensions,
+            spacing: header.spacing,
+            texture_data,
+            orientation: header.orientation(),
+        })
+    }
+
+    /// Reads a MetaImage and downsamples it if it has more than `max_voxels` voxels or more than
+    /// `max_size` voxels along any axis. Uncompressed
+    /// data in a single file is memory-mapped, so only the sampled voxels are read. Compressed data
+    /// or data split into several files is read into memory first.
+    pub fn read_meta_image_within(
+        header_path: &Path,
+        max_voxels: usize,
+        max_size: usize,
+        method: Downsampling,
+    ) -> Result<Texture, VolumeError> {
+        let meta_data = map_file(header_path)?;
+        let (header, data_offset) = MetaImageHeader::parse(&meta_data)?;
+        let factors = downsampling_factors(header.dimensions, max_voxels, max_size);
+        if factors == [1, 1, 1] {
+            return Ok(Volume::try_read_meta_image(&FileSystem, header_path)?);
+        }
+        log::info!(
+            "Downsampling {} by {:?} to fit into the texture",
+            header_path.display(),
+            factors
+        );
+
+        let mut layout = RawLayout {
+            dimensions: header.dimensions,
+            spacing: header.spacing,
+            element_type: header.element_type,
+            big_endian: header.byte_order_msb,
+            offset: 0,
+        };
+        let directory = header_path.parent().unwrap_or(Path::new(""));
+        let texture = match (&header.element_data_file, header.compressed_data) {
+            (ElementDataFile::Local, false) => {
+                let data_size = meta_data.len() - data_offset;
+                layout.offset = data_offset + skip_size(&header, data_size, header.data_size());
+                downsample(header_path, &meta_data, &layout, factors, method)
+            }
+            (ElementDataFile::File(file), false) => {
+                let data_path = directory.join(file);
+                let data = map_file(&data_path)?;
+                layout.offset = skip_size(&header, data.len(), header.data_size());
+                downsample(&data_path, &data, &layout, factors, method)
+            }
+            _ => {
+                let data =
+                    read_element_data(&FileSystem, header_path, &meta_data, &header, data_offset)?;
+                downsample(header_path, &data, &layout, factors, method)
+            }
+        }?;
+        Ok(Texture {
+            orientation: header.orientation(),
+            ..texture
+        })
+    }
+}
+
+/// Reads the voxel bytes of a MetaImage, inflating them if they are compressed.
+fn read_element_data(
+    source: &dyn FileSource,
+    header_path: &Path,
+    meta_data: &[u8],
+    header: &MetaImageHeader,
+    data_offset: usize,
+) -> Result<Vec<u8>, MetaImageError> {
+    let directory = header_path.parent().unwrap_or(Path::new(""));
+    match &header.element_data_file {
+        ElementDataFile::Local => element_data(&meta_data[data_offset..], header, 1),
+        ElementDataFile::File(file) => {
+            element_data(&read_file(source, &directory.join(file))?, header, 1)
+        }
+        ElementDataFile::List(files) => {
+            let mut data = Vec::with_capacity(header.data_size());
+            for file in files {
+                let slice = read_file(source, &directory.join(file))?;
+                data.extend(element_data(&slice, header, files.len())?);
+            }
+            Ok(data)
+        }
+    }
+}
+
+fn read_file<'a>(source: &'a dyn FileSource, path: &Path) -> Result<Cow<'a, [u8]>, MetaImageError> {
+    source
+        .read(path)
+        .map_err(|error| MetaImageError::Io(path.to_path_buf(), error))
+}
+
+/// Extracts the voxel bytes from a data file which holds `1 / file_count` of the voxel data,
+/// skipping HeaderSize bytes and inflating compressed data.
+fn element_data(
+    data: &[u8],
+    header: &MetaImageHeader,
+    file_count: usize,
+) -> Result<Vec<u8>, MetaImageError> {
+    let expected = header.data_size() / file_count;
+    let stored = if header.compressed_data {
+        header
+            .compressed_data_size
+            .filter(|_| file_count == 1)
+            .unwrap_or(
+                data.len()
+                    .saturating_sub(header.header_size.max(0) as usize),
+            )
+    } else {
+        expected
+    };
+    let skip = skip_size(header, data.len(), stored);
+    if data.len() < skip + stored {
+        return Err(MetaImageError::TruncatedData {
+            expected: skip + stored,
+            actual: data.len(),
+        });
+    }
+    let data = &data[skip..skip + stored];
+
+    if header.compressed_data {
+        inflate(data, expected)
+    } else {
+        Ok(data.to_vec())
+    }
+}
+
+/// Returns the number of bytes before the `stored` bytes of voxel data in a data file.
+fn skip_size(header: &MetaImageHeader, data_len: usize, stored: usize) -> usize {
+    if header.header_size < 0 {
+        data_len.saturating_sub(stored)
+    } else {
+        header.header_size as usize
+    }
+}
+
+/// Inflates a zlib stream, or a gzip stream as written by some tools.
+fn inflate(data: &[u8], expected: usize) -> Result<Vec<u8>, MetaImageError> {
+    let mut inflated = Vec::with_capacity(expected);
+    let result = if data.starts_with(&[0x1f, 0x8b]) {
+        GzDecoder::new(data).read_to_end(&mut inflated)
+    } else {
+        ZlibDecoder::new(data).read_to_end(&mut inflated)
+    };
+    result.map_err(MetaImageError::Decompression)?;
+    Ok(inflated)
+}
+
+/// Expands the `pattern min max step` form of ElementDataFile, e.g. `slice%03d.raw 1 94 1`.
+pub(super) fn expand_file_pattern(value: &str) -> Option<Vec<String>> {
+    let mut parts = value.split_whitespace();
+    let pattern = parts.next()?;
+    let min: i64 = parts.next()?.parse().ok()?;
+    let max: i64 = parts.next()?.parse().ok()?;
+    let step: i64 = parts.next().map_or(Some(1), |step| step.parse().ok())?;
+    if step <= 0 {
+        return None;
+    }
+
+    let start = pattern.find('%')?;
+    let end = start + pattern[start..].find('d')?;
+    let width: usize = pattern[start + 1..end]
+        .trim_start_matches('0')
+        .parse()
+        .unwrap_or(0);
+    let zero_padded = pattern[start + 1..end].starts_with('0');
+    let files = (min..=max)
+        .step_by(step as usize)
+        .map(|index| {
+            let number = if zero_padded {
+                format!("{:0width$}", index, width = width)
+            } else {
+                format!("{:width$}", index, width = width)
+            };
+            format!("{}{}{}", &pattern[..start], number, &pattern[end + 1..])
+        })
+        .collect();
+    Some(files)
+}
+
+fn invalid_value(key: &str, value: &str) -> MetaImageError {
+    MetaImageError::InvalidValue(key.to_string(), value.to_string())
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, MetaImageError> {
+    value.parse().map_err(|_| invalid_value(key, value))
+}
+
+fn parse_values<T: std::str::FromStr>(key: &str, value: &str) -> Result<Vec<T>, MetaImageError> {
+    value
+        .split_whitespace()
+        .map(|part| parse_value(key, part))
+        .collect()
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, MetaImageError> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(invalid_value(key, value)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::write_test_file;
+    use crate::volume::ValueRange;
+
+    const FULL_HEAD_HEADER: &str = "ObjectType = Image
+NDims = 3
+BinaryData = True
+BinaryDataByteOrderMSB = False
+CompressedData = False
+TransformMatrix = -1 0 0 0 1 0 0 0 -1
+Offset = 0 0 0
+CenterOfRotation = 0 0 0
+AnatomicalOrientation = LAS
+ElementSpacing = 0.9375 0.9375 1.5
+ITK_InputFilterName = MetaImageIO
+DimSize = 256 256 94
+ElementType = MET_SHORT
+ElementDataFile = FullHead.raw
+";
+
+    #[test]
+    fn test_parse_dim() {
+        let input = "NDims = 3\nDimSize = 512 512 333\nElementSpacing = 0.402344 0.402344 0.899994\nElementType = MET_USHORT\nElementDataFile = sinus.raw";
+        let expected = Dim {
+            width: 512,
+            height: 512,
+            depth: 333,
+        };
+        let (result, _) = MetaImageHeader::parse(input.as_bytes()).unwrap();
+
+        assert_eq!(expected, result.dimensions);
+    }
+
+    #[test]
+    fn test_parse_full_head_header() {
+        let (header, _) = MetaImageHeader::parse(FULL_HEAD_HEADER.as_bytes()).unwrap();
+
+        assert_eq!(ElementType::I16, header.element_type);
+        assert_eq!(
+            Spacing {
+                x: 0.9375,
+                y: 0.9375,
+                z: 1.5
+            },
+            header.spacing
+        );
+        assert_eq!(
+            [-1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, -1.0],
+            header.transform_matrix
+        );
+        assert_eq!(Some("LAS".to_string()), header.anatomical_orientation);
+        assert_eq!(
+            ElementDataFile::File("FullHead.raw".to_string()),
+            header.element_data_file
+        );
+        assert_eq!(256 * 256 * 94 * 2, header.data_size());
+    }
+
+    #[test]
+    fn test_orientation() {
+        let (header, _) = MetaImageHeader::parse(FULL_HEAD_HEADER.as_bytes()).unwrap();
+        let las = [[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]];
+        assert_eq!(las, header.orientation().directions);
+
+        let input = "NDims = 3\nDimSize = 2 2 2\nOffset = 10 20 30\nAnatomicalOrientation = LAS\nElementType = MET_UCHAR\nElementDataFile = data.raw";
+        let (header, _) = MetaImageHeader::parse(input.as_bytes()).unwrap();
+        let orientation = header.orientation();
+        assert_eq!(las, orientation.directions);
+        assert_eq!([10.0, 20.0, 30.0], orientation.origin);
+    }
+
+    #[test]
+    fn test_parse_invalid_dim_size() {
+        let input =
+            "NDims = 3\nDimSize = -1 2 2\nElementType = MET_UCHAR\nElementDataFile = data.raw";
+        let result = MetaImageHeader::parse(input.as_bytes());
+
+        assert!(matches!(
+            result,
+            Err(MetaImageError::InvalidValue(field, _)) if field == "DimSize"
+        ));
+    }
+
+    #[test]
+    fn test_parse_missing_element_type() {
+        let input = "NDims = 3\nDimSize = 2 2 2\nElementDataFile = data.raw";
+        let result = MetaImageHeader::parse(input.as_bytes());
+
+        assert!(matches!(
+            result,
+            Err(MetaImageError::MissingField("ElementType"))
+        ));
+    }
+
+    #[test]
+    fn test_decode_big_endian() {
+        let input =
+            "NDims = 2\nDimSize = 3 1\nElementType = MET_SHORT\nBinaryDataByteOrderMSB = True\nElementDataFile = LOCAL\n";
+        let (header, _) = MetaImageHeader::parse(input.as_bytes()).unwrap();
+        let result = header
+            .decode(&[0xfc, 0x00, 0x00, 0x00, 0x0b, 0xff])
+            .unwrap();
+
+        assert_eq!(VoxelData::I16(vec![-1024, 0, 3071]), result);
+    }
+
+    #[test]
+    fn test_expand_file_pattern() {
+        let expected = vec![
+            "slice001.raw".to_string(),
+            "slice003.raw".to_string(),
+            "slice005.raw".to_string(),
+        ];
+
+        assert_eq!(Some(expected), expand_file_pattern("slice%03d.raw 1 5 2"));
+    }
+
+    #[test]
+    fn test_read_local_meta_image() {
+        let mut input = b"NDims = 3\nDimSize = 2 1 2\nElementType = MET_USHORT\nElementSpacing = 0.5 0.5 2\nElementDataFile = LOCAL\n".to_vec();
+        input.extend([1, 0, 2, 0, 3, 0, 4, 0]);
+        let path = write_test_file("meta_image", "local.mha", &input);
+        let texture = Volume::read_meta_image(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(VoxelData::U16(vec![1, 2, 3, 4]), texture.texture_data);
+        assert_eq!(ValueRange { min: 1.0, max: 4.0 }, texture.value_range);
+        assert_eq!(2.0, texture.spacing.z);
+    }
+
+    fn zlib_compress(data: &[u8]) -> Vec<u8> {
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_read_compressed_local_meta_image() {
+        let voxels: Vec<i16> = (0..4 * 3 * 2).map(|value| value * 100 - 1024).collect();
+        let compressed = zlib_compress(bytemuck::cast_slice(&voxels));
+        let mut input = format!(
+            "NDims = 3\nDimSize = 4 3 2\nElementType = MET_SHORT\nCompressedData = True\nCompressedDataSize = {}\nElementDataFile = LOCAL\n",
+            compressed.len()
+        )
+        .into_bytes();
+        input.extend(&compressed);
+        let path = write_test_file("meta_image", "compressed.mha", &input);
+        let texture = Volume::read_meta_image(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(VoxelData::I16(voxels), texture.texture_data);
+    }
+
+    #[test]
+    fn test_read_meta_image_downsampled() {
+        let mut raw = vec![0xff; 3];
+        raw.extend(0..32u8);
+        write_test_file("meta_image", "large.raw", &raw);
+        let header = b"NDims = 3\nDimSize = 4 4 2\nElementType = MET_UCHAR\nHeaderSize = -1\nElementDataFile = large.raw\n";
+        let path = write_test_file("meta_image", "large.mhd", header);
+        let texture =
+            Volume::read_meta_image_within(&path, 8, usize::MAX, Downsampling::Stride).unwrap();
+
+        assert_eq!(
+            VoxelData::U8(vec![0, 2, 8, 10, 16, 18, 24, 26]),
+            texture.texture_data
+        );
+        assert_eq!(2.0, texture.spacing.x);
+        assert_eq!(1.0, texture.spacing.z);
+
+        let voxels: Vec<i16> = (0..4 * 3 * 2).map(|value| value * 100 - 1024).collect();
+        let mut input = b"NDims = 3\nDimSize = 4 3 2\nElementType = MET_SHORT\nCompressedData = True\nElementDataFile = LOCAL\n".to_vec();
+        input.extend(zlib_compress(bytemuck::cast_slice(&voxels)));
+        let path = write_test_file("meta_image", "large_compressed.mha", &input);
+        let texture =
+            Volume::read_meta_image_within(&path, 12, usize::MAX, Downsampling::Box).unwrap();
+
+        let averages: Vec<i16> = (0..12).map(|pair| pair * 200 + 50 - 1024).collect();
+        assert_eq!(VoxelData::I16(averages), texture.texture_data);
+        assert_eq!(2, texture.dimensions.width);
+    }
+
+    #[test]
+    fn test_read_compressed_raw_file() {
+        let voxels: Vec<f32> = vec![-0.5, 0.25, 1.5, 1000.0];
+        write_test_file(
+            "meta_image",
+            "compressed.zraw",
+            &zlib_compress(bytemuck::cast_slice(&voxels)),
+        );
+        let path = write_test_file(
+            "meta_image", "compressed.mhd",
+            b"NDims = 3\nDimSize = 2 2 1\nElementType = MET_FLOAT\nCompressedData = True\nElementDataFile = compressed.zraw\n",
+        );
+        let texture = Volume::read_meta_image(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(VoxelData::F32(voxels), texture.texture_data);
+    }
+
+    #[test]
+    fn test_read_meta_image_file_list() {
+        write_test_file("meta_image", "list_0.raw", &[0, 0, 0, 0, 0, 0, 0, 0, 1, 2]);
+        write_test_file("meta_image", "list_1.raw", &[0, 0, 0, 0, 0, 0, 0, 0, 3, 4]);
+        let path = write_test_file(
+            "meta_image", "list.mhd",
+            b"NDims = 3\nDimSize = 2 1 2\nElementType = MET_UCHAR\nHeaderSize = 8\nElementDataFile = LIST\nlist_0.raw\nlist_1.raw\n",
+        );
+        let texture = Volume::read_meta_image(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(VoxelData::U8(vec![1, 2, 3, 4]), texture.texture_data);
+    }
+}
@@ -0,0 +1,576 @@
+use super::memory::{FileSource, FileSystem};
+use super::orientation::{ras_to_lps, Orientation};
+use super::{Dim, ElementType, Spacing, Texture, Volume, VolumeError, VoxelData};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use flate2::read::MultiGzDecoder;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const NIFTI1_HEADER_SIZE: usize = 348;
+const NIFTI2_HEADER_SIZE: usize = 540;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct NiftiHeader {
+    /// 1 for NIfTI-1 and 2 for NIfTI-2.
+    pub version: u8,
+    pub big_endian: bool,
+    pub dimensions: Dim,
+    pub element_type: ElementType,
+    pub spacing: Spacing,
+    pub scl_slope: f64,
+    pub scl_inter: f64,
+    pub vox_offset: usize,
+    pub qform_code: i32,
+    pub sform_code: i32,
+    /// Voxel index to RAS world coordinates in millimeters, taken from sform, qform or pixdim.
+    pub voxel_to_world: [[f64; 4]; 3],
+    /// The voxel data is stored in a separate .img file.
+    pub detached: bool,
+}
+
+#[derive(Debug)]
+pub enum NiftiError {
+    Io(PathBuf, std::io::Error),
+    Decompression(std::io::Error),
+    InvalidHeader(String),
+    UnsupportedDataType(i32),
+    TruncatedData { expected: usize, actual: usize },
+}
+
+impl fmt::Display for NiftiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NiftiError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            NiftiError::Decompression(error) => write!(f, "cannot decompress file: {}", error),
+            NiftiError::InvalidHeader(reason) => write!(f, "invalid header: {}", reason),
+            NiftiError::UnsupportedDataType(datatype) => {
+                write!(f, "unsupported datatype {}", datatype)
+            }
+            NiftiError::TruncatedData { expected, actual } => write!(
+                f,
+                "expected {} bytes of voxel data but found {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl NiftiHeader {
+    /// Parses a NIfTI-1 or NIfTI-2 header. The version and byte order are detected from
+    /// sizeof_hdr, which is 348 or 540.
+    pub fn parse(data: &[u8]) -> Result<NiftiHeader, NiftiError> {
+        if data.len() < NIFTI1_HEADER_SIZE {
+            return Err(NiftiError::InvalidHeader("file too short".to_string()));
+        }
+        let sizeof_hdr_le = LittleEndian::read_i32(data) as usize;
+        let sizeof_hdr_be = BigEndian::read_i32(data) as usize;
+        match (sizeof_hdr_le, sizeof_hdr_be) {
+            (NIFTI1_HEADER_SIZE, _) => parse_nifti1::<LittleEndian>(data, false),
+            (_, NIFTI1_HEADER_SIZE) => parse_nifti1::<BigEndian>(data, true),
+            (NIFTI2_HEADER_SIZE, _) if data.len() >= NIFTI2_HEADER_SIZE => {
+                parse_nifti2::<LittleEndian>(data, false)
+            }
+            (_, NIFTI2_HEADER_SIZE) if data.len() >= NIFTI2_HEADER_SIZE => {
+                parse_nifti2::<BigEndian>(data, true)
+            }
+            _ => Err(NiftiError::InvalidHeader(format!(
+                "sizeof_hdr is {}",
+                sizeof_hdr_le
+            ))),
+        }
+    }
+
+    /// Returns the position and axis directions of the image, converted from the RAS coordinates
+    /// of NIfTI to LPS.
+    pub fn orientation(&self) -> Orientation {
+        let affine = &self.voxel_to_world;
+        let column = |axis: usize| [affine[0][axis], affine[1][axis], affine[2][axis]];
+        let [x, y, z, origin] = [0, 1, 2, 3].map(|axis| ras_to_lps(column(axis)));
+        let mut lps = [[0.0; 4]; 3];
+        for (row, values) in lps.iter_mut().enumerate() {
+            *values = [x[row], y[row], z[row], origin[row]];
+        }
+        Orientation::from_affine(lps).0
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.dimensions.width as usize
+            * self.dimensions.height as usize
+            * self.dimensions.depth as usize
+    }
+
+    /// Decodes the first 3D volume and applies scl_slope/scl_inter if they are set.
+    pub fn decode(&self, data: &[u8]) -> Result<VoxelData, NiftiError> {
+        let count = self.element_count();
+        let expected = count * self.element_type.size();
+        if data.len() < expected {
+            return Err(NiftiError::TruncatedData {
+                expected,
+                actual: data.len(),
+            });
+        }
+        let texture_data = self
+            .element_type
+            .decode(data, self.big_endian, count)
+            .expect("Voxel data has the expected length");
+
+        let identity = self.scl_slope == 1.0 && self.scl_inter == 0.0;
+        if self.scl_slope == 0.0 || !self.scl_slope.is_finite() || identity {
+            return Ok(texture_data);
+        }
+        let slope = self.scl_slope as f32;
+        let inter = self.scl_inter as f32;
+        let values = match texture_data {
+            VoxelData::U8(data) => data.iter().map(|&v| v as f32 * slope + inter).collect(),
+            VoxelData::I16(data) => data.iter().map(|&v| v as f32 * slope + inter).collect(),
+            VoxelData::U16(data) => data.iter().map(|&v| v as f32 * slope + inter).collect(),
+            VoxelData::F32(data) => data.iter().map(|&v| v * slope + inter).collect(),
+        };
+        Ok(VoxelData::from_values(values))
+    }
+}
+
+impl Volume {
+    /// Reads a NIfTI-1/NIfTI-2 image (.nii, .nii.gz or a .hdr/.img pair).
+    pub fn read_nifti(path: &str) -> Result<Texture, VolumeError> {
+        Ok(Volume::try_read_nifti(&FileSystem, Path::new(path))?)
+    }
+
+    pub(super) fn try_read_nifti(
+        source: &dyn FileSource,
+        path: &Path,
+    ) -> Result<Texture, NiftiError> {
+        let data = read_file(source, path)?;
+        let header = NiftiHeader::parse(&data)?;
+
+        let texture_data = if header.detached {
+            let image_path = image_file_path(path);
+            let image = read_file(source, &image_path)?;
+            header.decode(image.get(header.vox_offset..).unwrap_or_default())?
+        } else {
+            header.decode(data.get(header.vox_offset..).unwrap_or_default())?
+        };
+
+        Ok(Texture {
+            value_range: texture_data.value_range(),
+            dimensions: header.dimensions,
+            spacing: header.spacing,
+            texture_data,
+            orientation: header.orientation(),
+        })
+    }
+}
+
+/// Reads a file and inflates it if it is gzip compressed.
+fn read_file<'a>(source: &'a dyn FileSource, path: &Path) -> Result<Cow<'a, [u8]>, NiftiError> {
+    let data = source
+        .read(path)
+        .map_err(|error| NiftiError::Io(path.to_path_buf(), error))?;
+    if !data.starts_with(&[0x1f, 0x8b]) {
+        return Ok(data);
+    }
+    let mut inflated = Vec::new();
+    MultiGzDecoder::new(data.as_ref())
+        .read_to_end(&mut inflated)
+        .map_err(NiftiError::Decompression)?;
+    Ok(Cow::Owned(inflated))
+}
+
+/// Returns the .img file that belongs to a .hdr or .hdr.gz header.
+fn image_file_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let image_name = if let Some(stem) = file_name.strip_suffix(".hdr.gz") {
+        format!("{}.img.gz", stem)
+    } else if let Some(stem) = file_name.strip_suffix(".hdr") {
+        format!("{}.img", stem)
+    } else {
+        format!("{}.img", file_name)
+    };
+    path.with_file_name(image_name)
+}
+
+fn parse_nifti1<B: ByteOrder>(data: &[u8], big_endian: bool) -> Result<NiftiHeader, NiftiError> {
+    let magic = &data[344..348];
+    let detached = match magic {
+        b"n+1\0" => false,
+        b"ni1\0" => true,
+        _ => {
+            return Err(NiftiError::InvalidHeader(
+                "magic is not n+1 or ni1".to_string(),
+            ))
+        }
+    };
+    let read_f32s = |offset: usize, count: usize| -> Vec<f64> {
+        (0..count)
+            .map(|i| B::read_f32(&data[offset + i * 4..]) as f64)
+            .collect()
+    };
+
+    let dim: Vec<i64> = (0..8)
+        .map(|i| B::read_i16(&data[40 + i * 2..]) as i64)
+        .collect();
+    let pixdim = read_f32s(76, 8);
+    let quatern = read_f32s(256, 6);
+    let srow = read_f32s(280, 12);
+
+    build_header(HeaderFields {
+        version: 1,
+        big_endian,
+        detached,
+        datatype: B::read_i16(&data[70..]) as i32,
+        dim,
+        pixdim,
+        vox_offset: B::read_f32(&data[108..]) as usize,
+        scl_slope: B::read_f32(&data[112..]) as f64,
+        scl_inter: B::read_f32(&data[116..]) as f64,
+        xyzt_units: data[123] as i32,
+        qform_code: B::read_i16(&data[252..]) as i32,
+        sform_code: B::read_i16(&data[254..]) as i32,
+        quatern,
+        srow,
+    })
+}
+
+fn parse_nifti2<B: ByteOrder>(data: &[u8], big_endian: bool) -> Result<NiftiHeader, NiftiError> {
+    let detached = match &data[4..8] {
+        b"n+2\0" => false,
+        b"ni2\0" => true,
+        _ => {
+            return Err(NiftiError::InvalidHeader(
+                "magic is not n+2 or ni2".to_string(),
+            ))
+        }
+    };
+    let read_f64s = |offset: usize, count: usize| -> Vec<f64> {
+        (0..count)
+            .map(|i| B::read_f64(&data[offset + i * 8..]))
+            .collect()
+    };
+
+    let dim: Vec<i64> = (0..8).map(|i| B::read_i64(&data[16 + i * 8..])).collect();
+    let pixdim = read_f64s(104, 8);
+    let quatern = read_f64s(352, 6);
+    let srow = read_f64s(400, 12);
+
+    build_header(HeaderFields {
+        version: 2,
+        big_endian,
+        detached,
+        datatype: B::read_i16(&data[12..]) as i32,
+        dim,
+        pixdim,
+        vox_offset: B::read_i64(&data[168..]).max(0) as usize,
+        scl_slope: B::read_f64(&data[176..]),
+        scl_inter: B::read_f64(&data[184..]),
+        xyzt_units: B::read_i32(&data[500..]),
+        qform_code: B::read_i32(&data[344..]),
+        sform_code: B::read_i32(&data[348..]),
+        quatern,
+        srow,
+    })
+}
+
+/// Header fields shared by NIfTI-1 and NIfTI-2, which only differ in their binary layout.
+struct HeaderFields {
+    version: u8,
+    big_endian: bool,
+    detached: bool,
+    datatype: i32,
+    dim: Vec<i64>,
+    pixdim: Vec<f64>,
+    vox_offset: usize,
+    scl_slope: f64,
+    scl_inter: f64,
+    xyzt_units: i32,
+    qform_code: i32,
+    sform_code: i32,
+    /// quatern_b, quatern_c, quatern_d, qoffset_x, qoffset_y, qoffset_z
+    quatern: Vec<f64>,
+    /// srow_x, srow_y, srow_z
+    srow: Vec<f64>,
+}
+
+fn build_header(fields: HeaderFields) -> Result<NiftiHeader, NiftiError> {
+    let element_type = match fields.datatype {
+        2 => ElementType::U8,
+        4 => ElementType::I16,
+        8 => ElementType::I32,
+        16 => ElementType::F32,
+        64 => ElementType::F64,
+        256 => ElementType::I8,
+        512 => ElementType::U16,
+        768 => ElementType::U32,
+        datatype => return Err(NiftiError::UnsupportedDataType(datatype)),
+    };
+
+    let dim = &fields.dim;
+    if !(1..=7).contains(&dim[0]) || dim[1..=dim[0] as usize].iter().any(|&size| size < 1) {
+        return Err(NiftiError::InvalidHeader(format!("dim is {:?}", dim)));
+    }
+    let size = |axis: usize| {
+        if axis <= dim[0] as usize {
+            dim[axis] as i32
+        } else {
+            1
+        }
+    };
+
+    // Spatial units are converted to millimeters.
+    let unit_scale = match fields.xyzt_units & 0x07 {
+        1 => 1000.0,
+        3 => 0.001,
+        _ => 1.0,
+    };
+    let pixdim = |axis: usize| {
+        let spacing = fields.pixdim[axis].abs() * unit_scale;
+        if spacing > 0.0 && spacing.is_finite() && axis <= dim[0] as usize {
+            spacing
+        } else {
+            1.0
+        }
+    };
+    let spacing = [pixdim(1), pixdim(2), pixdim(3)];
+
+    let voxel_to_world = if fields.sform_code > 0 {
+        let srow = &fields.srow;
+        [
+            [srow[0], srow[1], srow[2], srow[3]],
+            [srow[4], srow[5], srow[6], srow[7]],
+            [srow[8], srow[9], srow[10], srow[11]],
+        ]
+        .map(|row| row.map(|value| value * unit_scale))
+    } else if fields.qform_code > 0 {
+        let qfac = if fields.pixdim[0] < 0.0 { -1.0 } else { 1.0 };
+        qform_to_affine(&fields.quatern, spacing, qfac, unit_scale)
+    } else {
+        [
+            [spacing[0], 0.0, 0.0, 0.0],
+            [0.0, spacing[1], 0.0, 0.0],
+            [0.0, 0.0, spacing[2], 0.0],
+        ]
+    };
+
+    Ok(NiftiHeader {
+        version: fields.version,
+        big_endian: fields.big_endian,
+        dimensions: Dim {
+            width: size(1),
+            height: size(2),
+            depth: size(3),
+        },
+        element_type,
+        spacing: Spacing {
+            x: spacing[0] as f32,
+            y: spacing[1] as f32,
+            z: spacing[2] as f32,
+        },
+        scl_slope: fields.scl_slope,
+        scl_inter: fields.scl_inter,
+        vox_offset: fields.vox_offset,
+        qform_code: fields.qform_code,
+        sform_code: fields.sform_code,
+        voxel_to_world,
+        detached: fields.detached,
+    })
+}
+
+/// Builds the qform affine from the quaternion parameters as described in nifti1.h.
+fn qform_to_affine(
+    quatern: &[f64],
+    spacing: [f64; 3],
+    qfac: f64,
+    unit_scale: f64,
+) -> [[f64; 4]; 3] {
+    let (b, c, d) = (quatern[0], quatern[1], quatern[2]);
+    let a = (1.0 - (b * b + c * c + d * d)).max(0.0).sqrt();
+    let rotation = [
+        [
+            a * a + b * b - c * c - d * d,
+            2.0 * (b * c - a * d),
+            2.0 * (b * d + a * c),
+        ],
+        [
+            2.0 * (b * c + a * d),
+            a * a + c * c - b * b - d * d,
+            2.0 * (c * d - a * b),
+        ],
+        [
+            2.0 * (b * d - a * c),
+            2.0 * (c * d + a * b),
+            a * a + d * d - c * c - b * b,
+        ],
+    ];
+    let scale = [spacing[0], spacing[1], spacing[2] * qfac];
+    let mut affine = [[0.0; 4]; 3];
+    for row in 0..3 {
+        for column in 0..3 {
+            affine[row][column] = rotation[row][column] * scale[column];
+        }
+        affine[row][3] = quatern[3 + row] * unit_scale;
+    }
+    affine
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::test_path;
+    use byteorder::WriteBytesExt;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    /// Builds a single-file NIfTI-1 image with a 2x2x2 int16 volume.
+    fn nifti1_file(scl_slope: f32, scl_inter: f32, sform: Option<[f32; 12]>) -> Vec<u8> {
+        let mut data = vec![0u8; 352];
+        LittleEndian::write_i32(&mut data[0..], 348);
+        for (i, size) in [3i16, 2, 2, 2, 1, 1, 1, 1].iter().enumerate() {
+            LittleEndian::write_i16(&mut data[40 + i * 2..], *size);
+        }
+        LittleEndian::write_i16(&mut data[70..], 4);
+        LittleEndian::write_i16(&mut data[72..], 16);
+        for (i, spacing) in [1.0f32, 0.5, 0.5, 2.0].iter().enumerate() {
+            LittleEndian::write_f32(&mut data[76 + i * 4..], *spacing);
+        }
+        LittleEndian::write_f32(&mut data[108..], 352.0);
+        LittleEndian::write_f32(&mut data[112..], scl_slope);
+        LittleEndian::write_f32(&mut data[116..], scl_inter);
+        data[123] = 2;
+        if let Some(srow) = sform {
+            LittleEndian::write_i16(&mut data[254..], 1);
+            for (i, value) in srow.iter().enumerate() {
+                LittleEndian::write_f32(&mut data[280 + i * 4..], *value);
+            }
+        }
+        data[344..348].copy_from_slice(b"n+1\0");
+        for value in 0..8i16 {
+            data.write_i16::<LittleEndian>(value * 10).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_nifti1_header() {
+        let header = NiftiHeader::parse(&nifti1_file(0.0, 0.0, None)).unwrap();
+
+        assert_eq!(1, header.version);
+        assert_eq!(ElementType::I16, header.element_type);
+        assert_eq!(
+            Dim {
+                width: 2,
+                height: 2,
+                depth: 2
+            },
+            header.dimensions
+        );
+        assert_eq!(
+            Spacing {
+                x: 0.5,
+                y: 0.5,
+                z: 2.0
+            },
+            header.spacing
+        );
+        assert_eq!(352, header.vox_offset);
+    }
+
+    #[test]
+    fn test_parse_invalid_header() {
+        let result = NiftiHeader::parse(&[0u8; 400]);
+
+        assert!(matches!(result, Err(NiftiError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn test_sform_affine() {
+        let sform = [
+            -0.5, 0.0, 0.0, 10.0, 0.0, 0.5, 0.0, -20.0, 0.0, 0.0, 2.0, 30.0,
+        ];
+        let header = NiftiHeader::parse(&nifti1_file(0.0, 0.0, Some(sform))).unwrap();
+
+        assert_eq!(
+            [
+                [-0.5, 0.0, 0.0, 10.0],
+                [0.0, 0.5, 0.0, -20.0],
+                [0.0, 0.0, 2.0, 30.0]
+            ],
+            header.voxel_to_world
+        );
+        let orientation = header.orientation();
+        assert_eq!(
+            [[1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]],
+            orientation.directions
+        );
+        assert_eq!([-10.0, 20.0, 30.0], orientation.origin);
+    }
+
+    #[test]
+    fn test_qform_affine() {
+        // A rotation of 180 degrees around z flips x and y.
+        let affine = qform_to_affine(&[0.0, 0.0, 1.0, 1.0, 2.0, 3.0], [0.5, 0.5, 2.0], -1.0, 1.0);
+
+        assert_eq!(
+            [
+                [-0.5, 0.0, 0.0, 1.0],
+                [0.0, -0.5, 0.0, 2.0],
+                [0.0, 0.0, -2.0, 3.0]
+            ],
+            affine
+        );
+    }
+
+    #[test]
+    fn test_read_nifti_with_scaling() {
+        let path = test_path("nifti", "scaled.nii");
+        std::fs::write(&path, nifti1_file(0.5, -1.0, None)).unwrap();
+        let texture = Volume::read_nifti(path.to_str().unwrap()).unwrap();
+
+        let expected: Vec<f32> = (0..8).map(|v| v as f32 * 10.0 * 0.5 - 1.0).collect();
+        assert_eq!(
+            VoxelData::I16(expected.iter().map(|&v| v as i16).collect()),
+            texture.texture_data
+        );
+    }
+
+    #[test]
+    fn test_read_gzipped_nifti() {
+        let path = test_path("nifti", "compressed.nii.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&nifti1_file(0.0, 0.0, None)).unwrap();
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+        let texture = Volume::read_nifti(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(
+            VoxelData::I16((0..8).map(|v| v * 10).collect()),
+            texture.texture_data
+        );
+    }
+
+    #[test]
+    fn test_read_nifti2() {
+        let mut data = vec![0u8; 544];
+        LittleEndian::write_i32(&mut data[0..], 540);
+        data[4..12].copy_from_slice(b"n+2\0\r\n\x1a\n");
+        LittleEndian::write_i16(&mut data[12..], 16);
+        for (i, size) in [3i64, 2, 1, 1, 1, 1, 1, 1].iter().enumerate() {
+            LittleEndian::write_i64(&mut data[16 + i * 8..], *size);
+        }
+        for (i, spacing) in [1.0f64, 0.25, 0.25, 1.0].iter().enumerate() {
+            LittleEndian::write_f64(&mut data[104 + i * 8..], *spacing);
+        }
+        LittleEndian::write_i64(&mut data[168..], 544);
+        LittleEndian::write_i32(&mut data[500..], 2);
+        data.write_f32::<LittleEndian>(-1.5).unwrap();
+        data.write_f32::<LittleEndian>(2.5).unwrap();
+        let path = test_path("nifti", "image.nii");
+        std::fs::write(&path, data).unwrap();
+        let texture = Volume::read_nifti(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(VoxelData::F32(vec![-1.5, 2.5]), texture.texture_data);
+        assert_eq!(0.25, texture.spacing.x);
+    }
+}
@@ -0,0 +1,697 @@
+use super::memory::{FileSource, FileSystem};
+use super::meta_image::expand_file_pattern;
+use super::orientation::Orientation;
+use super::{Dim, ElementType, Spacing, Texture, Volume, VolumeError, VoxelData};
+use byteorder::{LittleEndian, WriteBytesExt};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NrrdEncoding {
+    Raw,
+    Gzip,
+    Ascii,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct NrrdHeader {
+    pub dimensions: Dim,
+    pub element_type: ElementType,
+    pub encoding: NrrdEncoding,
+    pub big_endian: bool,
+    pub spacing: Spacing,
+    pub space: Option<String>,
+    /// One direction vector per axis, scaled by the voxel spacing.
+    pub space_directions: Option<[[f64; 3]; 3]>,
+    pub space_origin: [f64; 3],
+    pub line_skip: usize,
+    /// Bytes to skip before the data, -1 means the raw data sits at the end of the file.
+    pub byte_skip: i64,
+    /// Detached data files relative to the header, empty if the data is attached.
+    pub data_files: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum NrrdError {
+    Io(PathBuf, std::io::Error),
+    InvalidMagic,
+    MissingField(&'static str),
+    InvalidValue(String, String),
+    UnsupportedType(String),
+    UnsupportedEncoding(String),
+    TruncatedData { expected: usize, actual: usize },
+    Decompression(std::io::Error),
+}
+
+impl fmt::Display for NrrdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NrrdError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            NrrdError::InvalidMagic => write!(f, "file does not start with NRRD000X"),
+            NrrdError::MissingField(field) => write!(f, "missing field {}", field),
+            NrrdError::InvalidValue(field, value) => {
+                write!(f, "invalid value '{}' for field {}", value, field)
+            }
+            NrrdError::UnsupportedType(element_type) => {
+                write!(f, "unsupported type {}", element_type)
+            }
+            NrrdError::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported encoding {}", encoding)
+            }
+            NrrdError::TruncatedData { expected, actual } => write!(
+                f,
+                "expected {} bytes of voxel data but found {}",
+                expected, actual
+            ),
+            NrrdError::Decompression(error) => {
+                write!(f, "cannot decompress voxel data: {}", error)
+            }
+        }
+    }
+}
+
+impl NrrdHeader {
+    /// Parses the header up to the blank line that separates it from attached data and returns
+    /// it together with the offset of the attached data.
+    pub fn parse(nrrd_data: &[u8]) -> Result<(NrrdHeader, usize), NrrdError> {
+        let mut lines = HeaderLines {
+            data: nrrd_data,
+            position: 0,
+        };
+        if !lines
+            .next()
+            .is_some_and(|magic| magic.starts_with("NRRD000"))
+        {
+            return Err(NrrdError::InvalidMagic);
+        }
+
+        let mut element_type = None;
+        let mut dimension = None;
+        let mut sizes: Option<Vec<i32>> = None;
+        let mut encoding = None;
+        let mut big_endian = false;
+        let mut spacings: Option<Vec<f64>> = None;
+        let mut space = None;
+        let mut space_directions = None;
+        let mut space_origin = [0.0; 3];
+        let mut line_skip = 0;
+        let mut byte_skip = 0;
+        let mut data_files = Vec::new();
+
+        while let Some(line) = lines.next() {
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with('#') || line.contains(":=") {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                return Err(invalid_value("line", &line));
+            };
+            let value = value.trim();
+
+            match key.trim() {
+                "type" => {
+                    element_type = Some(
+                        parse_element_type(value)
+                            .ok_or(NrrdError::UnsupportedType(value.to_string()))?,
+                    )
+                }
+                "dimension" => dimension = Some(parse_value::<usize>(key, value)?),
+                "sizes" => sizes = Some(parse_values(key, value)?),
+                "encoding" => {
+                    encoding = Some(match value {
+                        "raw" => NrrdEncoding::Raw,
+                        "gzip" | "gz" => NrrdEncoding::Gzip,
+                        "ascii" | "text" | "txt" => NrrdEncoding::Ascii,
+                        _ => return Err(NrrdError::UnsupportedEncoding(value.to_string())),
+                    })
+                }
+                "endian" => big_endian = value == "big",
+                "spacings" => {
+                    spacings = Some(
+                        value
+                            .split_whitespace()
+                            .map(|part| part.parse().unwrap_or(f64::NAN))
+                            .collect(),
+                    )
+                }
+                "space" => space = Some(value.to_string()),
+                "space directions" => space_directions = Some(parse_directions(key, value)?),
+                "space origin" => {
+                    let origin = parse_vector(key, value)?;
+                    for (target, value) in space_origin.iter_mut().zip(origin) {
+                        *target = value;
+                    }
+                }
+                "line skip" | "lineskip" => line_skip = parse_value(key, value)?,
+                "byte skip" | "byteskip" => byte_skip = parse_value(key, value)?,
+                "data file" | "datafile" => {
+                    data_files = if value.starts_with("LIST") {
+                        let mut files = Vec::new();
+                        while let Some(file) = lines.next() {
+                            if !file.is_empty() {
+                                files.push(file);
+                            }
+                        }
+                        files
+                    } else if let Some(files) = expand_file_pattern(value) {
+                        files
+                    } else {
+                        vec![value.to_string()]
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let sizes = sizes.ok_or(NrrdError::MissingField("sizes"))?;
+        let dimension = dimension.ok_or(NrrdError::MissingField("dimension"))?;
+        if !(2..=3).contains(&dimension) || sizes.len() != dimension {
+            return Err(invalid_value("dimension", &dimension.to_string()));
+        }
+        if sizes.iter().any(|&size| size < 1) {
+            return Err(NrrdError::InvalidValue(
+                "sizes".to_string(),
+                format!("{:?}", sizes),
+            ));
+        }
+
+        let spacing = match (&space_directions, &spacings) {
+            (Some(directions), _) => {
+                let length = |axis: &[f64; 3]| axis.iter().map(|v| v * v).sum::<f64>().sqrt();
+                Spacing {
+                    x: length(&directions[0]) as f32,
+                    y: length(&directions[1]) as f32,
+                    z: length(&directions[2]) as f32,
+                }
+            }
+            (None, Some(spacings)) => {
+                let spacing = |axis: usize| {
+                    spacings
+                        .get(axis)
+                        .copied()
+                        .filter(|spacing| spacing.is_finite())
+                        .unwrap_or(1.0) as f32
+                };
+                Spacing {
+                    x: spacing(0),
+                    y: spacing(1),
+                    z: spacing(2),
+                }
+            }
+            (None, None) => Spacing::default(),
+        };
+
+        let header = NrrdHeader {
+            dimensions: Dim {
+                width: sizes[0],
+                height: sizes[1],
+                depth: sizes.get(2).copied().unwrap_or(1),
+            },
+            element_type: element_type.ok_or(NrrdError::MissingField("type"))?,
+            encoding: encoding.ok_or(NrrdError::MissingField("encoding"))?,
+            big_endian,
+            spacing,
+            space,
+            space_directions,
+            space_origin,
+            line_skip,
+            byte_skip,
+            data_files,
+        };
+
+        Ok((header, lines.position))
+    }
+
+    /// Returns the position and axis directions of the image in LPS coordinates. Images in a
+    /// right-anterior-superior or left-anterior-superior space are flipped, and other spaces are
+    /// taken as LPS.
+    pub fn orientation(&self) -> Orientation {
+        let Some(directions) = self.space_directions else {
+            return Orientation {
+                origin: self.space_origin,
+                ..Orientation::default()
+            };
+        };
+        let flip = match self
+            .space
+            .as_deref()
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("right-anterior-superior" | "ras") => [-1.0, -1.0, 1.0],
+            Some("left-anterior-superior" | "las") => [1.0, -1.0, 1.0],
+            _ => [1.0; 3],
+        };
+        let mut affine = [[0.0; 4]; 3];
+        for (row, values) in affine.iter_mut().enumerate() {
+            for axis in 0..3 {
+                values[axis] = directions[axis][row] * flip[row];
+            }
+            values[3] = self.space_origin[row] * flip[row];
+        }
+        Orientation::from_affine(affine).0
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.dimensions.width as usize
+            * self.dimensions.height as usize
+            * self.dimensions.depth as usize
+    }
+
+    /// Decodes the content of a data file, which holds `count` samples.
+    fn decode(&self, data: &[u8], count: usize) -> Result<VoxelData, NrrdError> {
+        let mut data = data;
+        for _ in 0..self.line_skip {
+            let line_end = data.iter().position(|&byte| byte == b'\n');
+            data = line_end.map_or(&[], |end| &data[end + 1..]);
+        }
+
+        let expected = count * self.element_type.size();
+        match self.encoding {
+            NrrdEncoding::Raw => {
+                let skip = if self.byte_skip < 0 {
+                    data.len().saturating_sub(expected)
+                } else {
+                    self.byte_skip as usize
+                };
+                if data.len() < skip + expected {
+                    return Err(NrrdError::TruncatedData {
+                        expected: skip + expected,
+                        actual: data.len(),
+                    });
+                }
+                self.decode_raw(&data[skip..], count)
+            }
+            NrrdEncoding::Gzip => {
+                let mut inflated = Vec::with_capacity(expected);
+                MultiGzDecoder::new(data)
+                    .read_to_end(&mut inflated)
+                    .map_err(NrrdError::Decompression)?;
+                let skip = self.byte_skip.max(0) as usize;
+                if inflated.len() < skip + expected {
+                    return Err(NrrdError::TruncatedData {
+                        expected: skip + expected,
+                        actual: inflated.len(),
+                    });
+                }
+                self.decode_raw(&inflated[skip..], count)
+            }
+            NrrdEncoding::Ascii => {
+                let values: Vec<f32> = String::from_utf8_lossy(data)
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|value| !value.is_empty())
+                    .take(count)
+                    .map(|value| parse_value("data", value))
+                    .collect::<Result<_, _>>()?;
+                if values.len() < count {
+                    return Err(NrrdError::TruncatedData {
+                        expected: count,
+                        actual: values.len(),
+                    });
+                }
+                Ok(match self.element_type {
+                    ElementType::U8 => VoxelData::U8(values.iter().map(|&v| v as u8).collect()),
+                    ElementType::I8 | ElementType::I16 => {
+                        VoxelData::I16(values.iter().map(|&v| v as i16).collect())
+                    }
+                    ElementType::U16 => VoxelData::U16(values.iter().map(|&v| v as u16).collect()),
+                    _ => VoxelData::from_values(values),
+                })
+            }
+        }
+    }
+
+    fn decode_raw(&self, data: &[u8], count: usize) -> Result<VoxelData, NrrdError> {
+        let result = self.element_type.decode(data, self.big_endian, count);
+        // The callers check the length, so reading from the slice cannot fail.
+        Ok(result.expect("Voxel data has the expected length"))
+    }
+}
+
+impl Volume {
+    /// Reads an NRRD file with attached data (.nrrd) or a detached header (.nhdr).
+    pub fn read_nrrd(path: &str) -> Result<Texture, VolumeError> {
+        Ok(Volume::try_read_nrrd(&FileSystem, Path::new(path))?)
+    }
+
+    pub(super) fn try_read_nrrd(
+        source: &dyn FileSource,
+        path: &Path,
+    ) -> Result<Texture, NrrdError> {
+        let nrrd_data = read_file(source, path)?;
+        let (header, data_offset) = NrrdHeader::parse(&nrrd_data)?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+
+        let texture_data = if header.data_files.is_empty() {
+            header.decode(&nrrd_data[data_offset..], header.element_count())?
+        } else {
+            let count = header.element_count() / header.data_files.len();
+            let mut values = Vec::with_capacity(header.element_count());
+            for file in &header.data_files {
+                let data = read_file(source, &directory.join(file))?;
+                values.push(header.decode(&data, count)?);
+            }
+            concat_voxel_data(values)
+        };
+
+        Ok(Texture {
+            value_range: texture_data.value_range(),
+            dimensions: header.dimensions,
+            spacing: header.spacing,
+            texture_data,
+            orientation: header.orientation(),
+        })
+    }
+
+    /// Saves the volume as a single NRRD file with attached data.
+    pub fn write_nrrd(&self, path: &str, encoding: NrrdEncoding) -> Result<(), NrrdError> {
+        let path = Path::new(path);
+        let file = std::fs::File::create(path)
+            .map_err(|error| NrrdError::Io(path.to_path_buf(), error))?;
+        Volume::write_nrrd_to(&self.texture, BufWriter::new(file), encoding)
+            .map_err(|error| NrrdError::Io(path.to_path_buf(), error))
+    }
+
+    pub fn write_nrrd_to<W: Write>(
+        texture: &Texture,
+        mut writer: W,
+        encoding: NrrdEncoding,
+    ) -> std::io::Result<()> {
+        let type_name = match texture.texture_data {
+            VoxelData::U8(_) => "uchar",
+            VoxelData::I16(_) => "short",
+            VoxelData::U16(_) => "ushort",
+            VoxelData::F32(_) => "float",
+        };
+        let encoding_name = match encoding {
+            NrrdEncoding::Raw => "raw",
+            NrrdEncoding::Gzip => "gzip",
+            NrrdEncoding::Ascii => "ascii",
+        };
+        let dimensions = &texture.dimensions;
+        let affine = texture.voxel_to_patient();
+        let vector = |column: usize| {
+            format!(
+                "({},{},{})",
+                affine[0][column], affine[1][column], affine[2][column]
+            )
+        };
+
+        writeln!(writer, "NRRD0004")?;
+        writeln!(writer, "# Complete NRRD file format specification at:")?;
+        writeln!(writer, "# http://teem.sourceforge.net/nrrd/format.html")?;
+        writeln!(writer, "type: {}", type_name)?;
+        writeln!(writer, "dimension: 3")?;
+        writeln!(writer, "space: left-posterior-superior")?;
+        writeln!(
+            writer,
+            "sizes: {} {} {}",
+            dimensions.width, dimensions.height, dimensions.depth
+        )?;
+        writeln!(
+            writer,
+            "space directions: {} {} {}",
+            vector(0),
+            vector(1),
+            vector(2)
+        )?;
+        writeln!(writer, "kinds: domain domain domain")?;
+        writeln!(writer, "endian: little")?;
+        writeln!(writer, "encoding: {}", encoding_name)?;
+        writeln!(writer, "space origin: {}", vector(3))?;
+        writeln!(writer)?;
+
+        match encoding {
+            NrrdEncoding::Raw => write_samples(&texture.texture_data, &mut writer)?,
+            NrrdEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(&mut writer, flate2::Compression::default());
+                write_samples(&texture.texture_data, &mut encoder)?;
+                encoder.finish()?;
+            }
+            NrrdEncoding::Ascii => match &texture.texture_data {
+                VoxelData::U8(data) => write_ascii(data, &mut writer)?,
+                VoxelData::I16(data) => write_ascii(data, &mut writer)?,
+                VoxelData::U16(data) => write_ascii(data, &mut writer)?,
+                VoxelData::F32(data) => write_ascii(data, &mut writer)?,
+            },
+        }
+        writer.flush()
+    }
+}
+
+struct HeaderLines<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl HeaderLines<'_> {
+    fn next(&mut self) -> Option<String> {
+        if self.position >= self.data.len() {
+            return None;
+        }
+        let line_end = self.data[self.position..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(self.data.len(), |end| self.position + end);
+        let line = String::from_utf8_lossy(&self.data[self.position..line_end])
+            .trim_end_matches('\r')
+            .to_string();
+        self.position = (line_end + 1).min(self.data.len());
+        Some(line)
+    }
+}
+
+fn write_samples<W: Write>(texture_data: &VoxelData, writer: &mut W) -> std::io::Result<()> {
+    match texture_data {
+        VoxelData::U8(data) => writer.write_all(data),
+        VoxelData::I16(data) => data
+            .iter()
+            .try_for_each(|&value| writer.write_i16::<LittleEndian>(value)),
+        VoxelData::U16(data) => data
+            .iter()
+            .try_for_each(|&value| writer.write_u16::<LittleEndian>(value)),
+        VoxelData::F32(data) => data
+            .iter()
+            .try_for_each(|&value| writer.write_f32::<LittleEndian>(value)),
+    }
+}
+
+fn write_ascii<T: fmt::Display, W: Write>(data: &[T], writer: &mut W) -> std::io::Result<()> {
+    data.iter()
+        .try_for_each(|value| writeln!(writer, "{}", value))
+}
+
+fn concat_voxel_data(parts: Vec<VoxelData>) -> VoxelData {
+    let mut parts = parts.into_iter();
+    let Some(mut result) = parts.next() else {
+        return VoxelData::U8(Vec::new());
+    };
+    for part in parts {
+        match (&mut result, part) {
+            (VoxelData::U8(data), VoxelData::U8(part)) => data.extend(part),
+            (VoxelData::I16(data), VoxelData::I16(part)) => data.extend(part),
+            (VoxelData::U16(data), VoxelData::U16(part)) => data.extend(part),
+            (VoxelData::F32(data), VoxelData::F32(part)) => data.extend(part),
+            // Integer types wider than 16 bit may decode to i16 or f32 depending on the values.
+            (_, part) => {
+                let mut values = result.to_values();
+                values.extend(part.to_values());
+                result = VoxelData::F32(values);
+            }
+        }
+    }
+    result
+}
+
+fn parse_element_type(value: &str) -> Option<ElementType> {
+    match value {
+        "uchar" | "unsigned char" | "uint8" | "uint8_t" => Some(ElementType::U8),
+        "signed char" | "int8" | "int8_t" => Some(ElementType::I8),
+        "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => {
+            Some(ElementType::I16)
+        }
+        "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+            Some(ElementType::U16)
+        }
+        "int" | "signed int" | "int32" | "int32_t" => Some(ElementType::I32),
+        "uint" | "unsigned int" | "uint32" | "uint32_t" => Some(ElementType::U32),
+        "float" => Some(ElementType::F32),
+        "double" => Some(ElementType::F64),
+        _ => None,
+    }
+}
+
+/// Parses a vector in the form `(x,y,z)`.
+fn parse_vector(key: &str, value: &str) -> Result<Vec<f64>, NrrdError> {
+    value
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(|part| parse_value(key, part.trim()))
+        .collect()
+}
+
+/// Parses `space directions`, skipping `none` entries of non-spatial axes.
+fn parse_directions(key: &str, value: &str) -> Result<[[f64; 3]; 3], NrrdError> {
+    let vectors: Vec<Vec<f64>> = value
+        .split_whitespace()
+        .filter(|part| *part != "none")
+        .map(|part| parse_vector(key, part))
+        .collect::<Result<_, _>>()?;
+    let mut directions = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    if vectors.len() > 3 || vectors.iter().any(|vector| vector.len() > 3) {
+        return Err(invalid_value(key, value));
+    }
+    for (axis, vector) in vectors.iter().enumerate() {
+        directions[axis] = [0.0; 3];
+        directions[axis][..vector.len()].copy_from_slice(vector);
+    }
+    Ok(directions)
+}
+
+fn read_file<'a>(source: &'a dyn FileSource, path: &Path) -> Result<Cow<'a, [u8]>, NrrdError> {
+    source
+        .read(path)
+        .map_err(|error| NrrdError::Io(path.to_path_buf(), error))
+}
+
+fn invalid_value(key: &str, value: &str) -> NrrdError {
+    NrrdError::InvalidValue(key.to_string(), value.to_string())
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, NrrdError> {
+    value.parse().map_err(|_| invalid_value(key, value))
+}
+
+fn parse_values<T: std::str::FromStr>(key: &str, value: &str) -> Result<Vec<T>, NrrdError> {
+    value
+        .split_whitespace()
+        .map(|part| parse_value(key, part))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::{test_path, test_texture};
+    use crate::volume::ValueRange;
+
+    /// A 4 x 3 x 2 CT-like volume, flipped along x and z.
+    fn ct_texture() -> Texture {
+        let spacing = Spacing {
+            x: 0.5,
+            y: 0.5,
+            z: 2.5,
+        };
+        let mut texture = test_texture([4, 3, 2], spacing, |index| index as i16 * 100 - 1024);
+        texture.orientation = Orientation {
+            directions: [[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]],
+            origin: [120.0, -120.0, 70.5],
+        };
+        texture
+    }
+
+    #[test]
+    fn test_parse_slicer_header() {
+        let input = b"NRRD0004
+# Complete NRRD file format specification at:
+# http://teem.sourceforge.net/nrrd/format.html
+type: short
+dimension: 3
+space: left-posterior-superior
+sizes: 256 256 94
+space directions: (-0.9375,0,0) (0,0.9375,0) (0,0,-1.5)
+kinds: domain domain domain
+endian: big
+encoding: gzip
+space origin: (120,-120,70.5)
+data file: FullHead.raw.gz
+";
+        let (header, _) = NrrdHeader::parse(input).unwrap();
+
+        assert_eq!(ElementType::I16, header.element_type);
+        assert_eq!(NrrdEncoding::Gzip, header.encoding);
+        assert!(header.big_endian);
+        assert_eq!(
+            Spacing {
+                x: 0.9375,
+                y: 0.9375,
+                z: 1.5
+            },
+            header.spacing
+        );
+        assert_eq!([120.0, -120.0, 70.5], header.space_origin);
+        assert_eq!(
+            [[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]],
+            header.orientation().directions
+        );
+        assert_eq!(vec!["FullHead.raw.gz".to_string()], header.data_files);
+    }
+
+    #[test]
+    fn test_parse_invalid_magic() {
+        let result = NrrdHeader::parse(b"ObjectType = Image\n");
+
+        assert!(matches!(result, Err(NrrdError::InvalidMagic)));
+    }
+
+    #[test]
+    fn test_parse_invalid_sizes() {
+        let input = b"NRRD0004\ntype: uchar\ndimension: 3\nsizes: 2 -1 2\nencoding: raw\n\n";
+        let result = NrrdHeader::parse(input);
+
+        assert!(matches!(
+            result,
+            Err(NrrdError::InvalidValue(field, _)) if field == "sizes"
+        ));
+    }
+
+    #[test]
+    fn test_nrrd_round_trip() {
+        for (name, encoding) in [
+            ("raw.nrrd", NrrdEncoding::Raw),
+            ("gzip.nrrd", NrrdEncoding::Gzip),
+            ("ascii.nrrd", NrrdEncoding::Ascii),
+        ] {
+            let path = test_path("nrrd", name);
+            let volume = Volume::from_texture(ct_texture());
+            volume.write_nrrd(path.to_str().unwrap(), encoding).unwrap();
+            let texture = Volume::read_nrrd(path.to_str().unwrap()).unwrap();
+
+            assert_eq!(volume.texture.dimensions, texture.dimensions);
+            assert_eq!(volume.texture.spacing, texture.spacing);
+            assert_eq!(volume.texture.orientation, texture.orientation);
+            assert_eq!(volume.texture.texture_data, texture.texture_data);
+        }
+    }
+
+    #[test]
+    fn test_read_detached_big_endian() {
+        std::fs::write(test_path("nrrd", "detached.raw"), [0x00, 0x01, 0x00, 0x02]).unwrap();
+        let path = test_path("nrrd", "detached.nhdr");
+        std::fs::write(
+            &path,
+            b"NRRD0004\ntype: ushort\ndimension: 3\nsizes: 2 1 1\nspacings: 0.5 0.5 NaN\nendian: big\nencoding: raw\ndata file: detached.raw\n",
+        )
+        .unwrap();
+        let texture = Volume::read_nrrd(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(VoxelData::U16(vec![1, 2]), texture.texture_data);
+        assert_eq!(ValueRange { min: 1.0, max: 2.0 }, texture.value_range);
+        assert_eq!(
+            Spacing {
+                x: 0.5,
+                y: 0.5,
+                z: 1.0
+            },
+            texture.spacing
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use super::{Spacing, Texture};
+
+/// Directions of the patient axes in LPS coordinates, as used by DICOM and ITK: x points to the
+/// patient's left, y to posterior and z to superior. NIfTI and Slicer use RAS, which flips x and y.
+pub const PATIENT_DIRECTIONS: [(&str, [f64; 3]); 6] = [
+    ("L", [1.0, 0.0, 0.0]),
+    ("R", [-1.0, 0.0, 0.0]),
+    ("P", [0.0, 1.0, 0.0]),
+    ("A", [0.0, -1.0, 0.0]),
+    ("S", [0.0, 0.0, 1.0]),
+    ("I", [0.0, 0.0, -1.0]),
+];
+
+/// Position and axis directions of a volume in LPS patient coordinates. Together with the spacing
+/// of the volume they give the voxel-to-patient affine.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Orientation {
+    /// Unit direction of the x, y and z voxel axes.
+    pub directions: [[f64; 3]; 3],
+    /// Position of the centre of the first voxel in millimetres.
+    pub origin: [f64; 3],
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation {
+            directions: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            origin: [0.0; 3],
+        }
+    }
+}
+
+impl Orientation {
+    /// Creates the orientation from axis directions and an origin in RAS coordinates.
+    pub fn from_ras(directions: [[f64; 3]; 3], origin: [f64; 3]) -> Self {
+        Orientation {
+            directions: directions.map(ras_to_lps),
+            origin: ras_to_lps(origin),
+        }
+    }
+
+    /// Splits a voxel-to-patient affine in LPS coordinates, whose columns are the axis directions
+    /// scaled by the spacing followed by the origin, into the orientation and the spacing.
+    pub fn from_affine(affine: [[f64; 4]; 3]) -> (Self, Spacing) {
+        let column = |axis: usize| [affine[0][axis], affine[1][axis], affine[2][axis]];
+        let lengths = [0, 1, 2].map(|axis| length(column(axis)));
+        let directions = [0, 1, 2].map(|axis| {
+            if lengths[axis] > 0.0 {
+                column(axis).map(|value| value / lengths[axis])
+            } else {
+                Orientation::default().directions[axis]
+            }
+        });
+        let spacing = lengths.map(|length| if length > 0.0 { length } else { 1.0 });
+        (
+            Orientation {
+                directions,
+                origin: column(3),
+            },
+            Spacing {
+                x: spacing[0] as f32,
+                y: spacing[1] as f32,
+                z: spacing[2] as f32,
+            },
+        )
+    }
+
+    /// Reads the axis directions from a MetaImage AnatomicalOrientation such as `RAI`. Each letter
+    /// names the side the axis starts from, so `RAI` is the identity in LPS coordinates.
+    pub fn from_anatomical_code(code: &str) -> Option<[[f64; 3]; 3]> {
+        let letters: Vec<char> = code.trim().to_ascii_uppercase().chars().collect();
+        if letters.len() != 3 {
+            return None;
+        }
+        let mut directions = [[0.0; 3]; 3];
+        for (direction, letter) in directions.iter_mut().zip(letters) {
+            *direction = match letter {
+                'R' => [1.0, 0.0, 0.0],
+                'L' => [-1.0, 0.0, 0.0],
+                'A' => [0.0, 1.0, 0.0],
+                'P' => [0.0, -1.0, 0.0],
+                'I' => [0.0, 0.0, 1.0],
+                'S' => [0.0, 0.0, -1.0],
+                _ => return None,
+            };
+        }
+        // Each patient axis has to be used once.
+        let used = |axis: usize| directions.iter().any(|direction| direction[axis] != 0.0);
+        (0..3).all(used).then_some(directions)
+    }
+
+    /// Returns the voxel-to-patient affine in LPS coordinates, which maps a voxel index to its
+    /// position in millimetres.
+    pub fn voxel_to_patient(&self, spacing: Spacing) -> [[f64; 4]; 3] {
+        let spacing = [spacing.x as f64, spacing.y as f64, spacing.z as f64];
+        let mut affine = [[0.0; 4]; 3];
+        for (row, values) in affine.iter_mut().enumerate() {
+            for axis in 0..3 {
+                values[axis] = self.directions[axis][row] * spacing[axis];
+            }
+            values[3] = self.origin[row];
+        }
+        affine
+    }
+}
+
+impl Texture {
+    /// Returns the voxel-to-patient affine in LPS coordinates.
+    pub fn voxel_to_patient(&self) -> [[f64; 4]; 3] {
+        self.orientation.voxel_to_patient(self.spacing)
+    }
+
+    /// Returns the voxel-to-patient affine in RAS coordinates, as stored in NIfTI files.
+    pub fn voxel_to_ras(&self) -> [[f64; 4]; 3] {
+        let mut affine = self.voxel_to_patient();
+        affine[0] = affine[0].map(|value| -value);
+        affine[1] = affine[1].map(|value| -value);
+        affine
+    }
+}
+
+/// Converts a direction or position between RAS and LPS coordinates, which works both ways.
+pub fn ras_to_lps(vector: [f64; 3]) -> [f64; 3] {
+    [-vector[0], -vector[1], vector[2]]
+}
+
+fn length(vector: [f64; 3]) -> f64 {
+    vector.iter().map(|value| value * value).sum::<f64>().sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_affine_round_trip() {
+        let affine = [
+            [-0.5, 0.0, 0.0, 120.0],
+            [0.0, 0.0, 2.0, -80.0],
+            [0.0, -0.5, 0.0, 40.0],
+        ];
+        let (orientation, spacing) = Orientation::from_affine(affine);
+
+        assert_eq!(
+            [[-1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+            orientation.directions
+        );
+        assert_eq!([120.0, -80.0, 40.0], orientation.origin);
+        assert_eq!(2.0, spacing.z);
+        assert_eq!(affine, orientation.voxel_to_patient(spacing));
+    }
+
+    #[test]
+    fn test_from_ras() {
+        let orientation = Orientation::from_ras(
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            [10.0, 20.0, 30.0],
+        );
+
+        assert_eq!(
+            [[-1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]],
+            orientation.directions
+        );
+        assert_eq!([-10.0, -20.0, 30.0], orientation.origin);
+    }
+
+    #[test]
+    fn test_anatomical_code() {
+        assert_eq!(
+            Some(Orientation::default().directions),
+            Orientation::from_anatomical_code("RAI")
+        );
+        assert_eq!(
+            Some([[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]]),
+            Orientation::from_anatomical_code("las")
+        );
+        assert_eq!(None, Orientation::from_anatomical_code("RLI"));
+        assert_eq!(None, Orientation::from_anatomical_code("RA"));
+    }
+}
@@ -0,0 +1,324 @@
+use super::orientation::Orientation;
+use super::{Dim, ElementType, Spacing, Texture, Volume, VolumeError, VoxelData};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use std::fs::File;
+use std::path::Path;
+
+/// Number of voxels above which volumes are downsampled before they are uploaded to the GPU. A
+/// 512 x 512 x 1024 volume of 16-bit samples takes 512 MB of texture memory.
+pub const MAX_TEXTURE_VOXELS: usize = 512 * 512 * 1024;
+
+/// How a block of voxels is reduced to one voxel when a volume is downsampled.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Downsampling {
+    /// Takes the first voxel of each block, which is fastest but aliases fine structures.
+    Stride,
+    /// Averages the voxels of each block.
+    #[default]
+    Box,
+}
+
+/// Layout of uncompressed voxel data in a raw file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RawLayout {
+    pub dimensions: Dim,
+    pub spacing: Spacing,
+    pub element_type: ElementType,
+    pub big_endian: bool,
+    /// Number of bytes before the voxel data.
+    pub offset: usize,
+}
+
+impl RawLayout {
+    pub fn data_size(&self) -> usize {
+        self.dimensions.width as usize
+            * self.dimensions.height as usize
+            * self.dimensions.depth as usize
+            * self.element_type.size()
+    }
+}
+
+/// Returns the downsampling factor per axis so that the volume has at most `max_voxels` voxels and
+/// at most `max_size` voxels along each axis, e.g. the GL_MAX_3D_TEXTURE_SIZE of the GPU. The axis
+/// with the most remaining voxels is reduced first.
+pub fn downsampling_factors(dimensions: Dim, max_voxels: usize, max_size: usize) -> [usize; 3] {
+    let sizes = [
+        dimensions.width.max(1) as usize,
+        dimensions.height.max(1) as usize,
+        dimensions.depth.max(1) as usize,
+    ];
+    let mut factors = sizes.map(|size| size.div_ceil(max_size.max(1)));
+    let reduced = |factors: &[usize; 3], axis: usize| sizes[axis].div_ceil(factors[axis]);
+    while (0..3)
+        .map(|axis| reduced(&factors, axis))
+        .product::<usize>()
+        > max_voxels.max(1)
+    {
+        let Some(axis) = (0..3)
+            .filter(|&axis| reduced(&factors, axis) > 1)
+            .max_by_key(|&axis| reduced(&factors, axis))
+        else {
+            break;
+        };
+        factors[axis] += 1;
+    }
+    factors
+}
+
+/// Reduces the voxel data in `data`, read from `path` and laid out as described by `layout`, by the
+/// given factor per axis. Only the voxels that are sampled are read, so `data` can be a
+/// memory-mapped file that does not fit into memory.
+pub fn downsample(
+    path: &Path,
+    data: &[u8],
+    layout: &RawLayout,
+    factors: [usize; 3],
+    method: Downsampling,
+) -> Result<Texture, VolumeError> {
+    let available = data.len().saturating_sub(layout.offset);
+    if available < layout.data_size() {
+        return Err(VolumeError::MalformedHeader(
+            path.to_path_buf(),
+            format!(
+                "expected {} bytes of voxel data but found {}",
+                layout.data_size(),
+                available
+            ),
+        ));
+    }
+    let data = &data[layout.offset..layout.offset + layout.data_size()];
+    let values = if layout.big_endian {
+        downsample_values::<BigEndian>(data, layout, factors, method)
+    } else {
+        downsample_values::<LittleEndian>(data, layout, factors, method)
+    };
+
+    let dimensions = reduced_dimensions(layout.dimensions, factors);
+    let texture_data = voxel_data(values, layout.element_type);
+    Ok(Texture {
+        value_range: texture_data.value_range(),
+        dimensions,
+        spacing: Spacing {
+            x: layout.spacing.x * factors[0] as f32,
+            y: layout.spacing.y * factors[1] as f32,
+            z: layout.spacing.z * factors[2] as f32,
+        },
+        texture_data,
+        orientation: Orientation::default(),
+    })
+}
+
+impl Volume {
+    /// Reads a raw voxel file by memory-mapping it and downsampling it by the given factor per
+    /// axis, so that files larger than the available memory can be read.
+    pub fn read_raw(
+        path: &Path,
+        layout: &RawLayout,
+        factors: [usize; 3],
+        method: Downsampling,
+    ) -> Result<Texture, VolumeError> {
+        let map = map_file(path)?;
+        downsample(path, &map, layout, factors, method)
+    }
+}
+
+/// Memory-maps a file for reading.
+pub(super) fn map_file(path: &Path) -> Result<Mmap, VolumeError> {
+    let io_error = |error| VolumeError::Io(path.to_path_buf(), error);
+    let file = File::open(path).map_err(io_error)?;
+    // The file is only read. If another process truncates it while it is mapped, reading it
+    // fails, as it would with any other file reader.
+    unsafe { Mmap::map(&file) }.map_err(io_error)
+}
+
+fn reduced_dimensions(dimensions: Dim, factors: [usize; 3]) -> Dim {
+    Dim {
+        width: (dimensions.width as usize).div_ceil(factors[0]) as i32,
+        height: (dimensions.height as usize).div_ceil(factors[1]) as i32,
+        depth: (dimensions.depth as usize).div_ceil(factors[2]) as i32,
+    }
+}
+
+fn downsample_values<B: ByteOrder>(
+    data: &[u8],
+    layout: &RawLayout,
+    factors: [usize; 3],
+    method: Downsampling,
+) -> Vec<f32> {
+    let width = layout.dimensions.width as usize;
+    let height = layout.dimensions.height as usize;
+    let depth = layout.dimensions.depth as usize;
+    let reduced = reduced_dimensions(layout.dimensions, factors);
+    let slice_size = reduced.width as usize * reduced.height as usize;
+    let element_type = layout.element_type;
+    let sample = |x: usize, y: usize, z: usize| {
+        read_sample::<B>(data, element_type, (z * height + y) * width + x)
+    };
+
+    let mut values = vec![0.0; slice_size * reduced.depth as usize];
+    // Each output slice only reads its own block of input slices, so the pages of a memory-mapped
+    // file are touched in order.
+    values
+        .par_chunks_mut(slice_size.max(1))
+        .enumerate()
+        .for_each(|(z, slice)| {
+            let z_start = z * factors[2];
+            let z_end = (z_start + factors[2]).min(depth);
+            for (index, value) in slice.iter_mut().enumerate() {
+                let x_start = index % reduced.width as usize * factors[0];
+                let y_start = index / reduced.width as usize * factors[1];
+                *value = match method {
+                    Downsampling::Stride => sample(x_start, y_start, z_start),
+                    Downsampling::Box => {
+                        let x_end = (x_start + factors[0]).min(width);
+                        let y_end = (y_start + factors[1]).min(height);
+                        let mut sum = 0.0;
+                        for z in z_start..z_end {
+                            for y in y_start..y_end {
+                                for x in x_start..x_end {
+                                    sum += sample(x, y, z) as f64;
+                                }
+                            }
+                        }
+                        let count = (x_end - x_start) * (y_end - y_start) * (z_end - z_start);
+                        (sum / count as f64) as f32
+                    }
+                };
+            }
+        });
+    values
+}
+
+fn read_sample<B: ByteOrder>(data: &[u8], element_type: ElementType, index: usize) -> f32 {
+    let offset = index * element_type.size();
+    let bytes = &data[offset..offset + element_type.size()];
+    match element_type {
+        ElementType::U8 => bytes[0] as f32,
+        ElementType::I8 => bytes[0] as i8 as f32,
+        ElementType::I16 => B::read_i16(bytes) as f32,
+        ElementType::U16 => B::read_u16(bytes) as f32,
+        ElementType::I32 => B::read_i32(bytes) as f32,
+        ElementType::U32 => B::read_u32(bytes) as f32,
+        ElementType::F32 => B::read_f32(bytes),
+        ElementType::F64 => B::read_f64(bytes) as f32,
+    }
+}
+
+/// Stores downsampled values in the voxel type that [ElementType::decode] uses for the element
+/// type, rounding averages of integer samples.
+fn voxel_data(values: Vec<f32>, element_type: ElementType) -> VoxelData {
+    match element_type {
+        ElementType::U8 => {
+            VoxelData::U8(values.par_iter().map(|value| value.round() as u8).collect())
+        }
+        ElementType::I8 | ElementType::I16 => VoxelData::I16(
+            values
+                .par_iter()
+                .map(|value| value.round() as i16)
+                .collect(),
+        ),
+        ElementType::U16 => VoxelData::U16(
+            values
+                .par_iter()
+                .map(|value| value.round() as u16)
+                .collect(),
+        ),
+        ElementType::I32 | ElementType::U32 | ElementType::F32 | ElementType::F64 => {
+            VoxelData::from_values(values)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::test_path;
+
+    fn layout(width: i32, height: i32, depth: i32, element_type: ElementType) -> RawLayout {
+        RawLayout {
+            dimensions: Dim {
+                width,
+                height,
+                depth,
+            },
+            spacing: Spacing {
+                x: 0.5,
+                y: 0.5,
+                z: 2.0,
+            },
+            element_type,
+            big_endian: false,
+            offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_downsampling_factors() {
+        let dimensions = Dim {
+            width: 2048,
+            height: 2048,
+            depth: 100,
+        };
+        assert_eq!(
+            [1, 1, 1],
+            downsampling_factors(dimensions, usize::MAX, usize::MAX)
+        );
+        let factors = downsampling_factors(dimensions, 512 * 512 * 100, usize::MAX);
+        assert_eq!([4, 4, 1], factors);
+
+        let long = Dim {
+            width: 4100,
+            height: 64,
+            depth: 64,
+        };
+        assert_eq!([3, 1, 1], downsampling_factors(long, usize::MAX, 2048));
+        assert_eq!([1, 1, 1], downsampling_factors(long, usize::MAX, 8192));
+    }
+
+    #[test]
+    fn test_downsample() {
+        // 3 x 2 x 2 voxels, reduced by 2 along x and z.
+        let path = Path::new("test.raw");
+        let data: Vec<u8> = (0..12).collect();
+        let layout = layout(3, 2, 2, ElementType::U8);
+
+        let stride = downsample(path, &data, &layout, [2, 1, 2], Downsampling::Stride).unwrap();
+        assert_eq!(VoxelData::U8(vec![0, 2, 3, 5]), stride.texture_data);
+        assert_eq!(
+            Dim {
+                width: 2,
+                height: 2,
+                depth: 1
+            },
+            stride.dimensions
+        );
+        assert_eq!(1.0, stride.spacing.x);
+        assert_eq!(4.0, stride.spacing.z);
+
+        // The last column has no neighbour along x, so it only averages along z.
+        let average = downsample(path, &data, &layout, [2, 1, 2], Downsampling::Box).unwrap();
+        assert_eq!(VoxelData::U8(vec![4, 5, 7, 8]), average.texture_data);
+    }
+
+    #[test]
+    fn test_read_raw_big_endian() {
+        let path = test_path("raw", "big_endian.raw");
+        let values: [i16; 8] = [-100, 100, 0, 0, 200, 200, 0, 0];
+        let mut data = vec![0xff; 4];
+        data.extend(values.iter().flat_map(|value| value.to_be_bytes()));
+        std::fs::write(&path, data).unwrap();
+        let mut layout = layout(2, 2, 2, ElementType::I16);
+        layout.big_endian = true;
+        layout.offset = 4;
+        let texture = Volume::read_raw(&path, &layout, [2, 2, 2], Downsampling::Box).unwrap();
+
+        assert_eq!(VoxelData::I16(vec![50]), texture.texture_data);
+        layout.offset = 8;
+        assert!(matches!(
+            Volume::read_raw(&path, &layout, [1, 1, 1], Downsampling::Box),
+            Err(VolumeError::MalformedHeader(..))
+        ));
+    }
+}
@@ -0,0 +1,361 @@
+use super::loading::LoadProgress;
+use super::raw::MAX_TEXTURE_VOXELS;
+use super::{Dim, Spacing, Texture, Volume, VolumeError, VoxelData};
+use rayon::prelude::*;
+
+/// How voxel values between the sample positions of a volume are interpolated.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Interpolation {
+    #[default]
+    Trilinear,
+    /// Catmull-Rom interpolation over 4 x 4 x 4 voxels, which keeps edges sharper than trilinear
+    /// interpolation. The values are clamped to the value range of the volume, as the spline
+    /// overshoots at edges.
+    Cubic,
+}
+
+/// Returns the spacing of the smallest axis on all axes.
+pub fn isotropic_spacing(spacing: Spacing) -> Spacing {
+    let size = spacing.x.min(spacing.y).min(spacing.z);
+    Spacing {
+        x: size,
+        y: size,
+        z: size,
+    }
+}
+
+/// Resamples a volume to the given spacing. The first and last voxels stay at the same position,
+/// so the extent of the volume is kept up to a fraction of the new spacing. The samples keep
+/// their type, and integer samples are rounded. A spacing that would give a volume with more than
+/// [MAX_TEXTURE_VOXELS] voxels is scaled up by [fitting_spacing]. The size along each axis is only
+/// limited by the GPU, so a volume that is uploaded needs its spacing fitted to the GPU first.
+pub fn resample(texture: &Texture, spacing: Spacing, interpolation: Interpolation) -> Texture {
+    resample_with_progress(texture, spacing, interpolation, &LoadProgress::default())
+        .expect("resampling without a load to cancel")
+}
+
+/// Like [resample], but stops with [VolumeError::Cancelled] once the load is cancelled, which is
+/// checked before every slice.
+pub fn resample_with_progress(
+    texture: &Texture,
+    spacing: Spacing,
+    interpolation: Interpolation,
+    progress: &LoadProgress,
+) -> Result<Texture, VolumeError> {
+    let spacing = fitting_spacing(texture, spacing, usize::MAX);
+    let source = Grid {
+        values: texture.texture_data.to_values(),
+        dimensions: texture.dimensions,
+    };
+    let dimensions = resampled_dimensions(texture, spacing);
+    // Position of an output voxel in voxel coordinates of the input.
+    let scale = [
+        spacing.x / texture.spacing.x,
+        spacing.y / texture.spacing.y,
+        spacing.z / texture.spacing.z,
+    ];
+    let width = dimensions.width as usize;
+    let slice_size = width * dimensions.height as usize;
+    let value_range = texture.value_range;
+
+    let mut values = vec![0.0; slice_size * dimensions.depth as usize];
+    values
+        .par_chunks_mut(slice_size.max(1))
+        .enumerate()
+        .try_for_each(|(z, slice)| -> Result<(), VolumeError> {
+            progress.check_cancelled()?;
+            for (index, value) in slice.iter_mut().enumerate() {
+                let position = [
+                    (index % width) as f32 * scale[0],
+                    (index / width) as f32 * scale[1],
+                    z as f32 * scale[2],
+                ];
+                *value = match interpolation {
+                    Interpolation::Trilinear => source.trilinear(position),
+                    Interpolation::Cubic => source
+                        .cubic(position)
+                        .clamp(value_range.min, value_range.max),
+                };
+            }
+            Ok(())
+        })?;
+
+    let texture_data = same_type(values, &texture.texture_data);
+    Ok(Texture {
+        value_range: texture_data.value_range(),
+        dimensions,
+        spacing,
+        texture_data,
+        orientation: texture.orientation,
+    })
+}
+
+/// Scales up the spacing, keeping its proportions, until the resampled volume has at most
+/// [MAX_TEXTURE_VOXELS] voxels and at most `max_size` voxels along each axis.
+pub fn fitting_spacing(texture: &Texture, spacing: Spacing, max_size: usize) -> Spacing {
+    let mut spacing = spacing;
+    // The first voxel stays in place, so the sizes shrink a little slower than the spacing grows
+    // and a few more steps may be needed.
+    for _ in 0..32 {
+        let dimensions = resampled_dimensions(texture, spacing);
+        let sizes = [dimensions.width, dimensions.height, dimensions.depth].map(|size| size as f64);
+        let voxel_factor = (sizes.iter().product::<f64>() / MAX_TEXTURE_VOXELS as f64).cbrt();
+        let size_factor = sizes.iter().fold(0.0_f64, |max, &size| max.max(size)) / max_size as f64;
+        let factor = voxel_factor.max(size_factor);
+        if factor <= 1.0 {
+            break;
+        }
+        let factor = factor.max(1.01) as f32;
+        spacing = Spacing {
+            x: spacing.x * factor,
+            y: spacing.y * factor,
+            z: spacing.z * factor,
+        };
+    }
+    spacing
+}
+
+fn resampled_dimensions(texture: &Texture, spacing: Spacing) -> Dim {
+    Dim {
+        width: resampled_size(texture.dimensions.width, texture.spacing.x, spacing.x),
+        height: resampled_size(texture.dimensions.height, texture.spacing.y, spacing.y),
+        depth: resampled_size(texture.dimensions.depth, texture.spacing.z, spacing.z),
+    }
+}
+
+impl Volume {
+    /// Resamples the volume to the given spacing, e.g. to render or export it with another
+    /// resolution.
+    pub fn resample(&self, spacing: Spacing, interpolation: Interpolation) -> Volume {
+        Volume::from_texture(resample(&self.texture, spacing, interpolation))
+    }
+
+    /// Resamples the volume to cubic voxels with the smallest spacing of its axes.
+    pub fn resample_isotropic(&self, interpolation: Interpolation) -> Volume {
+        self.resample(isotropic_spacing(self.texture.spacing), interpolation)
+    }
+}
+
+fn resampled_size(size: i32, spacing: f32, new_spacing: f32) -> i32 {
+    if size <= 1 || spacing <= 0.0 || new_spacing <= 0.0 {
+        return size;
+    }
+    ((size - 1) as f32 * spacing / new_spacing).round() as i32 + 1
+}
+
+struct Grid {
+    values: Vec<f32>,
+    dimensions: Dim,
+}
+
+impl Grid {
+    /// Returns the value at integer voxel coordinates, clamped to the volume.
+    fn value(&self, x: i64, y: i64, z: i64) -> f32 {
+        let clamp = |value: i64, size: i32| value.clamp(0, size as i64 - 1) as usize;
+        let x = clamp(x, self.dimensions.width);
+        let y = clamp(y, self.dimensions.height);
+        let z = clamp(z, self.dimensions.depth);
+        let width = self.dimensions.width as usize;
+        self.values[(z * self.dimensions.height as usize + y) * width + x]
+    }
+
+    fn trilinear(&self, position: [f32; 3]) -> f32 {
+        let base = position.map(|value| value.floor());
+        let [x, y, z] = base.map(|value| value as i64);
+        let [fx, fy, fz] = [0, 1, 2].map(|axis| position[axis] - base[axis]);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let plane = |z: i64| {
+            lerp(
+                lerp(self.value(x, y, z), self.value(x + 1, y, z), fx),
+                lerp(self.value(x, y + 1, z), self.value(x + 1, y + 1, z), fx),
+                fy,
+            )
+        };
+        lerp(plane(z), plane(z + 1), fz)
+    }
+
+    fn cubic(&self, position: [f32; 3]) -> f32 {
+        let base = position.map(|value| value.floor());
+        let [x, y, z] = base.map(|value| value as i64);
+        let [wx, wy, wz] = [0, 1, 2].map(|axis| catmull_rom_weights(position[axis] - base[axis]));
+        let mut sum = 0.0;
+        for (k, weight_z) in wz.iter().enumerate() {
+            for (j, weight_y) in wy.iter().enumerate() {
+                for (i, weight_x) in wx.iter().enumerate() {
+                    let value = self.value(x + i as i64 - 1, y + j as i64 - 1, z + k as i64 - 1);
+                    sum += weight_x * weight_y * weight_z * value;
+                }
+            }
+        }
+        sum
+    }
+}
+
+/// Weights of the four neighbouring samples at offset `t` in [0, 1) from the second one.
+fn catmull_rom_weights(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
+/// Stores the values in the type of `like`, rounding values for integer types.
+fn same_type(values: Vec<f32>, like: &VoxelData) -> VoxelData {
+    match like {
+        VoxelData::U8(_) => {
+            VoxelData::U8(values.par_iter().map(|value| value.round() as u8).collect())
+        }
+        VoxelData::I16(_) => VoxelData::I16(
+            values
+                .par_iter()
+                .map(|value| value.round() as i16)
+                .collect(),
+        ),
+        VoxelData::U16(_) => VoxelData::U16(
+            values
+                .par_iter()
+                .map(|value| value.round() as u16)
+                .collect(),
+        ),
+        VoxelData::F32(_) => VoxelData::F32(values),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::volume::test_files::test_texture;
+    use crate::volume::ValueRange;
+
+    /// A 2 x 1 x 3 volume whose values grow by 10 along x and 100 along z.
+    fn ramp_texture() -> Texture {
+        let spacing = Spacing {
+            x: 0.5,
+            y: 0.5,
+            z: 1.0,
+        };
+        test_texture([2, 1, 3], spacing, |index| {
+            (index % 2) as f32 * 10.0 + (index / 2) as f32 * 100.0
+        })
+    }
+
+    #[test]
+    fn test_isotropic_trilinear() {
+        let texture = resample(
+            &ramp_texture(),
+            isotropic_spacing(ramp_texture().spacing),
+            Interpolation::Trilinear,
+        );
+
+        assert_eq!(
+            Dim {
+                width: 2,
+                height: 1,
+                depth: 5
+            },
+            texture.dimensions
+        );
+        assert_eq!(0.5, texture.spacing.z);
+        assert_eq!(
+            VoxelData::F32(vec![
+                0.0, 10.0, 50.0, 60.0, 100.0, 110.0, 150.0, 160.0, 200.0, 210.0
+            ]),
+            texture.texture_data
+        );
+    }
+
+    #[test]
+    fn test_cubic_interpolation() {
+        // A ramp along z with a step at its end, which makes the spline overshoot.
+        let values = [0.0, 100.0, 200.0, 300.0, 400.0, 400.0, 1000.0];
+        let spacing = Spacing {
+            x: 1.0,
+            y: 1.0,
+            z: 2.0,
+        };
+        let texture = test_texture([1, 1, 7], spacing, |index| values[index]);
+        let texture = resample(&texture, Spacing::default(), Interpolation::Cubic);
+        let VoxelData::F32(values) = &texture.texture_data else {
+            panic!("expected f32 samples");
+        };
+
+        assert_eq!(13, texture.dimensions.depth);
+        // Catmull-Rom reproduces linear ramps away from the border.
+        assert!((values[3] - 150.0).abs() < 1e-3);
+        assert!((values[5] - 250.0).abs() < 1e-3);
+        // Between the two equal samples the spline dips below them.
+        assert!(values[9] < 400.0);
+        assert_eq!(
+            ValueRange {
+                min: 0.0,
+                max: 1000.0
+            },
+            texture.value_range
+        );
+    }
+
+    #[test]
+    fn test_resample_keeps_sample_type() {
+        let texture = test_texture([1, 1, 2], Spacing::default(), |index| {
+            [-1000_i16, 1000][index]
+        });
+        let spacing = Spacing {
+            x: 1.0,
+            y: 1.0,
+            z: 0.25,
+        };
+        let volume = Volume::from_texture(texture).resample(spacing, Interpolation::Trilinear);
+
+        assert_eq!(
+            VoxelData::I16(vec![-1000, -500, 0, 500, 1000]),
+            volume.texture.texture_data
+        );
+    }
+
+    #[test]
+    fn test_fitting_spacing() {
+        let mut texture = ramp_texture();
+        texture.dimensions = Dim {
+            width: 1024,
+            height: 1024,
+            depth: 100,
+        };
+        texture.spacing = Spacing {
+            x: 0.5,
+            y: 0.5,
+            z: 5.0,
+        };
+        let spacing = fitting_spacing(&texture, isotropic_spacing(texture.spacing), usize::MAX);
+        let dimensions = resampled_dimensions(&texture, spacing);
+        let voxels =
+            dimensions.width as usize * dimensions.height as usize * dimensions.depth as usize;
+
+        assert!(voxels <= MAX_TEXTURE_VOXELS);
+        assert!(voxels > MAX_TEXTURE_VOXELS * 9 / 10);
+        assert_eq!(spacing.x, spacing.z);
+        assert_eq!(
+            texture.spacing,
+            fitting_spacing(&texture, texture.spacing, usize::MAX)
+        );
+        let spacing = fitting_spacing(&texture, texture.spacing, 512);
+        assert!(resampled_dimensions(&texture, spacing).width <= 512);
+    }
+
+    #[test]
+    fn test_cancel_resample() {
+        let progress = LoadProgress::default();
+        progress.cancel();
+        let result = resample_with_progress(
+            &ramp_texture(),
+            Spacing::default(),
+            Interpolation::Trilinear,
+            &progress,
+        );
+
+        assert!(matches!(result, Err(VolumeError::Cancelled)));
+    }
+}